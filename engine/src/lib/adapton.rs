@@ -1,8 +1,11 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use types::adapton::{
-    Action, Closure, Context, Edge, LogEvent, LogEventTag, LogEvents, Node, NodeId, Ref, Stack,
-    Store, Thunk,
+    Action, Agent, Closure, Context, Edge, LogEvent, LogEventTag, LogEvents, Node, NodeId, Ref,
+    Stack, Store, Thunk,
 };
-use types::lang::{Media, Name, Result as EvalResult};
+use types::lang::{Atom, Map, Media, Name, Result as EvalResult};
 
 // See also: Adapton in Motoko:
 // https://github.com/matthewhammer/cleansheets/blob/master/src/adapton.mo
@@ -14,103 +17,391 @@ mod algo {
     type RefNode = Ref;
     type Edges = Vec<Edge>;
 
-    #[allow(unused_variables, unused_mut)]
     pub fn dirty_ref(ctx: &mut Context, name: &Name, ref_node: &RefNode) {
-        unimplemented!()
+        begin_log_event(ctx);
+        for edge in ref_node.incoming.iter() {
+            dirty_edge(ctx, edge.clone())
+        }
+        end_log_event(ctx, LogEventTag::DirtyIncomingTo(name.clone()));
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn dirty_thunk(ctx: &mut Context, name: &Name, thunk_node: &ThunkNode) {
-        unimplemented!()
+        begin_log_event(ctx);
+        for edge in thunk_node.incoming.iter() {
+            dirty_edge(ctx, edge.clone())
+        }
+        end_log_event(ctx, LogEventTag::DirtyIncomingTo(name.clone()));
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn thunk_is_dirty(t: &ThunkNode) -> bool {
-        unimplemented!()
+        t.outgoing.iter().any(|e| e.dirty_flag)
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn add_edge(ctx: &mut Context, target: &NodeId, action: &Action) {
-        unimplemented!()
+        // only actions of thunks (not those of the editor) are recorded as edges:
+        let dependent = match (&ctx.agent, ctx.stack.0.last()) {
+            (Agent::Archivist, Some(name)) => NodeId { name: name.clone() },
+            _ => return,
+        };
+        ctx.edges.push(Edge {
+            dependent,
+            dependency: target.clone(),
+            checkpoint: action.clone(),
+            dirty_flag: false,
+        })
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn add_back_edges(ctx: &mut Context, edges: &Edges) {
-        unimplemented!()
+        for edge in edges.iter() {
+            add_back_edge(ctx, edge)
+        }
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn rem_back_edges(ctx: &mut Context, edges: &Edges) {
-        unimplemented!()
+        for edge in edges.iter() {
+            rem_back_edge(ctx, edge)
+        }
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn add_back_edge(ctx: &mut Context, edge: &Edge) {
-        unimplemented!()
+        let name = &edge.dependency.name;
+        match ctx.store.get_mut(name) {
+            Some(Node::Ref(r)) => r.incoming.push(edge.clone()),
+            Some(Node::Thunk(t)) => t.incoming.push(edge.clone()),
+            None => unreachable!(),
+        }
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn rem_back_edge(ctx: &mut Context, edge: &Edge) {
-        unimplemented!()
+        let name = &edge.dependency.name;
+        let dependent = &edge.dependent.name;
+        match ctx.store.get_mut(name) {
+            Some(Node::Ref(r)) => r.incoming.retain(|e| &e.dependent.name != dependent),
+            Some(Node::Thunk(t)) => t.incoming.retain(|e| &e.dependent.name != dependent),
+            None => {}
+        }
+    }
+
+    /// set the dirty flag of the edge(s) from dependent to dependency;
+    /// edges are copied into two nodes (as outgoing, and as incoming), so we update both copies.
+    fn set_edge_dirty_flag(ctx: &mut Context, edge: &Edge, flag: bool) {
+        let dependent = &edge.dependent.name;
+        let dependency = &edge.dependency.name;
+        if let Some(Node::Thunk(t)) = ctx.store.get_mut(dependent) {
+            for e in t.outgoing.iter_mut() {
+                if &e.dependency.name == dependency {
+                    e.dirty_flag = flag
+                }
+            }
+        };
+        let incoming = match ctx.store.get_mut(dependency) {
+            Some(Node::Ref(r)) => &mut r.incoming,
+            Some(Node::Thunk(t)) => &mut t.incoming,
+            None => return,
+        };
+        for e in incoming.iter_mut() {
+            if &e.dependent.name == dependent {
+                e.dirty_flag = flag
+            }
+        }
+    }
+
+    /// the current state of the edge, as saved by its dependent
+    fn current_edge(ctx: &Context, edge: &Edge) -> Option<Edge> {
+        match ctx.store.get(&edge.dependent.name) {
+            Some(Node::Thunk(t)) => t
+                .outgoing
+                .iter()
+                .find(|e| e.dependency.name == edge.dependency.name)
+                .cloned(),
+            _ => None,
+        }
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn clean_edge(ctx: &mut Context, edge: &Edge) -> bool {
-        unimplemented!()
+        if !edge.dirty_flag {
+            return true;
+        };
+        begin_log_event(ctx);
+        let node = ctx.store.get(&edge.dependency.name).cloned();
+        let is_clean = match (&edge.checkpoint, node) {
+            (Action::Put(media), Some(Node::Ref(r))) => media == &r.content,
+            (Action::Thunk(closure), Some(Node::Thunk(t))) => closure == &t.closure,
+            (Action::Get(res), Some(Node::Ref(r))) => res == &Ok(r.content),
+            (Action::Get(res), Some(Node::Thunk(t))) => {
+                let name = &edge.dependency.name;
+                let new_res = match t.result {
+                    Some(ref old_res) if !thunk_is_dirty(&t) || clean_thunk(ctx, name, &t) => {
+                        old_res.clone()
+                    }
+                    _ => match ctx.store.get(name).cloned() {
+                        Some(Node::Thunk(t)) => eval_thunk(ctx, name, &t),
+                        _ => unreachable!(),
+                    },
+                };
+                res == &new_res
+            }
+            (_, _) => false,
+        };
+        if is_clean {
+            set_edge_dirty_flag(ctx, edge, false)
+        };
+        end_log_event(
+            ctx,
+            LogEventTag::CleanEdgeTo(edge.dependency.name.clone(), is_clean),
+        );
+        is_clean
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn dirty_edge(ctx: &mut Context, edge: Edge) {
-        unimplemented!()
+        match current_edge(ctx, &edge) {
+            // already dirty, or no longer an edge of its dependent:
+            Some(ref e) if e.dirty_flag => {}
+            None => {}
+            Some(_) => {
+                set_edge_dirty_flag(ctx, &edge, true);
+                let name = &edge.dependent.name;
+                match ctx.store.get(name).cloned() {
+                    Some(Node::Thunk(t)) => dirty_thunk(ctx, name, &t),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    /// attempt to clean the dirty (outgoing) dependencies of the thunk, in order;
+    /// true means that its cached result is consistent again.
+    pub fn clean_thunk(ctx: &mut Context, name: &Name, thunk_node: &ThunkNode) -> bool {
+        begin_log_event(ctx);
+        for edge in thunk_node.outgoing.iter() {
+            if !clean_edge(ctx, edge) {
+                end_log_event(ctx, LogEventTag::CleanThunk(name.clone(), false));
+                return false;
+            }
+        }
+        end_log_event(ctx, LogEventTag::CleanThunk(name.clone(), true));
+        true
+    }
+
+    /// (re-)evaluate the thunk's closure, replacing its prior edges and cached result
+    pub fn eval_thunk(ctx: &mut Context, name: &Name, thunk_node: &ThunkNode) -> EvalResult {
+        begin_log_event(ctx);
+        rem_back_edges(ctx, &thunk_node.outgoing);
+        let saved_edges = std::mem::take(&mut ctx.edges);
+        let saved_agent = std::mem::replace(&mut ctx.agent, Agent::Archivist);
+        enter_scope(ctx, name.clone());
+        let closure = &thunk_node.closure;
+        let res = ::eval::eval_exp(&closure.env, ctx, &closure.exp);
+        leave_scope(ctx);
+        ctx.agent = saved_agent;
+        let edges = std::mem::replace(&mut ctx.edges, saved_edges);
+        // the thunk's incoming edges may have changed (e.g., been dirtied) during evaluation:
+        let incoming = match ctx.store.get(name) {
+            Some(Node::Thunk(t)) => t.incoming.clone(),
+            _ => thunk_node.incoming.clone(),
+        };
+        ctx.store.put(
+            name.clone(),
+            Node::Thunk(Thunk {
+                closure: closure.clone(),
+                result: Some(res.clone()),
+                outgoing: edges.clone(),
+                incoming,
+            }),
+        );
+        add_back_edges(ctx, &edges);
+        end_log_event(ctx, LogEventTag::EvalThunk(name.clone(), res.clone()));
+        res
     }
 }
 
 pub fn init() -> Context {
-    unimplemented!()
+    Context {
+        agent: Agent::Editor,
+        edges: vec![],
+        stack: Stack(vec![]),
+        store: Store(Map::new()),
+        log_buf: vec![],
+        log_stack: vec![],
+    }
 }
 
-#[allow(unused_variables, unused_mut)]
 pub fn put(ctx: &mut Context, name: Name, media: Media) -> Result<NodeId, PutError> {
-    unimplemented!()
+    check_put(ctx, &name)?;
+    begin_log_event(ctx);
+    let incoming = match ctx.store.get(&name).cloned() {
+        None => vec![],
+        Some(Node::Thunk(old)) => {
+            algo::dirty_thunk(ctx, &name, &old);
+            algo::rem_back_edges(ctx, &old.outgoing);
+            incoming_of(ctx, &name)
+        }
+        Some(Node::Ref(old)) => {
+            if old.content != media {
+                algo::dirty_ref(ctx, &name, &old);
+            };
+            incoming_of(ctx, &name)
+        }
+    };
+    ctx.store.put(
+        name.clone(),
+        Node::Ref(Ref {
+            content: media.clone(),
+            incoming,
+        }),
+    );
+    let id = NodeId { name: name.clone() };
+    algo::add_edge(ctx, &id, &Action::Put(media.clone()));
+    end_log_event(ctx, LogEventTag::Put(name, media));
+    Ok(id)
 }
 
-#[allow(unused_variables, unused_mut)]
 pub fn put_thunk(
     ctx: &mut Context,
     name: Option<Name>,
     closure: Closure,
 ) -> Result<NodeId, PutError> {
-    unimplemented!()
+    let name = match name {
+        Some(name) => name,
+        None => name_of_closure(&closure),
+    };
+    check_put(ctx, &name)?;
+    begin_log_event(ctx);
+    let new_thunk = |incoming| {
+        Node::Thunk(Thunk {
+            closure: closure.clone(),
+            result: None,
+            outgoing: vec![],
+            incoming,
+        })
+    };
+    match ctx.store.get(&name).cloned() {
+        None => {
+            ctx.store.put(name.clone(), new_thunk(vec![]));
+        }
+        Some(Node::Thunk(old)) => {
+            // an unchanged closure keeps its (possibly cached) thunk node
+            if old.closure != closure {
+                algo::dirty_thunk(ctx, &name, &old);
+                algo::rem_back_edges(ctx, &old.outgoing);
+                let incoming = incoming_of(ctx, &name);
+                ctx.store.put(name.clone(), new_thunk(incoming));
+            }
+        }
+        Some(Node::Ref(old)) => {
+            algo::dirty_ref(ctx, &name, &old);
+            let incoming = incoming_of(ctx, &name);
+            ctx.store.put(name.clone(), new_thunk(incoming));
+        }
+    };
+    let id = NodeId { name: name.clone() };
+    algo::add_edge(ctx, &id, &Action::Thunk(closure.clone()));
+    end_log_event(ctx, LogEventTag::PutThunk(name, closure));
+    Ok(id)
 }
 
-#[allow(unused_variables, unused_mut)]
+/// demand the content of the node; `name` labels this demand in the log.
 pub fn get(ctx: &mut Context, name: Name, node: NodeId) -> Result<EvalResult, GetError> {
-    unimplemented!()
+    let res = match ctx.store.get(&node.name).cloned() {
+        None => return Err(GetError::Undefined(node.name)),
+        Some(Node::Ref(r)) => {
+            begin_log_event(ctx);
+            Ok(r.content)
+        }
+        Some(Node::Thunk(t)) => {
+            if ctx.stack.0.contains(&node.name) {
+                return Err(GetError::Cycle(node.name));
+            };
+            begin_log_event(ctx);
+            match t.result {
+                Some(ref old_res)
+                    if !algo::thunk_is_dirty(&t) || algo::clean_thunk(ctx, &node.name, &t) =>
+                {
+                    old_res.clone()
+                }
+                _ => match ctx.store.get(&node.name).cloned() {
+                    Some(Node::Thunk(t)) => algo::eval_thunk(ctx, &node.name, &t),
+                    _ => unreachable!(),
+                },
+            }
+        }
+    };
+    algo::add_edge(ctx, &node, &Action::Get(res.clone()));
+    end_log_event(ctx, LogEventTag::Get(name, res.clone()));
+    Ok(res)
 }
 
-#[allow(unused_variables, unused_mut)]
+/// enter the evaluation scope of the named thunk;
+/// actions within this scope are recorded as the thunk's (outgoing) edges.
 pub fn enter_scope(ctx: &mut Context, name: Name) {
-    unimplemented!()
+    ctx.stack.push(name)
 }
 
-#[allow(unused_variables, unused_mut)]
 pub fn leave_scope(ctx: &mut Context) {
-    unimplemented!()
+    drop(ctx.stack.pop())
+}
+
+/// the structural name of an (anonymous) thunk, derived from its closure
+pub fn name_of_closure(closure: &Closure) -> Name {
+    let mut hasher = DefaultHasher::new();
+    closure.hash(&mut hasher);
+    Name::TaggedTuple(
+        Box::new(Name::Atom(Atom::String("thunk".to_string()))),
+        vec![Name::Atom(Atom::Usize(hasher.finish() as usize))],
+    )
+}
+
+/// a name may be put at most once per thunk evaluation, and never while its thunk is evaluating
+fn check_put(ctx: &Context, name: &Name) -> Result<(), PutError> {
+    if ctx.stack.0.contains(name) {
+        return Err(PutError::Active(name.clone()));
+    };
+    if let Agent::Archivist = ctx.agent {
+        let allocated = |e: &Edge| !matches!(e.checkpoint, Action::Get(_));
+        if ctx
+            .edges
+            .iter()
+            .any(|e| &e.dependency.name == name && allocated(e))
+        {
+            return Err(PutError::Ambiguous(name.clone()));
+        }
+    };
+    Ok(())
+}
+
+fn incoming_of(ctx: &Context, name: &Name) -> Vec<Edge> {
+    match ctx.store.get(name) {
+        Some(Node::Ref(r)) => r.incoming.clone(),
+        Some(Node::Thunk(t)) => t.incoming.clone(),
+        None => vec![],
+    }
 }
 
-pub enum PutError {}
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
+pub enum PutError {
+    /// the named thunk is evaluating, so it cannot be overwritten
+    Active(Name),
+    /// the current thunk has already put a node with this name
+    Ambiguous(Name),
+}
 
-pub enum GetError {}
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
+pub enum GetError {
+    /// no node has this name
+    Undefined(Name),
+    /// the thunk demands itself, transitively
+    Cycle(Name),
+}
 
 impl Store {
     fn put(&mut self, name: Name, node: Node) -> Option<Node> {
-        let prev = self.get(&name);
-        self.0.push((name, node));
-        prev
+        self.0.insert(name, node)
     }
-    #[allow(unused_variables, unused_mut)]
-    fn get(&self, name: &Name) -> Option<Node> {
-        unimplemented!()
+    fn get(&self, name: &Name) -> Option<&Node> {
+        self.0.get(name)
+    }
+    fn get_mut(&mut self, name: &Name) -> Option<&mut Node> {
+        self.0.get_mut(name)
     }
 }
 
@@ -133,7 +424,7 @@ fn log_event(tag: LogEventTag, body: LogEvents) -> LogEvent {
     match tag {
         LogEventTag::Put(n, m) => LogEvent::Put(n, m, body),
         LogEventTag::PutThunk(n, c) => LogEvent::PutThunk(n, c, body),
-        LogEventTag::Get(n, c) => LogEvent::Get(n, c, body),
+        LogEventTag::Get(n, r) => LogEvent::Get(n, r, body),
         LogEventTag::DirtyIncomingTo(n) => LogEvent::DirtyIncomingTo(n, body),
         LogEventTag::CleanEdgeTo(n, b) => LogEvent::CleanEdgeTo(n, b, body),
         LogEventTag::EvalThunk(n, r) => LogEvent::EvalThunk(n, r, body),
@@ -152,3 +443,104 @@ fn end_log_event(ctx: &mut Context, tag: LogEventTag) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get, init, put, put_thunk, PutError};
+    use types::adapton::{Closure, Context, Env, LogEvent, NodeId};
+    use types::lang::{Atom, Block, Error, Exp, Media, Name};
+    use types::util::name_of_str;
+
+    fn num(n: usize) -> Media {
+        Media::Atom(Atom::Usize(n))
+    }
+
+    fn closure(bindings: Vec<(Name, Exp)>) -> Closure {
+        Closure {
+            env: Env { bindings: vec![] },
+            exp: Exp::Block(Block { bindings }),
+        }
+    }
+
+    fn get_of(n: &str) -> Exp {
+        Exp::Get(Box::new(Exp::Name(name_of_str(n))))
+    }
+
+    fn demand(ctx: &mut Context, n: &str) -> Result<Media, Error> {
+        let name = name_of_str(n);
+        get(ctx, name.clone(), NodeId { name }).unwrap()
+    }
+
+    /// the names of the thunks (re-)evaluated by the logged events, in order
+    fn evaluated(events: &[LogEvent], out: &mut Vec<Name>) {
+        for event in events.iter() {
+            let body = match event {
+                LogEvent::EvalThunk(n, _, body) => {
+                    out.push(n.clone());
+                    body
+                }
+                LogEvent::Put(_, _, body)
+                | LogEvent::PutThunk(_, _, body)
+                | LogEvent::Get(_, _, body)
+                | LogEvent::DirtyIncomingTo(_, body)
+                | LogEvent::CleanEdgeTo(_, _, body)
+                | LogEvent::CleanThunk(_, _, body) => body,
+            };
+            evaluated(body, out)
+        }
+    }
+
+    #[test]
+    fn put_reevaluates_only_dependents() {
+        let mut ctx = init();
+        put(&mut ctx, name_of_str("a"), num(1)).unwrap();
+        put(&mut ctx, name_of_str("b"), num(2)).unwrap();
+        for (t, dep) in [("ta", "a"), ("tb", "b"), ("tc", "ta")].iter() {
+            let c = closure(vec![(name_of_str("x"), get_of(dep))]);
+            put_thunk(&mut ctx, Some(name_of_str(t)), c).unwrap();
+        }
+        assert_eq!(demand(&mut ctx, "tc"), Ok(num(1)));
+        assert_eq!(demand(&mut ctx, "tb"), Ok(num(2)));
+
+        ctx.log_buf.clear();
+        put(&mut ctx, name_of_str("a"), num(10)).unwrap();
+        assert_eq!(demand(&mut ctx, "tc"), Ok(num(10)));
+        assert_eq!(demand(&mut ctx, "tb"), Ok(num(2)));
+        let mut names = vec![];
+        evaluated(&ctx.log_buf, &mut names);
+        assert_eq!(names, vec![name_of_str("ta"), name_of_str("tc")]);
+
+        // puts replace nodes in place
+        assert_eq!(ctx.store.0.len(), 5);
+
+        // nothing changed, so nothing is re-evaluated
+        ctx.log_buf.clear();
+        put(&mut ctx, name_of_str("a"), num(10)).unwrap();
+        assert_eq!(demand(&mut ctx, "tc"), Ok(num(10)));
+        let mut names = vec![];
+        evaluated(&ctx.log_buf, &mut names);
+        assert_eq!(names, vec![]);
+    }
+
+    #[test]
+    fn put_errors() {
+        let mut ctx = init();
+        let put_exp = |n: &str| Exp::Put(name_of_str(n), Box::new(Exp::Atom(Atom::Usize(0))));
+        let active = closure(vec![(name_of_str("x"), put_exp("t"))]);
+        put_thunk(&mut ctx, Some(name_of_str("t")), active).unwrap();
+        assert_eq!(
+            demand(&mut ctx, "t"),
+            Err(Error::Put(PutError::Active(name_of_str("t"))))
+        );
+
+        let twice = closure(vec![
+            (name_of_str("x"), put_exp("r")),
+            (name_of_str("y"), put_exp("r")),
+        ]);
+        put_thunk(&mut ctx, Some(name_of_str("u")), twice).unwrap();
+        assert_eq!(
+            demand(&mut ctx, "u"),
+            Err(Error::Put(PutError::Ambiguous(name_of_str("r"))))
+        );
+    }
+}
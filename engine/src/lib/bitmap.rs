@@ -1,7 +1,11 @@
 // Serde: Persistent state between invocations of ZQM
 use serde::{Deserialize, Serialize};
 
-use types::lang::{Dir2D, Nat};
+use pixmap::Pixmap;
+use types::lang::{Dir2D, Name, Nat};
+
+/// the largest width (or height) that a bitmap may have
+pub const MAX_SIZE: usize = 4096;
 
 // Step 1:
 // -------
@@ -9,7 +13,7 @@ use types::lang::{Dir2D, Nat};
 // (no references or lifetimes; everything is affine, so no Rc<_>s either.)
 
 /// a grid of bits, represented as a 2D array
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub struct Bitmap {
     pub width: Nat,
     pub height: Nat,
@@ -18,7 +22,7 @@ pub struct Bitmap {
 }
 
 /// row-versus-column major order for grid representation
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum Major {
     /// row major ordering (rows indexed first, then columns)
     Row,
@@ -33,13 +37,71 @@ pub enum Major {
 
 /// commands that advance the state of the bitmap,
 /// whose execution is independent of editor state
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
 pub enum AutoCommand {
     /// toggle the bit at the given coordinate
     ToggleBit(Nat, Nat),
 
     /// set the bit at the given coordinate to the given Boolean value
     SetBit(Nat, Nat, bool),
+
+    /// resize to the given width and height, keeping the bits that remain
+    /// (anchored at the top-left corner); new bits are unset
+    Resize(Nat, Nat),
+
+    /// crop to the rectangle with the given top-left corner, width and height
+    Crop(Nat, Nat, Nat, Nat),
+
+    /// set the bits on the line between the two given coordinates (inclusive)
+    Line(Nat, Nat, Nat, Nat, bool),
+
+    /// set the bits on the outline of the rectangle with the given top-left corner, width and height
+    Rect(Nat, Nat, Nat, Nat, bool),
+
+    /// set the bits inside the rectangle with the given top-left corner, width and height
+    FillRect(Nat, Nat, Nat, Nat, bool),
+
+    /// set the bits on the outline of the ellipse inside the given rectangle
+    Ellipse(Nat, Nat, Nat, Nat, bool),
+
+    /// set the bits inside the ellipse inside the given rectangle
+    FillEllipse(Nat, Nat, Nat, Nat, bool),
+
+    /// set the bits of the region that is connected to the given coordinate
+    /// (horizontally or vertically), and whose bits agree with its bit
+    FloodFill(Nat, Nat, bool),
+
+    /// transform the whole bitmap
+    Transform(Transform),
+
+    /// transform the rectangle with the given top-left corner, width and height
+    TransformRect(Nat, Nat, Nat, Nat, Transform),
+
+    /// copy the given bitmap's bits, with its top-left corner at the given coordinate
+    /// (dropping any bits beyond the edges)
+    Paste(Nat, Nat, Bitmap),
+}
+
+/// transformations of a bitmap (or a rectangle within one)
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum Transform {
+    /// mirror the bits from left to right
+    FlipH,
+
+    /// mirror the bits from top to bottom
+    FlipV,
+
+    /// rotate the bits a quarter turn, clockwise
+    Rotate,
+
+    /// negate every bit
+    Invert,
+
+    /// move the bits one unit in a direction; the vacated bits are unset
+    Shift(Dir2D),
+
+    /// move the bits one unit in a direction; the bits that fall off one edge wrap to the other
+    Wrap(Dir2D),
 }
 
 // Step 3:
@@ -55,6 +117,12 @@ pub struct EditorState {
 
     /// initialized by an Init command; affected by Edit commands (but not Auto commands)
     pub cursor: (Nat, Nat),
+
+    /// if set, the selection is the rectangle between this anchor and the cursor (inclusive)
+    pub anchor: Option<(Nat, Nat)>,
+
+    /// the last copied (or cut) bits
+    pub clipboard: Option<Bitmap>,
 }
 
 // Step (3b) --
@@ -76,7 +144,7 @@ pub struct Editor {
 // Define commands that initialize the editor state.
 
 /// commands that create new bitmaps
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
 pub enum InitCommand {
     /// make a new 8x8 grid of bits
     Make8x8,
@@ -86,6 +154,18 @@ pub enum InitCommand {
 
     /// make a new 32x32 grid of bits
     Make32x32,
+
+    /// make a new grid of bits, with the given width and height
+    Make(Nat, Nat),
+
+    /// edit a copy of the given bitmap
+    Bitmap(Bitmap),
+
+    /// edit a copy of the named glyph, from the glyph map
+    Glyph(Name),
+
+    /// edit a monochrome copy of the given pixmap, whose set bits are its non-background pixels
+    Pixmap(Pixmap),
 }
 
 // Step 4b:
@@ -95,7 +175,7 @@ pub enum InitCommand {
 
 /// commands that advance the editor state,
 /// and possibly, its associated bitmap state.
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
 pub enum EditCommand {
     /// move the grid cursor one unit in a relative direction
     MoveRel(Dir2D),
@@ -105,6 +185,24 @@ pub enum EditCommand {
 
     /// toggle the bit at the cursor's grid position
     Toggle,
+
+    /// anchor the selection at the cursor's grid position (unless it is already anchored)
+    Anchor,
+
+    /// clear the selection anchor
+    Unanchor,
+
+    /// copy the selection (or the whole bitmap) to the clipboard
+    Copy,
+
+    /// copy the selection (or the whole bitmap) to the clipboard, and unset its bits
+    Cut,
+
+    /// paste the clipboard, with its top-left corner at the cursor's grid position
+    Paste,
+
+    /// transform the selection (or the whole bitmap)
+    Transform(Transform),
 }
 
 // Step 4c:
@@ -114,7 +212,7 @@ pub enum EditCommand {
 // and Edit sublanguages.
 
 /// commands that advance the evolution of a bitmap
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
 pub enum Command {
     /// commands that create new bitmaps
     Init(InitCommand),
@@ -140,8 +238,11 @@ pub enum Command {
 /// and the abstract syntax of its associated commands
 /// (independent from any IO library implementation details).
 pub mod semantics {
-    use super::{AutoCommand, Bitmap, Major};
+    use super::{AutoCommand, Bitmap, Major, Transform, MAX_SIZE};
     use super::{Command, Dir2D, EditCommand, Editor, EditorState, InitCommand};
+    use glyph;
+    use pixmap::semantics::{bitmap_of_pixmap, pixmap_check};
+    use types::lang::Error;
 
     fn bitmap_init(w: usize, h: usize) -> Bitmap {
         let row = vec![false; w];
@@ -154,6 +255,393 @@ pub mod semantics {
         }
     }
 
+    /// a new bitmap of unset bits, with the given width, height and major order
+    fn bitmap_init_major(w: usize, h: usize, major: &Major) -> Bitmap {
+        match major {
+            Major::Row => bitmap_init(w, h),
+            Major::Col => Bitmap {
+                width: w,
+                height: h,
+                major: Major::Col,
+                bits: vec![vec![false; h]; w],
+            },
+        }
+    }
+
+    /// check that the width and height are positive, and at most `MAX_SIZE`
+    /// (before making a bitmap of that size)
+    fn bitmap_check_size(w: usize, h: usize) -> Result<(), Error> {
+        if w == 0 || h == 0 {
+            Err(Error::Invalid(
+                "bitmap dimensions must be positive".to_string(),
+            ))
+        } else if w > MAX_SIZE || h > MAX_SIZE {
+            Err(Error::Invalid(format!(
+                "bitmap dimensions must be at most {}",
+                MAX_SIZE
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// check that the bitmap is non-empty (and not too large), and that its bits agree with its width, height and major order
+    pub fn bitmap_check(bitmap: &Bitmap) -> Result<(), Error> {
+        let (outer, inner) = match bitmap.major {
+            Major::Row => (bitmap.height, bitmap.width),
+            Major::Col => (bitmap.width, bitmap.height),
+        };
+        bitmap_check_size(bitmap.width, bitmap.height)?;
+        if bitmap.bits.len() != outer || bitmap.bits.iter().any(|v| v.len() != inner) {
+            Err(Error::Invalid(format!(
+                "bitmap bits do not match its {}x{} size",
+                bitmap.width, bitmap.height
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// the bitmap of the given width and height, holding the bits of the given one
+    /// (at the same positions, where they overlap)
+    pub fn bitmap_resize(bitmap: &mut Bitmap, w: usize, h: usize) -> Result<(), Error> {
+        bitmap_check_size(w, h)?;
+        let mut resized = bitmap_init_major(w, h, &bitmap.major);
+        for x in 0..w.min(bitmap.width) {
+            for y in 0..h.min(bitmap.height) {
+                bitmap_set_bit(&mut resized, x, y, bitmap_get_bit(bitmap, x, y))
+            }
+        }
+        *bitmap = resized;
+        Ok(())
+    }
+
+    /// check that the (non-empty) rectangle, with the given top-left corner, width and height, is within the bitmap
+    fn bitmap_check_rect(
+        bitmap: &Bitmap,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> Result<(), Error> {
+        if w == 0 || h == 0 {
+            Err(Error::Invalid(
+                "rectangle dimensions must be positive".to_string(),
+            ))
+        } else if x >= bitmap.width
+            || w > bitmap.width - x
+            || y >= bitmap.height
+            || h > bitmap.height - y
+        {
+            // the bottom-right corner (which may lie beyond any usize)
+            Err(Error::OutOfBounds(vec![
+                x.saturating_add(w - 1),
+                y.saturating_add(h - 1),
+            ]))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// the sub-bitmap with the given top-left corner, width and height
+    pub fn bitmap_crop(
+        bitmap: &mut Bitmap,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> Result<(), Error> {
+        *bitmap = bitmap_region(bitmap, x, y, w, h)?;
+        Ok(())
+    }
+
+    /// a copy of the rectangle with the given top-left corner, width and height
+    pub fn bitmap_region(
+        bitmap: &Bitmap,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> Result<Bitmap, Error> {
+        bitmap_check_rect(bitmap, x, y, w, h)?;
+        let mut region = bitmap_init_major(w, h, &bitmap.major);
+        for i in 0..w {
+            for j in 0..h {
+                bitmap_set_bit(&mut region, i, j, bitmap_get_bit(bitmap, x + i, y + j))
+            }
+        }
+        Ok(region)
+    }
+
+    /// copy the bits of the source, with its top-left corner at (x, y), dropping any bits beyond the edges
+    pub fn bitmap_paste(
+        bitmap: &mut Bitmap,
+        x: usize,
+        y: usize,
+        source: &Bitmap,
+    ) -> Result<(), Error> {
+        bitmap_check_rect(bitmap, x, y, 1, 1)?;
+        bitmap_check(source)?;
+        for i in 0..source.width.min(bitmap.width - x) {
+            for j in 0..source.height.min(bitmap.height - y) {
+                bitmap_set_bit(bitmap, x + i, y + j, bitmap_get_bit(source, i, j))
+            }
+        }
+        Ok(())
+    }
+
+    /// the transformed bitmap (in the same major order)
+    pub fn bitmap_transform(bitmap: &Bitmap, transform: &Transform) -> Bitmap {
+        let (w, h) = (bitmap.width, bitmap.height);
+        let get = |x: usize, y: usize| bitmap_get_bit(bitmap, x, y);
+        let (tw, th) = match transform {
+            Transform::Rotate => (h, w),
+            _ => (w, h),
+        };
+        let mut transformed = bitmap_init_major(tw, th, &bitmap.major);
+        for x in 0..tw {
+            for y in 0..th {
+                let b = match transform {
+                    Transform::FlipH => get(w - 1 - x, y),
+                    Transform::FlipV => get(x, h - 1 - y),
+                    Transform::Rotate => get(y, h - 1 - x),
+                    Transform::Invert => !get(x, y),
+                    Transform::Shift(dir) => match dir {
+                        Dir2D::Left => x + 1 < w && get(x + 1, y),
+                        Dir2D::Right => x > 0 && get(x - 1, y),
+                        Dir2D::Up => y + 1 < h && get(x, y + 1),
+                        Dir2D::Down => y > 0 && get(x, y - 1),
+                    },
+                    Transform::Wrap(dir) => match dir {
+                        Dir2D::Left => get((x + 1) % w, y),
+                        Dir2D::Right => get((x + w - 1) % w, y),
+                        Dir2D::Up => get(x, (y + 1) % h),
+                        Dir2D::Down => get(x, (y + h - 1) % h),
+                    },
+                };
+                bitmap_set_bit(&mut transformed, x, y, b)
+            }
+        }
+        transformed
+    }
+
+    /// transform the rectangle with the given top-left corner, width and height
+    /// (which must be square, to rotate it)
+    pub fn bitmap_transform_rect(
+        bitmap: &mut Bitmap,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        transform: &Transform,
+    ) -> Result<(), Error> {
+        if transform == &Transform::Rotate && w != h {
+            return Err(Error::Invalid(
+                "only a square rectangle can rotate in place".to_string(),
+            ));
+        };
+        let region = bitmap_region(bitmap, x, y, w, h)?;
+        bitmap_paste(bitmap, x, y, &bitmap_transform(&region, transform))
+    }
+
+    /// set the bits on the line from (x0, y0) to (x1, y1), using Bresenham's algorithm
+    pub fn bitmap_line(
+        bitmap: &mut Bitmap,
+        (x0, y0): (usize, usize),
+        (x1, y1): (usize, usize),
+        b: bool,
+    ) -> Result<(), Error> {
+        bitmap_check_rect(bitmap, x0.max(x1), y0.max(y1), 1, 1)?;
+        let (mut x, mut y) = (x0 as isize, y0 as isize);
+        let (x1, y1) = (x1 as isize, y1 as isize);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = (if x < x1 { 1 } else { -1 }, if y < y1 { 1 } else { -1 });
+        let mut err = dx + dy;
+        loop {
+            bitmap_set_bit(bitmap, x as usize, y as usize, b);
+            if x == x1 && y == y1 {
+                return Ok(());
+            };
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx
+            };
+            if e2 <= dx {
+                err += dx;
+                y += sy
+            };
+        }
+    }
+
+    /// set the bits on the outline (or, if filled, inside) of the rectangle
+    pub fn bitmap_rect(
+        bitmap: &mut Bitmap,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        filled: bool,
+        b: bool,
+    ) -> Result<(), Error> {
+        bitmap_check_rect(bitmap, x, y, w, h)?;
+        for i in x..x + w {
+            for j in y..y + h {
+                if filled || i == x || j == y || i == x + w - 1 || j == y + h - 1 {
+                    bitmap_set_bit(bitmap, i, j, b)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// set the bits on the outline (or, if filled, inside) of the ellipse inside the rectangle,
+    /// using Zingl's variant of Bresenham's algorithm (for ellipses of any width and height)
+    pub fn bitmap_ellipse(
+        bitmap: &mut Bitmap,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        filled: bool,
+        b: bool,
+    ) -> Result<(), Error> {
+        bitmap_check_rect(bitmap, x, y, w, h)?;
+        // set the bits of row y, from x0 to x1 (or, if not filled, just the two ends)
+        let mut span = |x0: i64, x1: i64, y: i64| {
+            let (x0, x1, y) = (x0 as usize, x1 as usize, y as usize);
+            if filled {
+                for x in x0..=x1 {
+                    bitmap_set_bit(bitmap, x, y, b)
+                }
+            } else {
+                bitmap_set_bit(bitmap, x0, y, b);
+                bitmap_set_bit(bitmap, x1, y, b)
+            }
+        };
+        let (a, bd) = (w as i64 - 1, h as i64 - 1);
+        let (mut x0, mut x1) = (x as i64, (x + w) as i64 - 1);
+        let odd = bd & 1;
+        let (mut dx, mut dy) = (4 * (1 - a) * bd * bd, 4 * (odd + 1) * a * a);
+        let mut err = dx + dy + odd * a * a;
+        let mut y0 = y as i64 + (bd + 1) / 2;
+        let mut y1 = y0 - odd;
+        let (a8, b8) = (8 * a * a, 8 * bd * bd);
+        loop {
+            span(x0, x1, y0);
+            span(x0, x1, y1);
+            let e2 = 2 * err;
+            if e2 <= dy {
+                y0 += 1;
+                y1 -= 1;
+                dy += a8;
+                err += dy
+            };
+            if e2 >= dx || 2 * err > dy {
+                x0 += 1;
+                x1 -= 1;
+                dx += b8;
+                err += dx
+            };
+            if x0 > x1 {
+                break;
+            }
+        }
+        // finish the tips of flat ellipses (of width one or two)
+        while y0 - y1 <= bd {
+            span(x0 - 1, x1 + 1, y0);
+            span(x0 - 1, x1 + 1, y1);
+            y0 += 1;
+            y1 -= 1
+        }
+        Ok(())
+    }
+
+    /// set the bits of the region that contains (x, y), and whose bits agree with its bit
+    pub fn bitmap_flood_fill(
+        bitmap: &mut Bitmap,
+        x: usize,
+        y: usize,
+        b: bool,
+    ) -> Result<(), Error> {
+        bitmap_check_rect(bitmap, x, y, 1, 1)?;
+        let old = bitmap_get_bit(bitmap, x, y);
+        if old == b {
+            return Ok(());
+        };
+        let mut todo = vec![(x, y)];
+        while let Some((x, y)) = todo.pop() {
+            if bitmap_get_bit(bitmap, x, y) != old {
+                continue;
+            };
+            bitmap_set_bit(bitmap, x, y, b);
+            if x > 0 {
+                todo.push((x - 1, y))
+            };
+            if y > 0 {
+                todo.push((x, y - 1))
+            };
+            if x + 1 < bitmap.width {
+                todo.push((x + 1, y))
+            };
+            if y + 1 < bitmap.height {
+                todo.push((x, y + 1))
+            };
+        }
+        Ok(())
+    }
+
+    /// the bitmap that the command creates
+    fn bitmap_of_init(command: &InitCommand) -> Result<Bitmap, Error> {
+        match command {
+            &InitCommand::Make8x8 => Ok(bitmap_init(8, 8)),
+            &InitCommand::Make16x16 => Ok(bitmap_init(16, 16)),
+            &InitCommand::Make32x32 => Ok(bitmap_init(32, 32)),
+            &InitCommand::Make(w, h) => {
+                bitmap_check_size(w, h)?;
+                Ok(bitmap_init(w, h))
+            }
+            InitCommand::Bitmap(bitmap) => {
+                bitmap_check(bitmap)?;
+                Ok(bitmap.clone())
+            }
+            InitCommand::Glyph(name) => match glyph::cap5x5::glyph_map().remove(name) {
+                Some(bitmap) => Ok(bitmap),
+                None => Err(Error::AbsentName(name.clone())),
+            },
+            InitCommand::Pixmap(pixmap) => {
+                pixmap_check(pixmap)?;
+                Ok(bitmap_of_pixmap(pixmap))
+            }
+        }
+    }
+
+    /// keep the cursor (and selection anchor) within the bitmap, e.g., after resizing or cropping it
+    fn clamp_cursor(editor: &mut EditorState) {
+        let (w, h) = (editor.bitmap.width, editor.bitmap.height);
+        let (x, y) = editor.cursor;
+        editor.cursor = (x.min(w - 1), y.min(h - 1));
+        editor.anchor = editor.anchor.map(|(x, y)| (x.min(w - 1), y.min(h - 1)));
+    }
+
+    /// the selection's top-left corner, width and height
+    pub fn selection_rect(editor: &EditorState) -> Option<(usize, usize, usize, usize)> {
+        editor.anchor.map(|(ax, ay)| {
+            let (cx, cy) = editor.cursor;
+            (
+                ax.min(cx),
+                ay.min(cy),
+                ax.max(cx) - ax.min(cx) + 1,
+                ay.max(cy) - ay.min(cy) + 1,
+            )
+        })
+    }
+
+    /// the selection (or the whole bitmap), as a rectangle
+    fn selection_or_all(editor: &EditorState) -> (usize, usize, usize, usize) {
+        selection_rect(editor).unwrap_or((0, 0, editor.bitmap.width, editor.bitmap.height))
+    }
+
     pub fn bitmap_set_bit(bitmap: &mut Bitmap, x: usize, y: usize, b: bool) {
         match bitmap.major {
             Major::Row => bitmap.bits[y][x] = b,
@@ -189,25 +677,44 @@ pub mod semantics {
     // 4. The successful return value could be `Media` (the full adjunction), or another trait param.
     // 5. The failure error code could be a common error type (every possible error), or a trait param.
 
-    pub fn bitmap_eval(bitmap: &mut Bitmap, command: &AutoCommand) -> Result<(), String> {
+    pub fn bitmap_eval(bitmap: &mut Bitmap, command: &AutoCommand) -> Result<(), Error> {
         debug!("bitmap_eval {:?}", command);
         let res = match command {
+            &AutoCommand::ToggleBit(x, y) | &AutoCommand::SetBit(x, y, _)
+                if x >= bitmap.width || y >= bitmap.height =>
+            {
+                Err(Error::OutOfBounds(vec![x, y]))
+            }
             &AutoCommand::ToggleBit(x, y) => {
                 bitmap_toggle_bit(bitmap, x, y);
+                Ok(())
             } // to do: actually return the bit.
             &AutoCommand::SetBit(x, y, b) => {
                 bitmap_set_bit(bitmap, x, y, b);
+                Ok(())
+            }
+            &AutoCommand::Resize(w, h) => bitmap_resize(bitmap, w, h),
+            &AutoCommand::Crop(x, y, w, h) => bitmap_crop(bitmap, x, y, w, h),
+            &AutoCommand::Line(x0, y0, x1, y1, b) => bitmap_line(bitmap, (x0, y0), (x1, y1), b),
+            &AutoCommand::Rect(x, y, w, h, b) => bitmap_rect(bitmap, x, y, w, h, false, b),
+            &AutoCommand::FillRect(x, y, w, h, b) => bitmap_rect(bitmap, x, y, w, h, true, b),
+            &AutoCommand::Ellipse(x, y, w, h, b) => bitmap_ellipse(bitmap, x, y, w, h, false, b),
+            &AutoCommand::FillEllipse(x, y, w, h, b) => bitmap_ellipse(bitmap, x, y, w, h, true, b),
+            &AutoCommand::FloodFill(x, y, b) => bitmap_flood_fill(bitmap, x, y, b),
+            AutoCommand::Transform(transform) => {
+                *bitmap = bitmap_transform(bitmap, transform);
+                Ok(())
+            }
+            &AutoCommand::TransformRect(x, y, w, h, ref transform) => {
+                bitmap_transform_rect(bitmap, x, y, w, h, transform)
             }
+            &AutoCommand::Paste(x, y, ref source) => bitmap_paste(bitmap, x, y, source),
         };
-        let res = Ok(res);
         debug!("bitmap_eval {:?} ==> {:?}", command, res);
         res
     }
 
-    pub fn editor_state_eval(
-        editor: &mut EditorState,
-        command: &EditCommand,
-    ) -> Result<(), String> {
+    pub fn editor_state_eval(editor: &mut EditorState, command: &EditCommand) -> Result<(), Error> {
         debug!("editor_state_eval {:?}", command);
         let res = match command {
             &EditCommand::MoveRel(ref dir) => {
@@ -226,7 +733,7 @@ pub mod semantics {
                     editor.cursor = (x, y);
                     Ok(())
                 } else {
-                    Err("MoveAbs: invalid coordinate".to_string())
+                    Err(Error::OutOfBounds(vec![x, y]))
                 }
             }
             &EditCommand::Toggle => {
@@ -234,35 +741,73 @@ pub mod semantics {
                 let _ = bitmap_toggle_bit(&mut editor.bitmap, x, y);
                 Ok(())
             }
+            &EditCommand::Anchor => {
+                if editor.anchor.is_none() {
+                    editor.anchor = Some(editor.cursor)
+                };
+                Ok(())
+            }
+            &EditCommand::Unanchor => {
+                editor.anchor = None;
+                Ok(())
+            }
+            &EditCommand::Copy | &EditCommand::Cut => {
+                let (x, y, w, h) = selection_or_all(editor);
+                editor.clipboard = Some(bitmap_region(&editor.bitmap, x, y, w, h)?);
+                if command == &EditCommand::Cut {
+                    bitmap_rect(&mut editor.bitmap, x, y, w, h, true, false)?
+                };
+                Ok(())
+            }
+            &EditCommand::Paste => match editor.clipboard {
+                Some(ref clip) => {
+                    let (x, y) = editor.cursor;
+                    bitmap_paste(&mut editor.bitmap, x, y, clip)
+                }
+                None => Err(Error::Invalid("nothing to paste".to_string())),
+            },
+            EditCommand::Transform(transform) => match selection_rect(editor) {
+                Some((x, y, w, h)) => {
+                    bitmap_transform_rect(&mut editor.bitmap, x, y, w, h, transform)
+                }
+                None => {
+                    editor.bitmap = bitmap_transform(&editor.bitmap, transform);
+                    clamp_cursor(editor);
+                    Ok(())
+                }
+            },
         };
         debug!("editor_state_eval {:?} ==> {:?}", command, res);
         res
     }
 
-    pub fn editor_eval(editor: &mut Editor, command: &Command) -> Result<(), String> {
+    pub fn editor_eval(editor: &mut Editor, command: &Command) -> Result<(), Error> {
         let num = editor.history.len();
         debug!("#{}: editor_eval {:?}", num, command);
         // save the command in the history
         editor.history.push(command.clone());
         // evaluate the command in the appropriate evaluation context:
         let res = match command {
-            &Command::Init(ref command) => {
+            &Command::Init(ref command) => bitmap_of_init(command).map(|bitmap| {
+                // keep the clipboard, to paste into the new bitmap
+                let clipboard = editor.state.take().and_then(|st| st.clipboard);
                 editor.state = Some(EditorState {
-                    bitmap: match command {
-                        &InitCommand::Make8x8 => bitmap_init(8, 8),
-                        &InitCommand::Make16x16 => bitmap_init(16, 16),
-                        &InitCommand::Make32x32 => bitmap_init(32, 32),
-                    },
+                    bitmap,
                     cursor: (0, 0),
-                });
-                Ok(())
-            }
+                    anchor: None,
+                    clipboard,
+                })
+            }),
             &Command::Auto(ref command) => match editor.state {
-                None => Err("Invalid editor state".to_string()),
-                Some(ref mut st) => bitmap_eval(&mut st.bitmap, &command),
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => {
+                    bitmap_eval(&mut st.bitmap, &command)?;
+                    clamp_cursor(st);
+                    Ok(())
+                }
             },
             &Command::Edit(ref command) => match editor.state {
-                None => Err("Invalid editor state".to_string()),
+                None => Err(Error::NoEditorState),
                 Some(ref mut st) => editor_state_eval(st, &command),
             },
         };
@@ -277,22 +822,43 @@ pub mod semantics {
 // Define the IO for the Editor using the abstract `render` module, and associated types.
 
 pub mod io {
-    use super::{Dir2D, EditCommand, EditorState};
-    use types::event::Event;
+    use super::{Dir2D, EditCommand, EditorState, Transform};
+    use types::event::{Event, Quit};
     use types::render::{self, Color, Fill, Rect};
 
-    pub fn edit_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, ()> {
+    pub fn edit_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, Quit> {
         match event {
-            &Event::Quit { .. } => Err(()),
-            &Event::KeyDown(ref kei) => match kei.key.as_str() {
-                "Escape" => Err(()),
-                " " => Ok(vec![EditCommand::Toggle]),
-                "ArrowLeft" => Ok(vec![EditCommand::MoveRel(Dir2D::Left)]),
-                "ArrowRight" => Ok(vec![EditCommand::MoveRel(Dir2D::Right)]),
-                "ArrowUp" => Ok(vec![EditCommand::MoveRel(Dir2D::Up)]),
-                "ArrowDown" => Ok(vec![EditCommand::MoveRel(Dir2D::Down)]),
-                _ => Ok(vec![]),
-            },
+            &Event::Quit { .. } => Err(Quit),
+            &Event::KeyDown(ref kei) => {
+                let dir = match kei.key.as_str() {
+                    "ArrowLeft" => Some(Dir2D::Left),
+                    "ArrowRight" => Some(Dir2D::Right),
+                    "ArrowUp" => Some(Dir2D::Up),
+                    "ArrowDown" => Some(Dir2D::Down),
+                    _ => None,
+                };
+                match (kei.key.as_str(), dir) {
+                    // arrows: with control, move the bits (wrapping, unless with shift too);
+                    // with shift, extend the selection
+                    (_, Some(dir)) => Ok(match (kei.ctrl, kei.shift) {
+                        (true, true) => vec![EditCommand::Transform(Transform::Shift(dir))],
+                        (true, false) => vec![EditCommand::Transform(Transform::Wrap(dir))],
+                        (false, true) => vec![EditCommand::Anchor, EditCommand::MoveRel(dir)],
+                        (false, false) => vec![EditCommand::MoveRel(dir)],
+                    }),
+                    ("Escape", _) => Err(Quit),
+                    ("c", _) if kei.ctrl => Ok(vec![EditCommand::Copy]),
+                    ("x", _) if kei.ctrl => Ok(vec![EditCommand::Cut]),
+                    ("v", _) if kei.ctrl => Ok(vec![EditCommand::Paste]),
+                    (" ", _) => Ok(vec![EditCommand::Toggle]),
+                    ("u", _) => Ok(vec![EditCommand::Unanchor]),
+                    ("h", _) => Ok(vec![EditCommand::Transform(Transform::FlipH)]),
+                    ("v", _) => Ok(vec![EditCommand::Transform(Transform::FlipV)]),
+                    ("r", _) => Ok(vec![EditCommand::Transform(Transform::Rotate)]),
+                    ("i", _) => Ok(vec![EditCommand::Transform(Transform::Invert)]),
+                    _ => Ok(vec![]),
+                }
+            }
             _ => Ok(vec![]),
         }
     }
@@ -312,6 +878,7 @@ pub mod io {
 
         let grid_border_color = Color::RGB(100, 80, 100);
         let cursor_border_color = Color::RGB(150, 255, 150);
+        let selection_border_color = Color::RGB(150, 150, 255);
 
         fn get_cell_color(is_set: bool, is_focus: bool) -> Color {
             // to do -- get these constants from the editor state
@@ -338,6 +905,18 @@ pub mod io {
         // grid border is a single background rect:
         let grid_rect = Rect::new(0, 0, width * cell_width, height * cell_width);
         render.rect(&grid_rect, Fill::Closed(grid_border_color.clone()));
+        if let Some((x, y, w, h)) = super::semantics::selection_rect(edit_state) {
+            let selection_rect = Rect::new(
+                (x * cell_width) as isize,
+                (y * cell_width) as isize,
+                w * cell_width,
+                h * cell_width,
+            );
+            render.rect(
+                &selection_rect,
+                Fill::Closed(selection_border_color.clone()),
+            );
+        };
         render.rect(&cursor_rect, Fill::Closed(cursor_border_color.clone()));
 
         // grid cells are rects:
@@ -359,3 +938,444 @@ pub mod io {
         Ok(render.into_elms())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::semantics::{
+        bitmap_check, bitmap_eval, bitmap_get_bit, bitmap_set_bit, editor_eval,
+    };
+    use super::{AutoCommand, Bitmap, Command, EditCommand, Editor, InitCommand, Major};
+    use super::{Transform, MAX_SIZE};
+    use pixmap::Pixmap;
+    use types::lang::{Dir2D, Error};
+    use types::render::Color;
+    use types::util::name_of_string;
+
+    /// the (row major) bitmap whose rows are drawn with `#` for a set bit, and `.` for an unset one
+    fn bitmap(rows: &[&str]) -> Bitmap {
+        Bitmap {
+            width: rows[0].len(),
+            height: rows.len(),
+            major: Major::Row,
+            bits: rows
+                .iter()
+                .map(|row| row.chars().map(|c| c == '#').collect())
+                .collect(),
+        }
+    }
+
+    /// the same bits, in column major order
+    fn col(bitmap: &Bitmap) -> Bitmap {
+        let mut col = Bitmap {
+            width: bitmap.width,
+            height: bitmap.height,
+            major: Major::Col,
+            bits: vec![vec![false; bitmap.height]; bitmap.width],
+        };
+        for x in 0..bitmap.width {
+            for y in 0..bitmap.height {
+                bitmap_set_bit(&mut col, x, y, bitmap_get_bit(bitmap, x, y))
+            }
+        }
+        col
+    }
+
+    /// the rows of the bitmap, drawn as `bitmap` reads them (in either major order)
+    fn rows(bitmap: &Bitmap) -> Vec<String> {
+        (0..bitmap.height)
+            .map(|y| {
+                (0..bitmap.width)
+                    .map(|x| {
+                        if bitmap_get_bit(bitmap, x, y) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// an editor, initialized by the command
+    fn editor(init: InitCommand) -> Result<Editor, Error> {
+        let mut ed = Editor {
+            history: vec![],
+            state: None,
+        };
+        editor_eval(&mut ed, &Command::Init(init))?;
+        Ok(ed)
+    }
+
+    fn edited(ed: &Editor) -> &Bitmap {
+        &ed.state.as_ref().unwrap().bitmap
+    }
+
+    #[test]
+    fn init_commands() {
+        let ed = editor(InitCommand::Make16x16).unwrap();
+        assert_eq!((edited(&ed).width, edited(&ed).height), (16, 16));
+        let ed = editor(InitCommand::Make(3, 2)).unwrap();
+        assert_eq!(rows(edited(&ed)), vec!["...", "..."]);
+        assert_eq!(bitmap_check(edited(&ed)), Ok(()));
+        // too small, or too large to make
+        for (w, h) in [
+            (0, 2),
+            (2, 0),
+            (MAX_SIZE + 1, 1),
+            (1, usize::MAX),
+            (usize::MAX, usize::MAX),
+        ] {
+            assert!(matches!(
+                editor(InitCommand::Make(w, h)),
+                Err(Error::Invalid(_))
+            ));
+        }
+
+        let b = bitmap(&["#..", ".#."]);
+        assert_eq!(edited(&editor(InitCommand::Bitmap(b.clone())).unwrap()), &b);
+        assert_eq!(
+            edited(&editor(InitCommand::Bitmap(col(&b))).unwrap()),
+            &col(&b)
+        );
+        let mut bad = b;
+        bad.bits[1].pop();
+        assert!(matches!(
+            editor(InitCommand::Bitmap(bad)),
+            Err(Error::Invalid(_))
+        ));
+        bad = bitmap(&["#"]);
+        bad.major = Major::Col;
+        bad.width = 2;
+        assert!(matches!(
+            editor(InitCommand::Bitmap(bad)),
+            Err(Error::Invalid(_))
+        ));
+
+        let one = editor(InitCommand::Glyph(name_of_string("1".to_string()))).unwrap();
+        assert_eq!(rows(edited(&one))[0], ".##..");
+        let absent = name_of_string("no such glyph".to_string());
+        assert_eq!(
+            editor(InitCommand::Glyph(absent.clone())).err(),
+            Some(Error::AbsentName(absent))
+        );
+
+        // any color but the background is a set bit
+        let mut pixmap = Pixmap {
+            width: 3,
+            height: 1,
+            palette: vec![
+                Color::RGB(0, 0, 0),
+                Color::RGB(9, 9, 9),
+                Color::RGB(255, 0, 0),
+            ],
+            pixels: vec![vec![2, 0, 1]],
+        };
+        let ed = editor(InitCommand::Pixmap(pixmap.clone())).unwrap();
+        assert_eq!(rows(edited(&ed)), vec!["#.#"]);
+        pixmap.pixels[0][1] = 3;
+        assert_eq!(
+            editor(InitCommand::Pixmap(pixmap)).err(),
+            Some(Error::OutOfBounds(vec![3]))
+        );
+    }
+
+    #[test]
+    fn resize_keeps_the_overlap() {
+        for b in [bitmap(&["#..", ".##"]), col(&bitmap(&["#..", ".##"]))] {
+            let mut grown = b.clone();
+            bitmap_eval(&mut grown, &AutoCommand::Resize(4, 3)).unwrap();
+            assert_eq!(rows(&grown), vec!["#...", ".##.", "...."]);
+            assert_eq!(
+                (grown.major.clone(), bitmap_check(&grown)),
+                (b.major.clone(), Ok(()))
+            );
+            let mut shrunk = b.clone();
+            bitmap_eval(&mut shrunk, &AutoCommand::Resize(2, 1)).unwrap();
+            assert_eq!(rows(&shrunk), vec!["#."]);
+            assert_eq!(bitmap_check(&shrunk), Ok(()));
+            for (w, h) in [(0, 1), (MAX_SIZE + 1, 1), (usize::MAX, usize::MAX)] {
+                let mut resized = b.clone();
+                let res = bitmap_eval(&mut resized, &AutoCommand::Resize(w, h));
+                assert!(matches!(res, Err(Error::Invalid(_))));
+                assert_eq!(resized, b);
+            }
+        }
+
+        // the cursor stays within the bitmap
+        let mut ed = editor(InitCommand::Make(4, 4)).unwrap();
+        ed.state.as_mut().unwrap().cursor = (3, 2);
+        editor_eval(&mut ed, &Command::Auto(AutoCommand::Resize(2, 3))).unwrap();
+        assert_eq!(ed.state.as_ref().unwrap().cursor, (1, 2));
+    }
+
+    #[test]
+    fn crop_within_bounds() {
+        for b in [bitmap(&["#..", ".##"]), col(&bitmap(&["#..", ".##"]))] {
+            let mut cropped = b.clone();
+            bitmap_eval(&mut cropped, &AutoCommand::Crop(1, 0, 2, 2)).unwrap();
+            assert_eq!(rows(&cropped), vec!["..", "##"]);
+            assert_eq!(
+                (cropped.major.clone(), bitmap_check(&cropped)),
+                (b.major.clone(), Ok(()))
+            );
+            let mut all = b.clone();
+            bitmap_eval(&mut all, &AutoCommand::Crop(0, 0, 3, 2)).unwrap();
+            assert_eq!(all, b);
+
+            let mut cropped = b.clone();
+            let mut crop = |x, y, w, h| bitmap_eval(&mut cropped, &AutoCommand::Crop(x, y, w, h));
+            assert_eq!(crop(2, 0, 2, 1), Err(Error::OutOfBounds(vec![3, 0])));
+            assert_eq!(crop(0, 2, 1, 1), Err(Error::OutOfBounds(vec![0, 2])));
+            assert!(matches!(crop(0, 0, 0, 1), Err(Error::Invalid(_))));
+            // without overflowing
+            let max = usize::MAX;
+            assert_eq!(crop(max, 0, 2, 1), Err(Error::OutOfBounds(vec![max, 0])));
+            assert_eq!(
+                crop(1, 1, max, max),
+                Err(Error::OutOfBounds(vec![max, max]))
+            );
+            assert_eq!(cropped, b);
+        }
+        let mut b = bitmap(&["..", ".."]);
+        let fill = AutoCommand::FillRect(1, 1, usize::MAX, 1, true);
+        assert_eq!(
+            bitmap_eval(&mut b, &fill),
+            Err(Error::OutOfBounds(vec![usize::MAX, 1]))
+        );
+        assert_eq!(rows(&b), vec!["..", ".."]);
+    }
+
+    /// the rows of a blank bitmap of the given size, after the (drawing) command
+    fn drawn(w: usize, h: usize, command: AutoCommand) -> Vec<String> {
+        let mut b = bitmap(&vec![&*".".repeat(w); h]);
+        bitmap_eval(&mut b, &command).unwrap();
+        rows(&b)
+    }
+
+    #[test]
+    fn lines() {
+        let line = |x0, y0, x1, y1| drawn(5, 4, AutoCommand::Line(x0, y0, x1, y1, true));
+        assert_eq!(line(0, 0, 4, 2), vec!["#....", ".##..", "...##", "....."]);
+        // the same line, drawn from the other end, which takes its steps in the other order
+        assert_eq!(line(4, 2, 0, 0), vec!["##...", "..##.", "....#", "....."]);
+        assert_eq!(line(0, 3, 3, 0), vec!["...#.", "..#..", ".#...", "#...."]);
+        assert_eq!(line(1, 0, 1, 3), vec![".#...", ".#...", ".#...", ".#..."]);
+        assert_eq!(line(4, 1, 0, 1), vec![".....", "#####", ".....", "....."]);
+        assert_eq!(line(2, 2, 2, 2), vec![".....", ".....", "..#..", "....."]);
+        // unset bits, too
+        let mut b = bitmap(&["###", "###"]);
+        bitmap_eval(&mut b, &AutoCommand::Line(0, 0, 2, 1, false)).unwrap();
+        assert_eq!(rows(&b), vec![".##", "#.."]);
+        let res = bitmap_eval(&mut b, &AutoCommand::Line(0, 0, 3, 1, true));
+        assert_eq!(res, Err(Error::OutOfBounds(vec![3, 1])));
+        assert_eq!(rows(&b), vec![".##", "#.."]);
+    }
+
+    #[test]
+    fn rects() {
+        let rect = |x, y, w, h| drawn(5, 4, AutoCommand::Rect(x, y, w, h, true));
+        assert_eq!(rect(1, 0, 4, 4), vec![".####", ".#..#", ".#..#", ".####"]);
+        assert_eq!(rect(0, 1, 3, 2), vec![".....", "###..", "###..", "....."]);
+        assert_eq!(rect(2, 1, 1, 1), vec![".....", "..#..", ".....", "....."]);
+        let fill = |x, y, w, h| drawn(5, 4, AutoCommand::FillRect(x, y, w, h, true));
+        assert_eq!(fill(1, 1, 3, 3), vec![".....", ".###.", ".###.", ".###."]);
+        let mut b = bitmap(&["####", "####", "####"]);
+        bitmap_eval(&mut b, &AutoCommand::FillRect(1, 0, 2, 3, false)).unwrap();
+        assert_eq!(rows(&b), vec!["#..#", "#..#", "#..#"]);
+        let res = bitmap_eval(&mut b, &AutoCommand::Rect(1, 1, 4, 1, true));
+        assert_eq!(res, Err(Error::OutOfBounds(vec![4, 1])));
+    }
+
+    #[test]
+    fn ellipses() {
+        let ellipse = |w, h| drawn(w, h, AutoCommand::Ellipse(0, 0, w, h, true));
+        let filled = |w, h| drawn(w, h, AutoCommand::FillEllipse(0, 0, w, h, true));
+        // flat ellipses fill their rectangles
+        for (w, h) in [(1, 1), (1, 4), (4, 1), (2, 5), (5, 2), (2, 2)] {
+            let all = vec!["#".repeat(w); h];
+            assert_eq!((ellipse(w, h), filled(w, h)), (all.clone(), all));
+        }
+        // even and odd sizes
+        assert_eq!(ellipse(3, 3), vec![".#.", "#.#", ".#."]);
+        assert_eq!(filled(3, 3), vec![".#.", "###", ".#."]);
+        assert_eq!(ellipse(4, 4), vec![".##.", "#..#", "#..#", ".##."]);
+        assert_eq!(filled(4, 4), vec![".##.", "####", "####", ".##."]);
+        assert_eq!(
+            ellipse(5, 5),
+            vec![".###.", "#...#", "#...#", "#...#", ".###."]
+        );
+        assert_eq!(
+            ellipse(7, 5),
+            vec!["..###..", ".#...#.", "#.....#", ".#...#.", "..###.."]
+        );
+        assert_eq!(
+            filled(7, 5),
+            vec!["..###..", ".#####.", "#######", ".#####.", "..###.."]
+        );
+        assert_eq!(ellipse(6, 4), vec![".####.", "#....#", "#....#", ".####."]);
+        assert_eq!(ellipse(8, 3), vec!["..####..", "##....##", "..####.."]);
+        // within a rectangle that is not at the corner
+        assert_eq!(
+            drawn(6, 5, AutoCommand::Ellipse(2, 1, 3, 3, true)),
+            vec!["......", "...#..", "..#.#.", "...#..", "......"]
+        );
+    }
+
+    #[test]
+    fn flood_fill_stops_at_walls() {
+        let walled = || bitmap(&["..#..", "..#..", "###..", "....."]);
+        let mut b = walled();
+        bitmap_eval(&mut b, &AutoCommand::FloodFill(0, 0, true)).unwrap();
+        assert_eq!(rows(&b), vec!["###..", "###..", "###..", "....."]);
+        // not diagonally, through the corner of the wall
+        let mut b = walled();
+        bitmap_eval(&mut b, &AutoCommand::FloodFill(4, 0, true)).unwrap();
+        assert_eq!(rows(&b), vec!["..###", "..###", "#####", "#####"]);
+        // the wall itself, set or unset
+        let mut b = col(&walled());
+        bitmap_eval(&mut b, &AutoCommand::FloodFill(2, 1, false)).unwrap();
+        assert_eq!(rows(&b), vec![".....", ".....", ".....", "....."]);
+        let mut b = walled();
+        bitmap_eval(&mut b, &AutoCommand::FloodFill(2, 0, true)).unwrap();
+        assert_eq!(b, walled());
+        let res = bitmap_eval(&mut b, &AutoCommand::FloodFill(5, 0, true));
+        assert_eq!(res, Err(Error::OutOfBounds(vec![5, 0])));
+    }
+
+    #[test]
+    fn each_drawing_command_is_one_history_entry() {
+        let mut ed = editor(InitCommand::Make(4, 4)).unwrap();
+        let commands = [
+            AutoCommand::SetBit(1, 1, true),
+            AutoCommand::ToggleBit(2, 2),
+            AutoCommand::Line(0, 0, 3, 3, true),
+            AutoCommand::Rect(0, 0, 4, 4, true),
+            AutoCommand::FillRect(1, 1, 2, 2, false),
+            AutoCommand::Ellipse(0, 0, 4, 4, false),
+            AutoCommand::FillEllipse(0, 0, 3, 3, true),
+            AutoCommand::FloodFill(3, 3, true),
+            // failures, too
+            AutoCommand::Line(0, 0, 4, 4, true),
+            AutoCommand::Ellipse(0, 0, 0, 4, true),
+        ];
+        for (i, command) in commands.iter().enumerate() {
+            let command = Command::Auto(command.clone());
+            let _ = editor_eval(&mut ed, &command);
+            assert_eq!(ed.history.len(), i + 2);
+            assert_eq!(ed.history.last(), Some(&command));
+        }
+    }
+
+    /// the rows of the bitmap after the transform, which keeps its major order
+    fn transformed(b: &Bitmap, transform: Transform) -> Vec<String> {
+        let mut b = b.clone();
+        let major = b.major.clone();
+        bitmap_eval(&mut b, &AutoCommand::Transform(transform)).unwrap();
+        assert_eq!((b.major.clone(), bitmap_check(&b)), (major, Ok(())));
+        rows(&b)
+    }
+
+    #[test]
+    fn rotate_and_flip() {
+        for b in [bitmap(&["##.", "..#"]), col(&bitmap(&["##.", "..#"]))] {
+            // clockwise, so the top row becomes the right column
+            assert_eq!(transformed(&b, Transform::Rotate), vec![".#", ".#", "#."]);
+            let mut turned = b.clone();
+            for _ in 0..4 {
+                bitmap_eval(&mut turned, &AutoCommand::Transform(Transform::Rotate)).unwrap();
+            }
+            assert_eq!(turned, b);
+            assert_eq!(transformed(&b, Transform::FlipH), vec![".##", "#.."]);
+            assert_eq!(transformed(&b, Transform::FlipV), vec!["..#", "##."]);
+            assert_eq!(transformed(&b, Transform::Invert), vec!["..#", "##."]);
+        }
+    }
+
+    #[test]
+    fn shift_versus_wrap() {
+        for b in [bitmap(&["#..", ".##"]), col(&bitmap(&["#..", ".##"]))] {
+            let check = |dir: Dir2D, shifted: [&str; 2], wrapped: [&str; 2]| {
+                assert_eq!(transformed(&b, Transform::Shift(dir.clone())), shifted);
+                assert_eq!(transformed(&b, Transform::Wrap(dir)), wrapped);
+            };
+            check(Dir2D::Left, ["...", "##."], ["..#", "##."]);
+            check(Dir2D::Right, [".#.", "..#"], [".#.", "#.#"]);
+            check(Dir2D::Up, [".##", "..."], [".##", "#.."]);
+            check(Dir2D::Down, ["...", "#.."], [".##", "#.."]);
+        }
+    }
+
+    fn edit(ed: &mut Editor, command: EditCommand) -> Result<(), Error> {
+        editor_eval(ed, &Command::Edit(command))
+    }
+
+    /// select the rectangle between the two corners (inclusive)
+    fn select(ed: &mut Editor, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) {
+        edit(ed, EditCommand::Unanchor).unwrap();
+        edit(ed, EditCommand::MoveAbs(x0, y0)).unwrap();
+        edit(ed, EditCommand::Anchor).unwrap();
+        edit(ed, EditCommand::MoveAbs(x1, y1)).unwrap();
+    }
+
+    #[test]
+    fn cut_and_paste_at_the_edge() {
+        let b = bitmap(&["#.#.", ".#..", "...."]);
+        for b in [b.clone(), col(&b)] {
+            let mut ed = editor(InitCommand::Bitmap(b)).unwrap();
+            assert!(matches!(
+                edit(&mut ed, EditCommand::Paste),
+                Err(Error::Invalid(_))
+            ));
+            select(&mut ed, (0, 0), (1, 1));
+            edit(&mut ed, EditCommand::Cut).unwrap();
+            assert_eq!(rows(edited(&ed)), vec!["..#.", "....", "...."]);
+            let clip = ed.state.as_ref().unwrap().clipboard.clone().unwrap();
+            assert_eq!(rows(&clip), vec!["#.", ".#"]);
+            // only the clipboard's top-left bits fit
+            edit(&mut ed, EditCommand::Unanchor).unwrap();
+            edit(&mut ed, EditCommand::MoveAbs(3, 1)).unwrap();
+            edit(&mut ed, EditCommand::Paste).unwrap();
+            assert_eq!(rows(edited(&ed)), vec!["..#.", "...#", "...."]);
+            edit(&mut ed, EditCommand::MoveAbs(2, 2)).unwrap();
+            edit(&mut ed, EditCommand::Paste).unwrap();
+            assert_eq!(rows(edited(&ed)), vec!["..#.", "...#", "..#."]);
+            assert_eq!(bitmap_check(edited(&ed)), Ok(()));
+            // without a selection, the whole bitmap
+            edit(&mut ed, EditCommand::Copy).unwrap();
+            let clip = ed.state.as_ref().unwrap().clipboard.clone().unwrap();
+            assert_eq!(rows(&clip), rows(edited(&ed)));
+        }
+    }
+
+    #[test]
+    fn rotate_only_square_rects() {
+        let b = bitmap(&["#..", "#..", "..."]);
+        for b in [b.clone(), col(&b)] {
+            let mut rotated = b.clone();
+            let rotate = |x, y, w, h| AutoCommand::TransformRect(x, y, w, h, Transform::Rotate);
+            let res = bitmap_eval(&mut rotated, &rotate(0, 0, 2, 3));
+            assert!(matches!(res, Err(Error::Invalid(_))));
+            assert_eq!(rotated, b);
+            bitmap_eval(&mut rotated, &rotate(0, 0, 2, 2)).unwrap();
+            assert_eq!(rows(&rotated), vec!["##.", "...", "..."]);
+            // other transforms of any rectangle
+            let flip = AutoCommand::TransformRect(0, 1, 3, 1, Transform::FlipH);
+            bitmap_eval(&mut rotated, &flip).unwrap();
+            assert_eq!(rows(&rotated), vec!["##.", "...", "..."]);
+            let flip = AutoCommand::TransformRect(0, 0, 3, 1, Transform::FlipH);
+            bitmap_eval(&mut rotated, &flip).unwrap();
+            assert_eq!(rows(&rotated), vec![".##", "...", "..."]);
+
+            // the same, as an edit of the selection
+            let mut ed = editor(InitCommand::Bitmap(b.clone())).unwrap();
+            select(&mut ed, (0, 0), (1, 2));
+            let res = edit(&mut ed, EditCommand::Transform(Transform::Rotate));
+            assert!(matches!(res, Err(Error::Invalid(_))));
+            assert_eq!(edited(&ed), &b);
+            select(&mut ed, (0, 0), (1, 1));
+            edit(&mut ed, EditCommand::Transform(Transform::Rotate)).unwrap();
+            assert_eq!(rows(edited(&ed)), vec!["##.", "...", "..."]);
+        }
+    }
+}
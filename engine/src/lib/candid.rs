@@ -0,0 +1,1077 @@
+/*!
+
+Candid types, as menu types; Candid values, as menu trees
+
+Parses the type syntax of Candid (`.did` files): type definitions,
+records, variants, options, vectors, tuples, the primitive types
+`nat`, `text`, `bool` and `null`, and the methods of a service.
+Other primitive types (e.g., `nat8` and `blob`) are rejected, rather
+than approximated by types whose binary encodings differ.
+
+Each type becomes a `menu::MenuType`, so that the menu editor can
+edit values of that type.  A service becomes a variant type, with a
+tuple of arguments for each method.  Each use of a defined type name
+becomes a named type, which (recursively) refers to itself by name.
+
+Each complete `menu::MenuTree` (one without blanks) prints as a
+Candid textual value, e.g., for `dfx canister call`; conversely,
+Candid textual values parse into menu trees, guided by their types.
+A complete tree of a service type chooses a method, and gives a call
+to send, with its arguments encoded in the binary format (`binary`).
+
+*/
+
+pub mod binary;
+
+use menu::semantics::{check_tree, tree_blanks, tree_tag, typ_tag, unfold};
+use menu::{self, Label, LabelChoice, MenuTree, MenuType, Nat, PrimType, Tag};
+use types::lang::{Atom, Error, Name};
+
+/// a Candid program: its type definitions, and (optionally) its service
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prog {
+    pub defs: Vec<(String, MenuType)>,
+    pub service: Option<Vec<Method>>,
+}
+
+/// a service method, with its argument and result types
+#[derive(Clone, Debug, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub args: Vec<MenuType>,
+    pub rets: Vec<MenuType>,
+    /// annotations, e.g., `query` and `oneway`
+    pub modes: Vec<String>,
+}
+
+/// a call to a service method, ready to send
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub method: String,
+    /// the arguments, as a Candid message
+    pub args: Vec<u8>,
+    /// the method's annotations, e.g., `query`
+    pub modes: Vec<String>,
+}
+
+impl Prog {
+    /// the type with the given name, if defined
+    pub fn get_type(&self, name: &str) -> Option<&MenuType> {
+        self.defs.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+
+    /// the service method with the given name, if defined
+    pub fn get_method(&self, name: &str) -> Option<&Method> {
+        self.service.iter().flatten().find(|m| m.name == name)
+    }
+
+    /// the service, as a variant over its methods (each with a tuple of arguments)
+    pub fn service_type(&self) -> Option<MenuType> {
+        self.service.as_ref().map(|methods| {
+            MenuType::Variant(
+                methods
+                    .iter()
+                    .map(|m| (label_of_string(&m.name), MenuType::Tup(m.args.clone())))
+                    .collect(),
+            )
+        })
+    }
+
+    /// the call that a complete tree of the service type chooses
+    pub fn call_of_tree(&self, tree: &MenuTree) -> Result<Call, Error> {
+        let (methods, typ) = match (self.service.as_ref(), self.service_type()) {
+            (Some(methods), Some(typ)) => (methods, typ),
+            _ => return Err(Error::Invalid("no service defined".to_string())),
+        };
+        check_tree(tree, &typ, &mut vec![]).map_err(Error::Menu)?;
+        let mut blanks = vec![];
+        tree_blanks(tree, &mut vec![], &mut blanks);
+        if !blanks.is_empty() {
+            return Err(Error::Menu(menu::Error::Blanks(blanks)));
+        };
+        let (label, args) = match tree {
+            MenuTree::Variant(arms) => match arms.choice {
+                Some((ref label, MenuTree::Tup(ref args), _)) => (label, args),
+                _ => return Err(Error::Invalid("expected a method's arguments".to_string())),
+            },
+            tree => return Err(Error::MenuTag(Tag::Variant, tree_tag(tree))),
+        };
+        match methods.iter().find(|m| label_of_string(&m.name) == *label) {
+            Some(m) => Ok(Call {
+                method: m.name.clone(),
+                args: binary::encode(args)?,
+                modes: m.modes.clone(),
+            }),
+            None => Err(Error::Unbound(label.clone())),
+        }
+    }
+
+    /// the named type, if given; otherwise, the service type, or the last type defined
+    pub fn menu_type(&self, name: Option<&str>) -> Result<MenuType, Error> {
+        match name {
+            Some(n) => match self.get_type(n) {
+                Some(t) => Ok(t.clone()),
+                None => Err(Error::Unbound(label_of_string(n))),
+            },
+            None => match (self.service_type(), self.defs.last()) {
+                (Some(t), _) => Ok(t),
+                (None, Some((_, t))) => Ok(t.clone()),
+                (None, None) => Err(Error::Invalid("no types defined".to_string())),
+            },
+        }
+    }
+}
+
+/// parse a `.did` file: a sequence of type definitions, and an optional service
+pub fn parse_prog(s: &str) -> Result<Prog, Error> {
+    let mut p = Parser::new(s);
+    let mut defs = vec![];
+    let mut service = None;
+    loop {
+        match p.peek_ident().as_deref() {
+            Some("type") => {
+                p.keyword("type")?;
+                let name = p.name()?;
+                p.expect('=')?;
+                defs.push((name, p.typ()?));
+            }
+            Some("import") => {
+                return p.error("a definition (imports are not supported)");
+            }
+            Some("service") => {
+                p.keyword("service")?;
+                if p.peek_ident().is_some() {
+                    p.name()?;
+                };
+                p.expect(':')?;
+                if p.peek() == Some('(') {
+                    // a service constructor; we ignore its initialization arguments
+                    p.args()?;
+                    p.expect('-')?;
+                    p.expect('>')?;
+                };
+                service = Some(p.service()?);
+            }
+            _ => (),
+        };
+        if !p.eat(';') {
+            break;
+        }
+    }
+    p.end()?;
+    let mut resolved: Vec<(String, MenuType)> = vec![];
+    for (n, t) in defs.iter() {
+        match resolve_def(&defs, &mut vec![], t)? {
+            // function and service types are not data; they only describe services
+            Ty::Func(_) | Ty::Service(_) => (),
+            _ => resolved.push((n.clone(), resolve(&defs, &mut vec![], &Ty::Id(n.clone()))?)),
+        }
+    }
+    let service = match service {
+        None => None,
+        Some(Ty::Service(methods)) => Some(resolve_methods(&defs, &methods)?),
+        Some(ty) => match resolve_def(&defs, &mut vec![], &ty)? {
+            Ty::Service(methods) => Some(resolve_methods(&defs, &methods)?),
+            _ => return Err(Error::Invalid("expected a service type".to_string())),
+        },
+    };
+    Ok(Prog {
+        defs: resolved,
+        service,
+    })
+}
+
+/// parse a single Candid type (without any definitions)
+pub fn parse_type(s: &str) -> Result<MenuType, Error> {
+    let mut p = Parser::new(s);
+    let ty = p.typ()?;
+    p.end()?;
+    resolve(&[], &mut vec![], &ty)
+}
+
+fn label_of_string(s: &str) -> Label {
+    Name::Atom(Atom::String(s.to_string()))
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Abstract syntax of Candid types, before resolving type names:
+
+#[derive(Clone, Debug)]
+enum Ty {
+    Id(String),
+    Prim(PrimType),
+    /// a type that the menu editor does not support (yet)
+    Unsupported(String),
+    Opt(Box<Ty>),
+    Vec(Box<Ty>),
+    /// record or variant fields; each label is absent for a tuple component
+    Record(Vec<(Option<Label>, Ty)>),
+    Variant(Vec<(Option<Label>, Ty)>),
+    Func(FuncTy),
+    Service(Vec<(String, Ty)>),
+}
+
+#[derive(Clone, Debug)]
+struct FuncTy {
+    args: Vec<Ty>,
+    rets: Vec<Ty>,
+    modes: Vec<String>,
+}
+
+/// follow type names to a type that is not a name
+fn resolve_def(defs: &[(String, Ty)], stack: &mut Vec<String>, ty: &Ty) -> Result<Ty, Error> {
+    match ty {
+        Ty::Id(n) => {
+            if stack.contains(n) {
+                return Err(Error::Invalid(format!(
+                    "recursive type without structure: {}",
+                    n
+                )));
+            };
+            match defs.iter().find(|(m, _)| m == n) {
+                None => Err(Error::Unbound(label_of_string(n))),
+                Some((_, t)) => {
+                    stack.push(n.clone());
+                    let t = resolve_def(defs, stack, t);
+                    stack.pop();
+                    t
+                }
+            }
+        }
+        ty => Ok(ty.clone()),
+    }
+}
+
+/// resolve the type names, each within the stack of definitions that encloses it
+fn resolve(defs: &[(String, Ty)], stack: &mut Vec<String>, ty: &Ty) -> Result<MenuType, Error> {
+    match ty {
+        Ty::Id(n) => {
+            if stack.contains(n) {
+                return Ok(MenuType::Ref(label_of_string(n)));
+            };
+            resolve_def(defs, &mut vec![], ty)?;
+            match defs.iter().find(|(m, _)| m == n) {
+                None => Err(Error::Unbound(label_of_string(n))),
+                Some((_, t)) => {
+                    stack.push(n.clone());
+                    let t = resolve(defs, stack, t);
+                    stack.pop();
+                    Ok(MenuType::Named(label_of_string(n), Box::new(t?)))
+                }
+            }
+        }
+        Ty::Prim(p) => Ok(MenuType::Prim(p.clone())),
+        Ty::Unsupported(t) => Err(Error::Invalid(format!("unsupported Candid type: {}", t))),
+        Ty::Opt(t) => Ok(MenuType::Option(Box::new(resolve(defs, stack, t)?))),
+        Ty::Vec(t) => Ok(MenuType::Vec(Box::new(resolve(defs, stack, t)?))),
+        Ty::Record(fs) => {
+            if !fs.is_empty() && fs.iter().all(|(l, _)| l.is_none()) {
+                let mut typs = vec![];
+                for (_, t) in fs.iter() {
+                    typs.push(resolve(defs, stack, t)?)
+                }
+                Ok(MenuType::Tup(typs))
+            } else {
+                Ok(MenuType::Product(resolve_fields(defs, stack, fs)?))
+            }
+        }
+        Ty::Variant(fs) => Ok(MenuType::Variant(resolve_fields(defs, stack, fs)?)),
+        Ty::Func(_) => Err(Error::Invalid("unsupported Candid type: func".to_string())),
+        Ty::Service(_) => Err(Error::Invalid(
+            "unsupported Candid type: service".to_string(),
+        )),
+    }
+}
+
+/// resolve each field type, labeling each unlabeled field by its position
+fn resolve_fields(
+    defs: &[(String, Ty)],
+    stack: &mut Vec<String>,
+    fields: &[(Option<Label>, Ty)],
+) -> Result<Vec<(Label, MenuType)>, Error> {
+    let mut out = vec![];
+    for (i, (l, t)) in fields.iter().enumerate() {
+        let l = l.clone().unwrap_or(Name::Atom(Atom::Usize(i)));
+        out.push((l, resolve(defs, stack, t)?))
+    }
+    Ok(out)
+}
+
+fn resolve_methods(defs: &[(String, Ty)], methods: &[(String, Ty)]) -> Result<Vec<Method>, Error> {
+    let mut out = vec![];
+    for (name, ty) in methods.iter() {
+        match resolve_def(defs, &mut vec![], ty)? {
+            Ty::Func(f) => {
+                let mut args = vec![];
+                for t in f.args.iter() {
+                    args.push(resolve(defs, &mut vec![], t)?)
+                }
+                let mut rets = vec![];
+                for t in f.rets.iter() {
+                    rets.push(resolve(defs, &mut vec![], t)?)
+                }
+                out.push(Method {
+                    name: name.clone(),
+                    args,
+                    rets,
+                    modes: f.modes,
+                })
+            }
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "expected a function type for method {}",
+                    name
+                )))
+            }
+        }
+    }
+    Ok(out)
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Textual values:
+
+/// print the complete tree, of the given type, as a Candid textual value
+pub fn print_value(tree: &MenuTree, typ: &MenuType) -> Result<String, Error> {
+    check_tree(tree, typ, &mut vec![]).map_err(Error::Menu)?;
+    let mut out = String::new();
+    value_text(&mut out, tree, typ)?;
+    Ok(out)
+}
+
+/// print the complete trees, of the given types, as a Candid argument sequence
+pub fn print_args(args: &[(MenuTree, MenuType)]) -> Result<String, Error> {
+    let mut out = String::from("(");
+    for (i, (tree, typ)) in args.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ")
+        };
+        check_tree(tree, typ, &mut vec![menu::Step::Pos(i)]).map_err(Error::Menu)?;
+        value_text(&mut out, tree, typ)?
+    }
+    out.push(')');
+    Ok(out)
+}
+
+/// parse a Candid textual value, of the given type, as a menu tree
+pub fn parse_value(s: &str, typ: &MenuType) -> Result<MenuTree, Error> {
+    let mut p = Parser::new(s);
+    let tree = p.value(typ)?;
+    p.end()?;
+    Ok(tree)
+}
+
+/// parse a Candid argument sequence, of the given types, as menu trees
+pub fn parse_args(s: &str, typs: &[MenuType]) -> Result<Vec<MenuTree>, Error> {
+    let mut p = Parser::new(s);
+    p.expect('(')?;
+    let mut trees = vec![];
+    for (i, typ) in typs.iter().enumerate() {
+        if i > 0 {
+            p.expect(',')?
+        };
+        trees.push(p.value(typ)?)
+    }
+    p.eat(',');
+    p.expect(')')?;
+    p.end()?;
+    Ok(trees)
+}
+
+/// words that must be quoted when used as labels
+const KEYWORDS: &[&str] = &[
+    "blob",
+    "bool",
+    "composite_query",
+    "empty",
+    "false",
+    "float32",
+    "float64",
+    "func",
+    "import",
+    "int",
+    "int8",
+    "int16",
+    "int32",
+    "int64",
+    "nat",
+    "nat8",
+    "nat16",
+    "nat32",
+    "nat64",
+    "null",
+    "oneway",
+    "opt",
+    "principal",
+    "query",
+    "record",
+    "reserved",
+    "service",
+    "text",
+    "true",
+    "type",
+    "variant",
+    "vec",
+];
+
+fn label_text(out: &mut String, label: &Label) -> Result<(), Error> {
+    match label {
+        Name::Atom(Atom::Usize(n)) => out.push_str(&n.to_string()),
+        Name::Atom(Atom::String(s)) => {
+            let mut chars = s.chars();
+            let is_ident = match chars.next() {
+                Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+                }
+                _ => false,
+            };
+            if is_ident && !KEYWORDS.contains(&s.as_str()) {
+                out.push_str(s)
+            } else {
+                text_text(out, s)
+            }
+        }
+        l => return Err(Error::Invalid(format!("not a Candid label: {:?}", l))),
+    };
+    Ok(())
+}
+
+fn text_text(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"')
+}
+
+/// print the value of the tree, which `check_tree` accepts as one of the given type
+fn value_text(out: &mut String, tree: &MenuTree, typ: &MenuType) -> Result<(), Error> {
+    if let MenuTree::Blank(_) = tree {
+        return Err(Error::MenuTag(typ_tag(typ), Tag::Blank));
+    };
+    match tree {
+        MenuTree::Unit => out.push_str("null"),
+        MenuTree::Nat(n) => out.push_str(&n.to_string()),
+        MenuTree::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        MenuTree::Text(t) => text_text(out, t),
+        MenuTree::Option(false, _, _) => out.push_str("null"),
+        MenuTree::Option(true, tree, t) => {
+            out.push_str("opt ");
+            value_text(out, tree, t)?
+        }
+        MenuTree::Vec(trees, t) => {
+            out.push_str("vec {");
+            for tree in trees.iter() {
+                out.push(' ');
+                value_text(out, tree, t)?;
+                out.push(';')
+            }
+            out.push_str(if trees.is_empty() { "}" } else { " }" })
+        }
+        MenuTree::Tup(trees) => {
+            out.push_str("record {");
+            for (tree, t) in trees.iter() {
+                out.push(' ');
+                value_text(out, tree, t)?;
+                out.push(';')
+            }
+            out.push_str(if trees.is_empty() { "}" } else { " }" })
+        }
+        MenuTree::Product(fields) => {
+            out.push_str("record {");
+            for (l, tree, t) in fields.iter() {
+                out.push(' ');
+                label_text(out, l)?;
+                out.push_str(" = ");
+                value_text(out, tree, t)?;
+                out.push(';')
+            }
+            out.push_str(if fields.is_empty() { "}" } else { " }" })
+        }
+        MenuTree::Variant(arms) => match arms.choice {
+            None => return Err(Error::Invalid("variant without a choice".to_string())),
+            Some((ref l, ref tree, ref t)) => {
+                out.push_str("variant { ");
+                label_text(out, l)?;
+                if unfold(t) != MenuType::Prim(PrimType::Unit) {
+                    out.push_str(" = ");
+                    value_text(out, tree, t)?
+                };
+                out.push_str(" }")
+            }
+        },
+        MenuTree::Blank(_) => unreachable!(),
+    };
+    Ok(())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Parsing, by recursive descent over the characters of the input:
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Parser {
+        Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error<X>(&self, expected: &str) -> Result<X, Error> {
+        let (mut line, mut col) = (1, 1);
+        for c in self.chars[..self.pos].iter() {
+            if *c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        Err(Error::Syntax(line, col, expected.to_string()))
+    }
+
+    /// skip whitespace, and both line (`//`) and block (`/* */`) comments
+    fn skip_ws(&mut self) {
+        loop {
+            match (self.chars.get(self.pos), self.chars.get(self.pos + 1)) {
+                (Some(c), _) if c.is_whitespace() => self.pos += 1,
+                (Some('/'), Some('/')) => {
+                    while self.pos < self.chars.len() && self.chars[self.pos] != '\n' {
+                        self.pos += 1
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.pos += 2;
+                    while self.pos < self.chars.len()
+                        && !(self.chars[self.pos] == '*'
+                            && self.chars.get(self.pos + 1) == Some(&'/'))
+                    {
+                        self.pos += 1
+                    }
+                    self.pos = (self.pos + 2).min(self.chars.len())
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ws();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(&format!("`{}`", c))
+        }
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => self.error("end of input"),
+        }
+    }
+
+    fn peek_ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = self.pos;
+                while end < self.chars.len()
+                    && (self.chars[end].is_ascii_alphanumeric() || self.chars[end] == '_')
+                {
+                    end += 1
+                }
+                Some(self.chars[self.pos..end].iter().collect())
+            }
+            _ => None,
+        }
+    }
+
+    fn keyword(&mut self, kw: &str) -> Result<(), Error> {
+        match self.peek_ident() {
+            Some(ref id) if id == kw => {
+                self.pos += kw.len();
+                Ok(())
+            }
+            _ => self.error(&format!("`{}`", kw)),
+        }
+    }
+
+    /// an identifier, or a quoted name
+    fn name(&mut self) -> Result<String, Error> {
+        if self.peek() == Some('"') {
+            return self.text();
+        };
+        match self.peek_ident() {
+            Some(id) => {
+                self.pos += id.len();
+                Ok(id)
+            }
+            None => self.error("a name"),
+        }
+    }
+
+    fn text(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                None => return self.error("end of text"),
+                Some(c) => *c,
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let c = match self.chars.get(self.pos) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\'') => '\'',
+                        Some('\\') => '\\',
+                        Some('u') => {
+                            self.pos += 1;
+                            self.unicode_escape()?
+                        }
+                        _ => return self.error("text escape"),
+                    };
+                    self.pos += 1;
+                    s.push(c)
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_ascii_digit() || self.chars[self.pos] == '_')
+        {
+            self.pos += 1
+        }
+        let digits: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        match digits.parse() {
+            Ok(n) => Ok(n),
+            Err(_) => {
+                self.pos = start;
+                self.error("a number")
+            }
+        }
+    }
+
+    fn nat(&mut self) -> Result<Nat, Error> {
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_ascii_digit() || self.chars[self.pos] == '_')
+        {
+            self.pos += 1
+        }
+        let digits: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        match digits.parse() {
+            Ok(n) => Ok(n),
+            Err(_) => {
+                self.pos = start;
+                self.error("a natural number")
+            }
+        }
+    }
+
+    fn typ(&mut self) -> Result<Ty, Error> {
+        let id = match self.peek_ident() {
+            Some(id) => id,
+            None => return self.error("a type"),
+        };
+        self.pos += id.len();
+        Ok(match id.as_str() {
+            "nat" => Ty::Prim(PrimType::Nat),
+            "text" => Ty::Prim(PrimType::Text),
+            "bool" => Ty::Prim(PrimType::Bool),
+            "null" => Ty::Prim(PrimType::Unit),
+            "nat8" | "nat16" | "nat32" | "nat64" | "blob" | "int" | "int8" | "int16" | "int32"
+            | "int64" | "float32" | "float64" | "principal" | "reserved" | "empty" => {
+                Ty::Unsupported(id)
+            }
+            "opt" => Ty::Opt(Box::new(self.typ()?)),
+            "vec" => Ty::Vec(Box::new(self.typ()?)),
+            "record" => Ty::Record(self.fields(false)?),
+            "variant" => Ty::Variant(self.fields(true)?),
+            "func" => Ty::Func(self.func()?),
+            "service" => self.service()?,
+            _ => Ty::Id(id),
+        })
+    }
+
+    /// `{ field; ... }`, where each field is `label : type`, or just `type`
+    /// (a tuple component), or just `label` (a variant field of type `null`)
+    fn fields(&mut self, variant: bool) -> Result<Vec<(Option<Label>, Ty)>, Error> {
+        self.expect('{')?;
+        let mut fields = vec![];
+        while !self.eat('}') {
+            let start = self.pos;
+            let label = match self.peek() {
+                Some('"') => Some(label_of_string(&self.text()?)),
+                Some(c) if c.is_ascii_digit() => Some(Name::Atom(Atom::Usize(self.number()?))),
+                _ => match self.peek_ident() {
+                    Some(id) => {
+                        self.pos += id.len();
+                        Some(label_of_string(&id))
+                    }
+                    None => None,
+                },
+            };
+            let field = if self.eat(':') {
+                (label, self.typ()?)
+            } else if variant && label.is_some() {
+                (label, Ty::Prim(PrimType::Unit))
+            } else {
+                self.pos = start;
+                (None, self.typ()?)
+            };
+            fields.push(field);
+            if !self.eat(';') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(fields)
+    }
+
+    /// `(args) -> (rets) modes`
+    fn func(&mut self) -> Result<FuncTy, Error> {
+        let args = self.args()?;
+        self.expect('-')?;
+        self.expect('>')?;
+        let rets = self.args()?;
+        let mut modes = vec![];
+        while let Some(id) = self.peek_ident() {
+            match id.as_str() {
+                "query" | "oneway" | "composite_query" => {
+                    self.pos += id.len();
+                    modes.push(id)
+                }
+                _ => break,
+            }
+        }
+        Ok(FuncTy { args, rets, modes })
+    }
+
+    /// `(type, ...)`, where each type may be preceded by `name :`
+    fn args(&mut self) -> Result<Vec<Ty>, Error> {
+        self.expect('(')?;
+        let mut args = vec![];
+        while !self.eat(')') {
+            let start = self.pos;
+            if self.name().is_err() || !self.eat(':') {
+                self.pos = start
+            };
+            args.push(self.typ()?);
+            if !self.eat(',') {
+                self.expect(')')?;
+                break;
+            }
+        }
+        Ok(args)
+    }
+
+    /// `{ method : functype; ... }`, or the name of a service type
+    fn service(&mut self) -> Result<Ty, Error> {
+        if self.peek() != Some('{') {
+            let name = self.name()?;
+            return Ok(Ty::Id(name));
+        };
+        self.expect('{')?;
+        let mut methods = vec![];
+        while !self.eat('}') {
+            let name = self.name()?;
+            self.expect(':')?;
+            let ty = if self.peek() == Some('(') {
+                Ty::Func(self.func()?)
+            } else {
+                Ty::Id(self.name()?)
+            };
+            methods.push((name, ty));
+            if !self.eat(';') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(Ty::Service(methods))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Parsing textual values, guided by their types:
+
+impl Parser {
+    /// the `{hex}` of a `\u{hex}` escape; leaves the final `}` for the caller to skip
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        if self.chars.get(self.pos) != Some(&'{') {
+            return self.error("`{`");
+        };
+        let start = self.pos + 1;
+        let mut end = start;
+        while end < self.chars.len() && self.chars[end].is_ascii_hexdigit() {
+            end += 1
+        }
+        let hex: String = self.chars[start..end].iter().collect();
+        self.pos = end;
+        match u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+        {
+            Some(c) if self.chars.get(end) == Some(&'}') => Ok(c),
+            _ => self.error("a unicode escape"),
+        }
+    }
+
+    /// a label: an identifier, a quoted name, or a number
+    fn label(&mut self) -> Result<Label, Error> {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => Ok(Name::Atom(Atom::Usize(self.number()?))),
+            _ => Ok(label_of_string(&self.name()?)),
+        }
+    }
+
+    fn value(&mut self, typ: &MenuType) -> Result<MenuTree, Error> {
+        let typ = &unfold(typ);
+        match typ {
+            MenuType::Prim(PrimType::Unit) => {
+                self.keyword("null")?;
+                Ok(MenuTree::Unit)
+            }
+            MenuType::Prim(PrimType::Nat) => match self.peek() {
+                Some(c) if c.is_ascii_digit() => Ok(MenuTree::Nat(self.nat()?)),
+                _ => self.error("a nat"),
+            },
+            MenuType::Prim(PrimType::Text) => Ok(MenuTree::Text(self.text()?)),
+            MenuType::Prim(PrimType::Bool) => match self.peek_ident().as_deref() {
+                Some("true") => self.keyword("true").map(|_| MenuTree::Bool(true)),
+                Some("false") => self.keyword("false").map(|_| MenuTree::Bool(false)),
+                _ => self.error("a bool"),
+            },
+            MenuType::Option(t) => match self.peek_ident().as_deref() {
+                Some("null") => {
+                    self.keyword("null")?;
+                    Ok(MenuTree::Option(
+                        false,
+                        Box::new(MenuTree::Blank(*t.clone())),
+                        *t.clone(),
+                    ))
+                }
+                _ => {
+                    self.keyword("opt")?;
+                    let tree = self.value(t)?;
+                    Ok(MenuTree::Option(true, Box::new(tree), *t.clone()))
+                }
+            },
+            MenuType::Vec(t) => {
+                self.keyword("vec")?;
+                self.expect('{')?;
+                let mut trees = vec![];
+                while !self.eat('}') {
+                    trees.push(self.value(t)?);
+                    if !self.eat(';') {
+                        self.expect('}')?;
+                        break;
+                    }
+                }
+                Ok(MenuTree::Vec(trees, *t.clone()))
+            }
+            MenuType::Tup(ts) => {
+                self.keyword("record")?;
+                self.expect('{')?;
+                let mut trees = vec![];
+                for (i, t) in ts.iter().enumerate() {
+                    if i > 0 {
+                        self.expect(';')?
+                    };
+                    trees.push((self.value(t)?, t.clone()))
+                }
+                self.eat(';');
+                self.expect('}')?;
+                Ok(MenuTree::Tup(trees))
+            }
+            MenuType::Product(fields) => {
+                self.keyword("record")?;
+                self.expect('{')?;
+                let mut trees: Vec<Option<MenuTree>> = vec![None; fields.len()];
+                let mut pos = 0;
+                while !self.eat('}') {
+                    // each field is either `label = value`, or (positionally) `value`
+                    let start = self.pos;
+                    let i = match self.label() {
+                        Ok(ref l) if self.eat('=') => match fields.iter().position(|f| f.0 == *l) {
+                            Some(i) => i,
+                            None => {
+                                self.pos = start;
+                                return self.error("a field label of the record type");
+                            }
+                        },
+                        _ => {
+                            self.pos = start;
+                            pos
+                        }
+                    };
+                    if i >= fields.len() || trees[i].is_some() {
+                        self.pos = start;
+                        return self.error("a distinct field of the record type");
+                    };
+                    trees[i] = Some(self.value(&fields[i].1)?);
+                    pos = i + 1;
+                    if !self.eat(';') {
+                        self.expect('}')?;
+                        break;
+                    }
+                }
+                let mut out = vec![];
+                for ((l, t), tree) in fields.iter().zip(trees) {
+                    match tree {
+                        Some(tree) => out.push((l.clone(), tree, t.clone())),
+                        None => {
+                            let mut field = String::new();
+                            label_text(&mut field, l)?;
+                            return self.error(&format!("a value for field {}", field));
+                        }
+                    }
+                }
+                Ok(MenuTree::Product(out))
+            }
+            MenuType::Variant(arms) => {
+                self.keyword("variant")?;
+                self.expect('{')?;
+                let start = self.pos;
+                let label = self.label()?;
+                let i = match arms.iter().position(|a| a.0 == label) {
+                    Some(i) => i,
+                    None => {
+                        self.pos = start;
+                        return self.error("a label of the variant type");
+                    }
+                };
+                let t = &arms[i].1;
+                let tree = if self.eat('=') {
+                    self.value(t)?
+                } else if unfold(t) == MenuType::Prim(PrimType::Unit) {
+                    MenuTree::Unit
+                } else {
+                    return self.error("`=`");
+                };
+                self.eat(';');
+                self.expect('}')?;
+                let blank =
+                    |(l, t): &(Label, MenuType)| (l.clone(), MenuTree::Blank(t.clone()), t.clone());
+                Ok(MenuTree::Variant(Box::new(LabelChoice {
+                    before: arms[..i].iter().map(blank).collect(),
+                    choice: Some((label, tree, t.clone())),
+                    after: arms[i + 1..].iter().map(blank).collect(),
+                })))
+            }
+            MenuType::Named(_, _) | MenuType::Ref(_) => {
+                Err(Error::Invalid(format!("type without structure: {}", typ)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_prog, parse_type, parse_value, Call};
+    use menu::{self, MenuTree, MenuType, PrimType};
+    use types::lang::Error;
+
+    #[test]
+    fn fixed_width_types_are_rejected() {
+        for t in ["nat8", "nat16", "nat32", "nat64", "blob"].iter() {
+            let did = format!(
+                "type t = record {{ n : nat; x : {} }};\nservice : {{ f : (t) -> () }}",
+                t
+            );
+            assert_eq!(
+                parse_prog(&did),
+                Err(Error::Invalid(format!("unsupported Candid type: {}", t)))
+            );
+            let did = format!("service : {{ f : (vec {}) -> () }}", t);
+            assert!(parse_prog(&did).is_err());
+        }
+        let prog = parse_prog("type t = record { n : nat };\nservice : { f : (t) -> () }");
+        assert_eq!(prog.unwrap().service.unwrap()[0].args.len(), 1);
+        assert_eq!(
+            parse_type("vec nat"),
+            Ok(MenuType::Vec(Box::new(MenuType::Prim(PrimType::Nat))))
+        );
+    }
+
+    #[test]
+    fn call_of_chosen_method() {
+        let prog = parse_prog(
+            "type point = record { x : nat };
+             service : {
+                 greet : (text) -> (text);
+                 add : (nat, point) -> (nat) query;
+             }",
+        )
+        .unwrap();
+        let typ = prog.service_type().unwrap();
+        let tree = parse_value("variant { add = record { 2; record { x = 3 } } }", &typ).unwrap();
+        let call = prog.call_of_tree(&tree).unwrap();
+        assert_eq!(
+            call,
+            Call {
+                method: "add".to_string(),
+                // `DIDL`, a table of one entry (`record { 120 : nat }`, as `x` hashes to 120),
+                // the argument types (`nat`, then the entry), and the values 2 and 3
+                args: vec![
+                    0x44, 0x49, 0x44, 0x4c, 0x01, 0x6c, 0x01, 0x78, 0x7d, 0x02, 0x7d, 0x00, 0x02,
+                    0x03,
+                ],
+                modes: vec!["query".to_string()],
+            }
+        );
+
+        // the other method, with its text argument
+        let tree = parse_value("variant { greet = record { \"hi\" } }", &typ).unwrap();
+        let call = prog.call_of_tree(&tree).unwrap();
+        assert_eq!(call.method, "greet");
+        assert_eq!(call.args, b"DIDL\x00\x01\x71\x02hi".to_vec());
+        assert!(call.modes.is_empty());
+
+        // a method must be chosen, and its arguments complete
+        match prog.call_of_tree(&menu::semantics::auto_fill(&typ, 1)) {
+            Err(Error::Menu(_)) | Err(Error::Invalid(_)) => (),
+            res => panic!("expected an incomplete call, not {:?}", res),
+        }
+        assert!(prog.call_of_tree(&MenuTree::Blank(typ)).is_err());
+    }
+}
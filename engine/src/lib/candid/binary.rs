@@ -0,0 +1,1124 @@
+/*!
+
+Candid binary (`DIDL`) encoding and decoding of menu trees
+
+A message consists of the magic bytes `DIDL`, a type table of the
+composite types (opt, vec, record, variant), the sequence of argument
+types, and then the sequence of argument values.  Numbers in the
+header are LEB128 (unsigned) or SLEB128 (signed, for type references,
+where negative numbers name primitive types).
+
+Record and variant fields are identified on the wire by a 32-bit hash
+of their labels (or by number, for tuples and numeric labels), and are
+ordered by these identifiers.  Hence, decoding recovers labels only
+when given the expected types (see `decode_as`); otherwise, each field
+is labeled by its identifier (see `decode`).
+
+A recursive type occupies a table entry that refers to itself; each
+named menu type (see `menu::MenuType::Named`) gets its own entry.
+
+Since messages may come from untrusted peers, decoding rejects type
+tables whose records nest within themselves (e.g., `record { 0 : t0 }`,
+whose values would recurse without consuming any bytes), and bounds
+the nesting of types and values by `MAX_DEPTH`.
+
+*/
+
+use menu::semantics::{check_tree, typ_tag, unfold};
+use menu::{self, Label, LabelChoice, MenuTree, MenuType, Nat, Path, PrimType, Step, Tag};
+use types::lang::{Atom, Error, Name};
+
+const MAGIC: &[u8] = b"DIDL";
+
+// type codes, for primitive types and type constructors
+const NULL: i64 = -1;
+const BOOL: i64 = -2;
+const NAT: i64 = -3;
+const NAT8: i64 = -5;
+const NAT16: i64 = -6;
+const NAT32: i64 = -7;
+const NAT64: i64 = -8;
+const TEXT: i64 = -15;
+const OPT: i64 = -18;
+const VEC: i64 = -19;
+const RECORD: i64 = -20;
+const VARIANT: i64 = -21;
+
+/// the deepest nesting of types and values that decoding accepts
+pub const MAX_DEPTH: usize = 100;
+
+/// encode the complete trees, of the given types, as a Candid message
+pub fn encode(args: &[(MenuTree, MenuType)]) -> Result<Vec<u8>, Error> {
+    let mut table = Table {
+        entries: vec![],
+        names: vec![],
+    };
+    let mut refs = vec![];
+    for (_, typ) in args.iter() {
+        refs.push(table.type_ref(typ)?)
+    }
+    let mut out = MAGIC.to_vec();
+    leb128(&mut out, table.entries.len() as u64);
+    for entry in table.entries.iter() {
+        out.extend_from_slice(entry)
+    }
+    leb128(&mut out, refs.len() as u64);
+    for r in refs.iter() {
+        sleb128(&mut out, *r)
+    }
+    for (i, (tree, typ)) in args.iter().enumerate() {
+        check_tree(tree, typ, &mut vec![Step::Pos(i)]).map_err(Error::Menu)?;
+        encode_value(&mut out, tree, typ)?
+    }
+    Ok(out)
+}
+
+/// decode a Candid message whose argument types are the given (expected) types
+pub fn decode_as(bytes: &[u8], typs: &[MenuType]) -> Result<Vec<MenuTree>, Error> {
+    let mut d = Decoder {
+        bytes,
+        pos: 0,
+        table: vec![],
+        depth: 0,
+        path: vec![],
+    };
+    let refs = d.header()?;
+    if refs.len() != typs.len() {
+        return d.error(&format!("{} arguments", typs.len()));
+    };
+    let mut trees = vec![];
+    for (i, (r, typ)) in refs.iter().zip(typs.iter()).enumerate() {
+        if !d.agrees(*r, typ, &mut vec![]) {
+            return Err(Error::Menu(menu::Error::MenuTypeMismatch(
+                vec![Step::Pos(i)],
+                typ.clone(),
+                d.menu_type(*r, &mut vec![])?,
+            )));
+        };
+        trees.push(d.value(typ)?)
+    }
+    d.end()?;
+    Ok(trees)
+}
+
+/// decode a Candid message, labeling each field by its identifier
+pub fn decode(bytes: &[u8]) -> Result<Vec<(MenuTree, MenuType)>, Error> {
+    let mut d = Decoder {
+        bytes,
+        pos: 0,
+        table: vec![],
+        depth: 0,
+        path: vec![],
+    };
+    let refs = d.header()?;
+    let mut args = vec![];
+    for (i, r) in refs.iter().enumerate() {
+        let typ = d.menu_type(*r, &mut vec![])?;
+        d.path = vec![Step::Pos(i)];
+        args.push((d.wire_value(*r, &typ)?, typ))
+    }
+    d.end()?;
+    Ok(args)
+}
+
+/// the field identifier of a label: its number, or the hash of its text
+pub fn label_id(label: &Label) -> Result<u32, Error> {
+    match label {
+        Name::Atom(Atom::Usize(n)) if *n <= u32::MAX as usize => Ok(*n as u32),
+        Name::Atom(Atom::String(s)) => Ok(s
+            .bytes()
+            .fold(0u32, |h, b| h.wrapping_mul(223).wrapping_add(b as u32))),
+        l => Err(Error::Invalid(format!("not a Candid label: {:?}", l))),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Encoding types:
+
+/// the type table of a message, under construction
+struct Table {
+    entries: Vec<Vec<u8>>,
+    /// the entries of the enclosing named types
+    names: Vec<(Label, i64)>,
+}
+
+impl Table {
+    /// the type reference for the type, adding entries to the table as needed
+    fn type_ref(&mut self, typ: &MenuType) -> Result<i64, Error> {
+        Ok(match typ {
+            MenuType::Prim(PrimType::Unit) => NULL,
+            MenuType::Prim(PrimType::Bool) => BOOL,
+            MenuType::Prim(PrimType::Nat) => NAT,
+            MenuType::Prim(PrimType::Text) => TEXT,
+            MenuType::Ref(name) => match self.names.iter().rev().find(|(n, _)| n == name) {
+                Some((_, r)) => *r,
+                None => return Err(Error::Invalid(format!("unbound type name: {}", name))),
+            },
+            MenuType::Named(_, _) => {
+                // a chain of names shares the entry of their structure
+                let mut names = vec![];
+                let mut def = typ;
+                while let MenuType::Named(name, d) = def {
+                    names.push(name.clone());
+                    def = d
+                }
+                if let MenuType::Prim(_) | MenuType::Ref(_) = def {
+                    return self.type_ref(def);
+                };
+                let r = self.entries.len() as i64;
+                self.entries.push(vec![]);
+                let depth = self.names.len();
+                self.names.extend(names.into_iter().map(|n| (n, r)));
+                let entry = self.entry(def);
+                self.names.truncate(depth);
+                self.entries[r as usize] = entry?;
+                r
+            }
+            _ => {
+                let entry = self.entry(typ)?;
+                // share equal entries
+                match self.entries.iter().position(|e| *e == entry) {
+                    Some(i) => i as i64,
+                    None => {
+                        self.entries.push(entry);
+                        self.entries.len() as i64 - 1
+                    }
+                }
+            }
+        })
+    }
+
+    /// the table entry for an opt, vec, record or variant type
+    fn entry(&mut self, typ: &MenuType) -> Result<Vec<u8>, Error> {
+        let mut entry = vec![];
+        let (code, fields) = match typ {
+            MenuType::Option(t) => (OPT, vec![(0, self.type_ref(t)?)]),
+            MenuType::Vec(t) => (VEC, vec![(0, self.type_ref(t)?)]),
+            MenuType::Tup(ts) => {
+                let mut fields = vec![];
+                for (i, t) in ts.iter().enumerate() {
+                    fields.push((i as u32, self.type_ref(t)?))
+                }
+                (RECORD, fields)
+            }
+            MenuType::Product(fs) => (RECORD, self.fields(fs)?),
+            MenuType::Variant(fs) => (VARIANT, self.fields(fs)?),
+            MenuType::Prim(_) | MenuType::Named(_, _) | MenuType::Ref(_) => unreachable!(),
+        };
+        sleb128(&mut entry, code);
+        if code == OPT || code == VEC {
+            sleb128(&mut entry, fields[0].1)
+        } else {
+            leb128(&mut entry, fields.len() as u64);
+            for (id, r) in fields.iter() {
+                leb128(&mut entry, *id as u64);
+                sleb128(&mut entry, *r)
+            }
+        };
+        Ok(entry)
+    }
+
+    /// the identifiers and type references of the fields, ordered by identifier
+    fn fields(&mut self, fields: &[(Label, MenuType)]) -> Result<Vec<(u32, i64)>, Error> {
+        let mut out = vec![];
+        for (l, t) in fields.iter() {
+            out.push((label_id(l)?, self.type_ref(t)?))
+        }
+        out.sort_by_key(|f| f.0);
+        if out.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(Error::Invalid("fields with equal identifiers".to_string()));
+        };
+        Ok(out)
+    }
+}
+
+/// the identifiers of the fields, with their types, ordered by identifier
+fn fields_by_id(fields: &[(Label, MenuType)]) -> Result<Vec<(u32, &MenuType)>, Error> {
+    let mut out = vec![];
+    for (l, t) in fields.iter() {
+        out.push((label_id(l)?, t))
+    }
+    out.sort_by_key(|f| f.0);
+    Ok(out)
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Encoding values:
+
+pub fn leb128(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80)
+    }
+}
+
+/// the LEB128 encoding of an arbitrary-precision natural number
+pub fn nat_leb128(out: &mut Vec<u8>, n: &Nat) {
+    let digits = n.to_radix_le(128);
+    for (i, d) in digits.iter().enumerate() {
+        if i + 1 < digits.len() {
+            out.push(d | 0x80)
+        } else {
+            out.push(*d)
+        }
+    }
+}
+
+pub fn sleb128(out: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80)
+    }
+}
+
+/// encode the value of the tree, which `check_tree` accepts as one of the given type
+fn encode_value(out: &mut Vec<u8>, tree: &MenuTree, typ: &MenuType) -> Result<(), Error> {
+    if let MenuTree::Blank(_) = tree {
+        return Err(Error::MenuTag(typ_tag(typ), Tag::Blank));
+    };
+    match tree {
+        MenuTree::Unit => (),
+        MenuTree::Bool(b) => out.push(*b as u8),
+        MenuTree::Nat(n) => nat_leb128(out, n),
+        MenuTree::Text(t) => {
+            leb128(out, t.len() as u64);
+            out.extend_from_slice(t.as_bytes())
+        }
+        MenuTree::Option(false, _, _) => out.push(0),
+        MenuTree::Option(true, tree, t) => {
+            out.push(1);
+            encode_value(out, tree, t)?
+        }
+        MenuTree::Vec(trees, t) => {
+            leb128(out, trees.len() as u64);
+            for tree in trees.iter() {
+                encode_value(out, tree, t)?
+            }
+        }
+        MenuTree::Tup(trees) => {
+            for (tree, t) in trees.iter() {
+                encode_value(out, tree, t)?
+            }
+        }
+        MenuTree::Product(fields) => {
+            let mut fields_by_id = vec![];
+            for f in fields.iter() {
+                fields_by_id.push((label_id(&f.0)?, f))
+            }
+            fields_by_id.sort_by_key(|f| f.0);
+            for (_, (_, tree, t)) in fields_by_id {
+                encode_value(out, tree, t)?
+            }
+        }
+        MenuTree::Variant(arms) => match arms.choice {
+            None => return Err(Error::Invalid("variant without a choice".to_string())),
+            Some((ref l, ref tree, ref t)) => {
+                let id = label_id(l)?;
+                let mut ids = vec![];
+                for (l, _, _) in arms.before.iter().chain(arms.after.iter()) {
+                    ids.push(label_id(l)?)
+                }
+                leb128(out, ids.iter().filter(|i| **i < id).count() as u64);
+                encode_value(out, tree, t)?
+            }
+        },
+        MenuTree::Blank(_) => unreachable!(),
+    };
+    Ok(())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Decoding:
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// each entry: its type code, and its fields' identifiers and type references
+    table: Vec<(i64, Vec<(u32, i64)>)>,
+    /// the nesting of the value being decoded
+    depth: usize,
+    /// the path of the value being decoded, from its argument (by `wire_value`)
+    path: Path,
+}
+
+impl<'a> Decoder<'a> {
+    fn error<X>(&self, expected: &str) -> Result<X, Error> {
+        Err(Error::Binary(self.pos, expected.to_string()))
+    }
+
+    fn end(&self) -> Result<(), Error> {
+        if self.pos == self.bytes.len() {
+            Ok(())
+        } else {
+            self.error("end of message")
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        match self.bytes.get(self.pos) {
+            Some(b) => {
+                self.pos += 1;
+                Ok(*b)
+            }
+            None => self.error("a byte"),
+        }
+    }
+
+    fn leb128(&mut self) -> Result<u64, Error> {
+        let (mut n, mut shift) = (0u64, 0);
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 || (shift == 63 && byte & 0x7e != 0) {
+                return self.error("a LEB128 number of at most 64 bits");
+            };
+            n |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+    }
+
+    fn nat_leb128(&mut self) -> Result<Nat, Error> {
+        let mut digits = vec![];
+        loop {
+            let byte = self.byte()?;
+            digits.push(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return match Nat::from_radix_le(&digits, 128) {
+                    Some(n) => Ok(n),
+                    None => self.error("a LEB128 number"),
+                };
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> Result<i64, Error> {
+        let (mut n, mut shift) = (0i64, 0);
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return self.error("a SLEB128 number of at most 64 bits");
+            };
+            n |= ((byte & 0x7f) as i64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    n |= -1i64 << shift
+                };
+                return Ok(n);
+            }
+        }
+    }
+
+    /// a length, bounded by the remaining bytes (each element occupies at least one byte)
+    fn len(&mut self) -> Result<usize, Error> {
+        let n = self.leb128()?;
+        if n > (self.bytes.len() - self.pos) as u64 {
+            self.error("a length within the message")
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    /// enter a nested value, bounding the nesting by `MAX_DEPTH`
+    /// (decoding stops at the first error, so only successful values leave again)
+    fn enter(&mut self) -> Result<(), Error> {
+        if self.depth >= MAX_DEPTH {
+            return self.error(&format!("values nested at most {} deep", MAX_DEPTH));
+        };
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn fixed(&mut self, width: usize) -> Result<u64, Error> {
+        let mut n = 0u64;
+        for i in 0..width {
+            n |= (self.byte()? as u64) << (8 * i)
+        }
+        Ok(n)
+    }
+
+    /// the magic bytes, the type table and the argument types (as type references)
+    fn header(&mut self) -> Result<Vec<i64>, Error> {
+        if !self.bytes.starts_with(MAGIC) {
+            return self.error("`DIDL`");
+        };
+        self.pos = MAGIC.len();
+        let len = self.len()?;
+        let mut table: Vec<(i64, Vec<(u32, i64)>)> = vec![];
+        for _ in 0..len {
+            let code = self.sleb128()?;
+            let entry = match code {
+                OPT | VEC => vec![(0, self.type_ref(len)?)],
+                RECORD | VARIANT => {
+                    let mut fields = vec![];
+                    for _ in 0..self.len()? {
+                        let id = self.leb128()?;
+                        if id > u32::MAX as u64 {
+                            return self.error("a 32-bit field identifier");
+                        };
+                        let ty = self.type_ref(len)?;
+                        match fields.last() {
+                            Some((prev, _)) if *prev >= id as u32 => {
+                                return self.error("fields ordered by identifier")
+                            }
+                            _ => fields.push((id as u32, ty)),
+                        }
+                    }
+                    fields
+                }
+                _ => return self.error("an opt, vec, record or variant type"),
+            };
+            table.push((code, entry))
+        }
+        if !records_are_finite(&table) {
+            return self.error("a type table without records nested in themselves");
+        };
+        self.table = table;
+        let mut refs = vec![];
+        for _ in 0..self.len()? {
+            refs.push(self.type_ref(len)?)
+        }
+        Ok(refs)
+    }
+
+    /// a type reference, to a primitive type or to one of the table's entries
+    fn type_ref(&mut self, table_len: usize) -> Result<i64, Error> {
+        let r = self.sleb128()?;
+        if r >= table_len as i64 {
+            return self.error("a type reference within the type table");
+        };
+        Ok(r)
+    }
+
+    /// does the type reference agree with the (expected) type?
+    fn agrees(&self, r: i64, typ: &MenuType, assumed: &mut Vec<(i64, MenuType)>) -> bool {
+        let typ = unfold(typ);
+        if r < 0 {
+            return matches!(
+                (r, &typ),
+                (NULL, MenuType::Prim(PrimType::Unit))
+                    | (BOOL, MenuType::Prim(PrimType::Bool))
+                    | (NAT, MenuType::Prim(PrimType::Nat))
+                    | (TEXT, MenuType::Prim(PrimType::Text))
+            );
+        };
+        // a recursive type agrees, unless its structure disagrees elsewhere
+        let pair = (r, typ);
+        if assumed.contains(&pair) {
+            return true;
+        };
+        if assumed.len() >= MAX_DEPTH {
+            return false;
+        };
+        assumed.push(pair.clone());
+        let (code, ref entry) = self.table[r as usize];
+        let agrees = match (code, &pair.1) {
+            (OPT, MenuType::Option(t)) | (VEC, MenuType::Vec(t)) => {
+                self.agrees(entry[0].1, t, assumed)
+            }
+            (RECORD, MenuType::Tup(ts)) => {
+                entry.len() == ts.len()
+                    && entry
+                        .iter()
+                        .zip(ts.iter())
+                        .enumerate()
+                        .all(|(i, ((id, r), t))| *id as usize == i && self.agrees(*r, t, assumed))
+            }
+            (RECORD, MenuType::Product(fs)) | (VARIANT, MenuType::Variant(fs)) => {
+                match fields_by_id(fs) {
+                    Ok(fields) => {
+                        entry.len() == fields.len()
+                            && entry.iter().zip(fields.iter()).all(|((id1, r), (id2, t))| {
+                                id1 == id2 && self.agrees(*r, t, assumed)
+                            })
+                    }
+                    Err(_) => false,
+                }
+            }
+            _ => false,
+        };
+        // (assumptions hold only along the path from the argument's type)
+        assumed.pop();
+        agrees
+    }
+
+    /// the menu type of the type reference, labeling each field by its identifier;
+    /// a record whose identifiers are 0, 1, 2, ... is a tuple, and an entry that
+    /// refers to itself is a named type.
+    fn menu_type(&self, r: i64, stack: &mut Vec<(i64, bool)>) -> Result<MenuType, Error> {
+        if r < 0 {
+            return Ok(match r {
+                NULL => MenuType::Prim(PrimType::Unit),
+                BOOL => MenuType::Prim(PrimType::Bool),
+                NAT | NAT8 | NAT16 | NAT32 | NAT64 => MenuType::Prim(PrimType::Nat),
+                TEXT => MenuType::Prim(PrimType::Text),
+                code => {
+                    return Err(Error::Invalid(format!(
+                        "unsupported Candid type code: {}",
+                        code
+                    )))
+                }
+            });
+        };
+        let name = Name::Atom(Atom::String(format!("t{}", r)));
+        if let Some(e) = stack.iter_mut().find(|e| e.0 == r) {
+            e.1 = true;
+            return Ok(MenuType::Ref(name));
+        };
+        if stack.len() >= MAX_DEPTH {
+            return Err(Error::Invalid(format!(
+                "Candid types nested more than {} deep",
+                MAX_DEPTH
+            )));
+        };
+        stack.push((r, false));
+        let (code, ref entry) = self.table[r as usize];
+        let mut typs = vec![];
+        for (_, t) in entry.iter() {
+            typs.push(self.menu_type(*t, stack)?)
+        }
+        let (_, recursive) = stack.pop().unwrap();
+        let labels = entry
+            .iter()
+            .map(|(id, _)| Name::Atom(Atom::Usize(*id as usize)));
+        let typ = match code {
+            OPT => MenuType::Option(Box::new(typs.pop().unwrap())),
+            VEC => MenuType::Vec(Box::new(typs.pop().unwrap())),
+            RECORD
+                if !entry.is_empty()
+                    && entry.iter().enumerate().all(|(i, f)| f.0 as usize == i) =>
+            {
+                MenuType::Tup(typs)
+            }
+            RECORD => MenuType::Product(labels.zip(typs).collect()),
+            _ => MenuType::Variant(labels.zip(typs).collect()),
+        };
+        Ok(if recursive {
+            MenuType::Named(name, Box::new(typ))
+        } else {
+            typ
+        })
+    }
+
+    fn text(&mut self) -> Result<String, Error> {
+        let len = self.len()?;
+        let bytes = self.bytes[self.pos..self.pos + len].to_vec();
+        match String::from_utf8(bytes) {
+            Ok(s) => {
+                self.pos += len;
+                Ok(s)
+            }
+            Err(_) => self.error("UTF-8 text"),
+        }
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => {
+                self.pos -= 1;
+                self.error("a bool")
+            }
+        }
+    }
+
+    /// a value of the given (expected) type, whose wire type is known to agree
+    fn value(&mut self, typ: &MenuType) -> Result<MenuTree, Error> {
+        self.enter()?;
+        let typ = &unfold(typ);
+        let tree = match typ {
+            MenuType::Prim(PrimType::Unit) => MenuTree::Unit,
+            MenuType::Prim(PrimType::Bool) => MenuTree::Bool(self.bool()?),
+            MenuType::Prim(PrimType::Nat) => MenuTree::Nat(self.nat_leb128()?),
+            MenuType::Prim(PrimType::Text) => MenuTree::Text(self.text()?),
+            MenuType::Option(t) => {
+                if self.bool()? {
+                    MenuTree::Option(true, Box::new(self.value(t)?), *t.clone())
+                } else {
+                    MenuTree::Option(false, Box::new(MenuTree::Blank(*t.clone())), *t.clone())
+                }
+            }
+            MenuType::Vec(t) => {
+                let mut trees = vec![];
+                for _ in 0..self.len()? {
+                    trees.push(self.value(t)?)
+                }
+                MenuTree::Vec(trees, *t.clone())
+            }
+            MenuType::Tup(ts) => {
+                let mut trees = vec![];
+                for t in ts.iter() {
+                    trees.push((self.value(t)?, t.clone()))
+                }
+                MenuTree::Tup(trees)
+            }
+            MenuType::Product(fs) => {
+                // decode in the order of the field identifiers; then restore the type's order
+                let mut order: Vec<usize> = (0..fs.len()).collect();
+                order.sort_by_key(|i| label_id(&fs[*i].0).unwrap_or(0));
+                let mut trees: Vec<Option<MenuTree>> = vec![None; fs.len()];
+                for i in order {
+                    trees[i] = Some(self.value(&fs[i].1)?)
+                }
+                MenuTree::Product(
+                    fs.iter()
+                        .zip(trees)
+                        .map(|((l, t), tree)| (l.clone(), tree.unwrap(), t.clone()))
+                        .collect(),
+                )
+            }
+            MenuType::Variant(fs) => {
+                let mut order: Vec<usize> = (0..fs.len()).collect();
+                order.sort_by_key(|i| label_id(&fs[*i].0).unwrap_or(0));
+                let k = self.leb128()? as usize;
+                if k >= fs.len() {
+                    return self.error("a variant index");
+                };
+                let i = order[k];
+                let tree = self.value(&fs[i].1)?;
+                variant_choice(fs, i, tree)
+            }
+            MenuType::Named(_, _) | MenuType::Ref(_) => {
+                return Err(Error::Invalid(format!("type without structure: {}", typ)))
+            }
+        };
+        self.depth -= 1;
+        Ok(tree)
+    }
+
+    /// a value of the type reference (and its menu type, from `menu_type`)
+    fn wire_value(&mut self, r: i64, typ: &MenuType) -> Result<MenuTree, Error> {
+        match r {
+            NAT8 => return Ok(MenuTree::Nat(Nat::from(self.fixed(1)?))),
+            NAT16 => return Ok(MenuTree::Nat(Nat::from(self.fixed(2)?))),
+            NAT32 => return Ok(MenuTree::Nat(Nat::from(self.fixed(4)?))),
+            NAT64 => return Ok(MenuTree::Nat(Nat::from(self.fixed(8)?))),
+            r if r < 0 => return self.value(typ),
+            _ => (),
+        };
+        self.enter()?;
+        let (code, entry) = self.table[r as usize].clone();
+        let tree = match (code, &unfold(typ)) {
+            (OPT, MenuType::Option(t)) => {
+                if self.bool()? {
+                    self.path.push(Step::Some);
+                    let tree = self.wire_value(entry[0].1, t)?;
+                    self.path.pop();
+                    Ok(MenuTree::Option(true, Box::new(tree), *t.clone()))
+                } else {
+                    Ok(MenuTree::Option(
+                        false,
+                        Box::new(MenuTree::Blank(*t.clone())),
+                        *t.clone(),
+                    ))
+                }
+            }
+            (VEC, MenuType::Vec(t)) => {
+                let mut trees = vec![];
+                for i in 0..self.len()? {
+                    self.path.push(Step::Pos(i));
+                    trees.push(self.wire_value(entry[0].1, t)?);
+                    self.path.pop();
+                }
+                Ok(MenuTree::Vec(trees, *t.clone()))
+            }
+            (RECORD, MenuType::Tup(ts)) => {
+                let mut trees = vec![];
+                for (i, ((_, r), t)) in entry.iter().zip(ts.iter()).enumerate() {
+                    self.path.push(Step::Pos(i));
+                    trees.push((self.wire_value(*r, t)?, t.clone()));
+                    self.path.pop();
+                }
+                Ok(MenuTree::Tup(trees))
+            }
+            (RECORD, MenuType::Product(fs)) => {
+                let mut trees = vec![];
+                for ((_, r), (l, t)) in entry.iter().zip(fs.iter()) {
+                    self.path.push(Step::Field(l.clone()));
+                    trees.push((l.clone(), self.wire_value(*r, t)?, t.clone()));
+                    self.path.pop();
+                }
+                Ok(MenuTree::Product(trees))
+            }
+            (VARIANT, MenuType::Variant(fs)) => {
+                let i = self.leb128()? as usize;
+                if i >= fs.len() {
+                    return self.error("a variant index");
+                };
+                self.path.push(Step::Choice(fs[i].0.clone()));
+                let tree = self.wire_value(entry[i].1, &fs[i].1)?;
+                self.path.pop();
+                Ok(variant_choice(fs, i, tree))
+            }
+            (_, typ) => Err(Error::Menu(menu::Error::MenuTypeMismatch(
+                self.path.clone(),
+                typ.clone(),
+                self.menu_type(r, &mut vec![])?,
+            ))),
+        }?;
+        self.depth -= 1;
+        Ok(tree)
+    }
+}
+
+/// does each record of the table nest only finitely many records (within itself)?
+/// a record that (transitively) contains itself has no finite values; a decoder
+/// would recurse on its fields without consuming any bytes.
+fn records_are_finite(table: &[(i64, Vec<(u32, i64)>)]) -> bool {
+    let is_record = |r: i64| r >= 0 && table[r as usize].0 == RECORD;
+    // for each record: the number of its fields that are records not (yet) known to be finite
+    let mut pending = vec![0; table.len()];
+    let mut parents = vec![vec![]; table.len()];
+    let mut finite = vec![];
+    for (i, (code, fields)) in table.iter().enumerate() {
+        if *code != RECORD {
+            continue;
+        };
+        for (_, r) in fields.iter().filter(|f| is_record(f.1)) {
+            pending[i] += 1;
+            parents[*r as usize].push(i)
+        }
+        if pending[i] == 0 {
+            finite.push(i)
+        }
+    }
+    let mut count = 0;
+    while let Some(i) = finite.pop() {
+        count += 1;
+        for p in parents[i].iter() {
+            pending[*p] -= 1;
+            if pending[*p] == 0 {
+                finite.push(*p)
+            }
+        }
+    }
+    count == table.iter().filter(|e| e.0 == RECORD).count()
+}
+
+/// the variant tree that chooses the i-th arm (with the given tree), leaving the other arms blank
+fn variant_choice(arms: &[(Label, MenuType)], i: usize, tree: MenuTree) -> MenuTree {
+    let blank = |(l, t): &(Label, MenuType)| (l.clone(), MenuTree::Blank(t.clone()), t.clone());
+    MenuTree::Variant(Box::new(LabelChoice {
+        before: arms[..i].iter().map(blank).collect(),
+        choice: Some((arms[i].0.clone(), tree, arms[i].1.clone())),
+        after: arms[i + 1..].iter().map(blank).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_as, encode, label_id, leb128, nat_leb128, sleb128, Decoder};
+    use menu::{self, Label, LabelChoice, MenuTree, MenuType, Nat, PrimType, Step};
+    use types::lang::{Atom, Error, Name};
+
+    fn decoder(bytes: &[u8]) -> Decoder<'_> {
+        Decoder {
+            bytes,
+            pos: 0,
+            table: vec![],
+            depth: 0,
+            path: vec![],
+        }
+    }
+
+    fn label(s: &str) -> Label {
+        Name::Atom(Atom::String(s.to_string()))
+    }
+
+    fn prim(p: PrimType) -> MenuType {
+        MenuType::Prim(p)
+    }
+
+    fn nat(n: u64) -> MenuTree {
+        MenuTree::Nat(Nat::from(n))
+    }
+
+    /// the message of the arguments, after `DIDL`
+    fn message(body: &[u8]) -> Vec<u8> {
+        let mut bytes = b"DIDL".to_vec();
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    /// encode the arguments as the message, and decode the message as the arguments
+    fn check(args: Vec<(MenuTree, MenuType)>, body: &[u8]) {
+        let bytes = message(body);
+        assert_eq!(encode(&args), Ok(bytes.clone()));
+        let typs: Vec<MenuType> = args.iter().map(|a| a.1.clone()).collect();
+        let trees: Vec<MenuTree> = args.into_iter().map(|a| a.0).collect();
+        assert_eq!(decode_as(&bytes, &typs), Ok(trees));
+        assert!(decode(&bytes).is_ok());
+    }
+
+    #[test]
+    fn leb128_numbers() {
+        let cases: &[(u64, &[u8])] = &[
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (300, &[0xac, 0x02]),
+            (624_485, &[0xe5, 0x8e, 0x26]),
+            (
+                u64::MAX,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            ),
+        ];
+        for (n, bytes) in cases.iter() {
+            let mut out = vec![];
+            leb128(&mut out, *n);
+            assert_eq!(out, *bytes);
+            let mut out = vec![];
+            nat_leb128(&mut out, &Nat::from(*n));
+            assert_eq!(out, *bytes);
+            assert_eq!(decoder(bytes).leb128(), Ok(*n));
+            assert_eq!(decoder(bytes).nat_leb128(), Ok(Nat::from(*n)));
+        }
+        // 2^64 needs a tenth digit of 2, beyond `u64`
+        let big: &[u8] = &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x02];
+        let mut out = vec![];
+        nat_leb128(&mut out, &(Nat::from(1u8) << 64));
+        assert_eq!(out, big);
+        assert_eq!(decoder(big).nat_leb128(), Ok(Nat::from(1u8) << 64));
+        assert!(decoder(big).leb128().is_err());
+        assert!(decoder(&[0x80]).leb128().is_err());
+    }
+
+    #[test]
+    fn sleb128_numbers() {
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[0x00]),
+            (-1, &[0x7f]),
+            (63, &[0x3f]),
+            (64, &[0xc0, 0x00]),
+            (-64, &[0x40]),
+            (-65, &[0xbf, 0x7f]),
+            (-123_456, &[0xc0, 0xbb, 0x78]),
+            // the type codes of nat, and of record
+            (-3, &[0x7d]),
+            (-20, &[0x6c]),
+        ];
+        for (n, bytes) in cases.iter() {
+            let mut out = vec![];
+            sleb128(&mut out, *n);
+            assert_eq!(out, *bytes);
+            assert_eq!(decoder(bytes).sleb128(), Ok(*n));
+        }
+    }
+
+    #[test]
+    fn label_hashes() {
+        // sum of each byte times 223 to the power of its distance from the end, mod 2^32
+        assert_eq!(label_id(&label("a")), Ok(97));
+        assert_eq!(
+            label_id(&label("foo")),
+            Ok(102 * 223 * 223 + 111 * 223 + 111)
+        );
+        assert_eq!(label_id(&label("foo")), Ok(5_097_222));
+        assert_eq!(label_id(&label("bar")), Ok(4_895_187));
+        assert_eq!(label_id(&label("head")), Ok(1_158_359_328));
+        // wraps around 2^32: 1_291_237_008 == (116 * 223^3 + 97 * 223^2 + 105 * 223 + 108) mod 2^32
+        assert_eq!(label_id(&label("tail")), Ok(1_291_237_008));
+        assert_eq!(label_id(&Name::Atom(Atom::Usize(7))), Ok(7));
+        assert!(label_id(&Name::Void).is_err());
+    }
+
+    #[test]
+    fn primitives() {
+        check(vec![], &[0x00, 0x00]);
+        check(
+            vec![(MenuTree::Unit, prim(PrimType::Unit))],
+            &[0x00, 0x01, 0x7f],
+        );
+        check(
+            vec![(MenuTree::Bool(true), prim(PrimType::Bool))],
+            &[0x00, 0x01, 0x7e, 0x01],
+        );
+        check(
+            vec![(nat(300), prim(PrimType::Nat))],
+            &[0x00, 0x01, 0x7d, 0xac, 0x02],
+        );
+        check(
+            vec![(MenuTree::Text("hi".to_string()), prim(PrimType::Text))],
+            &[0x00, 0x01, 0x71, 0x02, b'h', b'i'],
+        );
+    }
+
+    #[test]
+    fn constructors() {
+        let nat_t = prim(PrimType::Nat);
+        let bool_t = prim(PrimType::Bool);
+        let text_t = prim(PrimType::Text);
+        // opt nat, as `some 1` and as `none`
+        check(
+            vec![
+                (
+                    MenuTree::Option(true, Box::new(nat(1)), nat_t.clone()),
+                    MenuType::Option(Box::new(nat_t.clone())),
+                ),
+                (
+                    MenuTree::Option(
+                        false,
+                        Box::new(MenuTree::Blank(nat_t.clone())),
+                        nat_t.clone(),
+                    ),
+                    MenuType::Option(Box::new(nat_t.clone())),
+                ),
+            ],
+            &[0x01, 0x6e, 0x7d, 0x02, 0x00, 0x00, 0x01, 0x01, 0x00],
+        );
+        // vec bool
+        check(
+            vec![(
+                MenuTree::Vec(
+                    vec![MenuTree::Bool(true), MenuTree::Bool(false)],
+                    bool_t.clone(),
+                ),
+                MenuType::Vec(Box::new(bool_t.clone())),
+            )],
+            &[0x01, 0x6d, 0x7e, 0x01, 0x00, 0x02, 0x01, 0x00],
+        );
+        // a tuple is a record with fields 0, 1, ...
+        check(
+            vec![(
+                MenuTree::Tup(vec![
+                    (nat(5), nat_t.clone()),
+                    (MenuTree::Text("a".to_string()), text_t.clone()),
+                ]),
+                MenuType::Tup(vec![nat_t.clone(), text_t.clone()]),
+            )],
+            &[
+                0x01, 0x6c, 0x02, 0x00, 0x7d, 0x01, 0x71, 0x01, 0x00, 0x05, 0x01, b'a',
+            ],
+        );
+        // record { foo : nat; bar : bool }: fields ordered by hash, so `bar` comes first
+        check(
+            vec![(
+                MenuTree::Product(vec![
+                    (label("foo"), nat(2), nat_t.clone()),
+                    (label("bar"), MenuTree::Bool(true), bool_t.clone()),
+                ]),
+                MenuType::Product(vec![
+                    (label("foo"), nat_t.clone()),
+                    (label("bar"), bool_t.clone()),
+                ]),
+            )],
+            &[
+                0x01, 0x6c, 0x02, 0xd3, 0xe3, 0xaa, 0x02, 0x7e, 0x86, 0x8e, 0xb7, 0x02, 0x7d, 0x01,
+                0x00, 0x01, 0x02,
+            ],
+        );
+        // variant { b : nat; a }, choosing `b`, the second arm by hash
+        let arms = vec![
+            (label("b"), nat_t.clone()),
+            (label("a"), prim(PrimType::Unit)),
+        ];
+        check(
+            vec![(
+                MenuTree::Variant(Box::new(LabelChoice {
+                    before: vec![],
+                    choice: Some((label("b"), nat(3), nat_t.clone())),
+                    after: vec![(
+                        label("a"),
+                        MenuTree::Blank(prim(PrimType::Unit)),
+                        prim(PrimType::Unit),
+                    )],
+                })),
+                MenuType::Variant(arms),
+            )],
+            &[
+                0x01, 0x6b, 0x02, 0x61, 0x7f, 0x62, 0x7d, 0x01, 0x00, 0x01, 0x03,
+            ],
+        );
+    }
+
+    #[test]
+    fn recursive_types() {
+        // type list = opt record { head : nat; tail : list }
+        let list = MenuType::Named(
+            label("list"),
+            Box::new(MenuType::Option(Box::new(MenuType::Product(vec![
+                (label("head"), prim(PrimType::Nat)),
+                (label("tail"), MenuType::Ref(label("list"))),
+            ])))),
+        );
+        let bytes = message(&[
+            // t0 = opt t1; t1 = record { head : nat; tail : t0 }
+            0x02, 0x6e, 0x01, 0x6c, 0x02, 0xa0, 0xd2, 0xac, 0xa8, 0x04, 0x7d, 0x90, 0xed, 0xda,
+            0xe7, 0x04, 0x00, // one argument, of type t0
+            0x01, 0x00, // [7], as `opt record { head = 7; tail = null }`
+            0x01, 0x07, 0x00,
+        ]);
+        let trees = decode_as(&bytes, std::slice::from_ref(&list)).unwrap();
+        let args: Vec<(MenuTree, MenuType)> =
+            trees.into_iter().map(|t| (t, list.clone())).collect();
+        assert_eq!(encode(&args), Ok(bytes.clone()));
+        assert!(decode(&bytes).is_ok());
+    }
+
+    #[test]
+    fn untrusted_messages() {
+        // t0 = record { 0 : t0 } has no finite values; decoding must not recurse on it
+        let cyclic = message(&[0x01, 0x6c, 0x01, 0x00, 0x00, 0x01, 0x00]);
+        assert!(decode(&cyclic).is_err());
+        let t = MenuType::Named(
+            label("t"),
+            Box::new(MenuType::Tup(vec![MenuType::Ref(label("t"))])),
+        );
+        assert!(decode_as(&cyclic, &[t]).is_err());
+        // ... nor on records that nest a record cyclically: t0 = record { t1 }, t1 = record { t0 }
+        let cyclic = message(&[
+            0x02, 0x6c, 0x01, 0x00, 0x01, 0x6c, 0x01, 0x00, 0x00, 0x01, 0x00,
+        ]);
+        assert!(decode(&cyclic).is_err());
+
+        // t0 = variant { 0 : t0; 1 : null }, nested `n` deep
+        let nested = |n: usize| {
+            let mut body = vec![0x01, 0x6b, 0x02, 0x00, 0x00, 0x01, 0x7f, 0x01, 0x00];
+            body.extend(std::iter::repeat_n(0x00, n));
+            body.push(0x01);
+            message(&body)
+        };
+        assert!(decode(&nested(super::MAX_DEPTH - 2)).is_ok());
+        match decode(&nested(100_000)) {
+            Err(Error::Binary(_, _)) => (),
+            res => panic!("expected an error for deep nesting, not {:?}", res),
+        }
+    }
+
+    #[test]
+    fn type_mismatches_have_paths() {
+        let (nat_t, text_t) = (prim(PrimType::Nat), prim(PrimType::Text));
+        let text = MenuTree::Text("a".to_string());
+        let bytes = encode(&[(nat(1), nat_t.clone()), (text.clone(), text_t.clone())]).unwrap();
+        assert_eq!(
+            decode_as(&bytes, &[nat_t.clone(), nat_t.clone()]),
+            Err(Error::Menu(menu::Error::MenuTypeMismatch(
+                vec![Step::Pos(1)],
+                nat_t.clone(),
+                text_t.clone()
+            )))
+        );
+        let ill_typed = MenuTree::Vec(vec![nat(1), text], nat_t.clone());
+        assert_eq!(
+            encode(&[(ill_typed, MenuType::Vec(Box::new(nat_t.clone())))]),
+            Err(Error::Menu(menu::Error::MenuTypeMismatch(
+                vec![Step::Pos(0), Step::Pos(1)],
+                nat_t,
+                text_t
+            )))
+        );
+    }
+}
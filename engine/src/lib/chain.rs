@@ -3,21 +3,21 @@ use serde::{Deserialize, Serialize};
 use types::lang::{Dir1D, Media, Name};
 
 /// a chain is an affine linked-list of nodes, each with optionally-named media.
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum Chain {
     Empty,
     Node(Node, Box<Chain>),
 }
 
 /// a node contains media, with an optional name.
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub struct Node {
     pub name: Name,
     pub media: Box<Media>,
 }
 
 /// errors that may arise from chain methods, and `AutoCommand` evaluation.
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum AutoError {
     /// error for insert/delete/replace when name is absent
     AbsentName(Name, Option<Media>),
@@ -29,54 +29,188 @@ pub type Res<R> = Result<R, AutoError>;
 pub type Unit = Res<()>;
 
 impl Chain {
-    #[allow(unused_variables, unused_mut)]
     pub fn insert_start(&mut self, name: Name, media: Media) -> Unit {
-        unimplemented!()
+        let rest = std::mem::replace(self, Chain::Empty);
+        *self = Chain::Node(
+            Node {
+                name,
+                media: Box::new(media),
+            },
+            Box::new(rest),
+        );
+        Ok(())
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn insert_end(&mut self, name: Name, media: Media) -> Unit {
-        unimplemented!()
+        match self {
+            Chain::Empty => self.insert_start(name, media),
+            Chain::Node(_, ref mut rest) => rest.insert_end(name, media),
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn insert_after(&mut self, name: Name, name_new: Name, media: Media) -> Unit {
-        unimplemented!()
+        match self {
+            Chain::Empty => Err(AutoError::AbsentName(name, Some(media))),
+            Chain::Node(ref node, ref mut rest) => {
+                if node.name == name {
+                    rest.insert_start(name_new, media)
+                } else {
+                    rest.insert_after(name, name_new, media)
+                }
+            }
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn insert_before(&mut self, name: Name, name_new: Name, media: Media) -> Unit {
-        unimplemented!()
+        let found = match self {
+            Chain::Empty => return Err(AutoError::AbsentName(name, Some(media))),
+            Chain::Node(ref node, _) => node.name == name,
+        };
+        if found {
+            self.insert_start(name_new, media)
+        } else {
+            match self {
+                Chain::Empty => unreachable!(),
+                Chain::Node(_, ref mut rest) => rest.insert_before(name, name_new, media),
+            }
+        }
     }
 
-    #[allow(unused_variables, unused_mut)]
     pub fn delete_start(&mut self) -> Res<Media> {
-        unimplemented!()
+        match std::mem::replace(self, Chain::Empty) {
+            Chain::Empty => Err(AutoError::AbsentMedia(None)),
+            Chain::Node(node, rest) => {
+                *self = *rest;
+                Ok(*node.media)
+            }
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn delete_end(&mut self) -> Res<Media> {
-        unimplemented!()
+        let is_last = match self {
+            Chain::Empty => return Err(AutoError::AbsentMedia(None)),
+            Chain::Node(_, ref rest) => rest.is_empty(),
+        };
+        if is_last {
+            self.delete_start()
+        } else {
+            match self {
+                Chain::Empty => unreachable!(),
+                Chain::Node(_, ref mut rest) => rest.delete_end(),
+            }
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn delete_after(&mut self, name: Name) -> Res<Media> {
-        unimplemented!()
+        match self {
+            Chain::Empty => Err(AutoError::AbsentName(name, None)),
+            Chain::Node(ref node, ref mut rest) => {
+                if node.name == name {
+                    rest.delete_start()
+                        .map_err(|_| AutoError::AbsentMedia(Some(name)))
+                } else {
+                    rest.delete_after(name)
+                }
+            }
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn delete_before(&mut self, name: Name) -> Res<Media> {
-        unimplemented!()
+        let (is_first, is_next) = match self {
+            Chain::Empty => return Err(AutoError::AbsentName(name, None)),
+            Chain::Node(ref node, ref rest) => (
+                node.name == name,
+                match **rest {
+                    Chain::Node(ref next, _) => next.name == name,
+                    Chain::Empty => false,
+                },
+            ),
+        };
+        if is_first {
+            Err(AutoError::AbsentMedia(Some(name)))
+        } else if is_next {
+            self.delete_start()
+        } else {
+            match self {
+                Chain::Empty => unreachable!(),
+                Chain::Node(_, ref mut rest) => rest.delete_before(name),
+            }
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn replace_start(&mut self, media: Media) -> Res<Media> {
-        unimplemented!()
+        match self {
+            Chain::Empty => Err(AutoError::AbsentMedia(None)),
+            Chain::Node(ref mut node, _) => Ok(std::mem::replace(&mut *node.media, media)),
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn replace_end(&mut self, media: Media) -> Res<Media> {
-        unimplemented!()
+        match self {
+            Chain::Empty => Err(AutoError::AbsentMedia(None)),
+            Chain::Node(ref mut node, ref mut rest) => {
+                if rest.is_empty() {
+                    Ok(std::mem::replace(&mut *node.media, media))
+                } else {
+                    rest.replace_end(media)
+                }
+            }
+        }
     }
-    #[allow(unused_variables, unused_mut)]
     pub fn replace(&mut self, name: Name, media: Media) -> Res<Media> {
-        unimplemented!()
+        match self {
+            Chain::Empty => Err(AutoError::AbsentName(name, Some(media))),
+            Chain::Node(ref mut node, ref mut rest) => {
+                if node.name == name {
+                    Ok(std::mem::replace(&mut *node.media, media))
+                } else {
+                    rest.replace(name, media)
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Chain::Empty => true,
+            Chain::Node(_, _) => false,
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Chain::Empty => 0,
+            Chain::Node(_, ref rest) => 1 + rest.len(),
+        }
+    }
+    pub fn push_node(&mut self, node: Node) {
+        let rest = std::mem::replace(self, Chain::Empty);
+        *self = Chain::Node(node, Box::new(rest));
+    }
+    pub fn pop_node(&mut self) -> Option<Node> {
+        match std::mem::replace(self, Chain::Empty) {
+            Chain::Empty => None,
+            Chain::Node(node, rest) => {
+                *self = *rest;
+                Some(node)
+            }
+        }
+    }
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { chain: self }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+/// iterates over the nodes of a chain, from start to end.
+pub struct Iter<'a> {
+    chain: &'a Chain,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Node;
+    fn next(&mut self) -> Option<&'a Node> {
+        match self.chain {
+            Chain::Empty => None,
+            Chain::Node(ref node, ref rest) => {
+                self.chain = rest;
+                Some(node)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum AutoCommand {
     InsertStart(Name, Media),
     InsertEnd(Name, Media),
@@ -124,6 +258,9 @@ impl AutoCommand {
     }
 }
 
+/// the history-_independent_ state of the editor, as a zipper:
+/// the `head` holds the nodes before the cursor, nearest node first;
+/// the `tail` holds the nodes after the cursor, in chain order.
 #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
 pub struct EditorState {
     pub head: Chain,
@@ -137,13 +274,13 @@ pub struct Editor {
     pub state: Option<EditorState>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum InitCommand {
     Empty,
     String(String),
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum EditCommand {
     MoveRel(Dir1D),
     MoveAbs(usize),
@@ -155,7 +292,7 @@ pub enum EditCommand {
 }
 
 /// commands that advance the evolution of a bitmap
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
 pub enum Command {
     /// commands that create new bitmaps
     Init(InitCommand),
@@ -170,22 +307,23 @@ pub enum Command {
 }
 
 pub mod semantics {
-    //use super::{Chain, Command, AutoCommand, EditCommand, InitCommand, Editor, EditorState};
-    use super::{AutoCommand, Chain, Command, EditCommand, EditorState, Media, Res};
+    use super::{AutoCommand, Chain, Command, EditCommand, Editor, EditorState, InitCommand};
+    use super::{AutoError, Dir1D, Media, Node, Res};
+    use types::lang::{Atom, Error};
+    use types::util::name_of_usize;
 
     // todo -- if we instead assume a moved Command rather than a borrowed one, we avoid clone()s here?
     //         OTOH, if we use a borrow, the Command constructors are affine too, which can be annoying, esp for logging.
 
-    #[allow(unused_variables, unused_mut)]
     pub fn chain_eval(chain: &mut Chain, command: &AutoCommand) -> Res<Option<Media>> {
         trace!("chain_eval {:?} ...", command);
         use self::AutoCommand::*;
         pub fn some(r: Res<Media>) -> Res<Option<Media>> {
-            r.map(|m| Some(m))
-        };
+            r.map(Some)
+        }
         pub fn none(r: Res<()>) -> Res<Option<Media>> {
             r.map(|_| None)
-        };
+        }
         let res = match &command {
             InsertStart(ref n, ref m) => none(chain.insert_start(n.clone(), m.clone())),
             DeleteStart => some(chain.delete_start()),
@@ -207,18 +345,712 @@ pub mod semantics {
         res
     }
 
-    #[allow(unused_variables, unused_mut)]
-    pub fn editor_state_eval(
-        editor: &mut EditorState,
-        command: &EditCommand,
-    ) -> Result<(), String> {
+    /// a chain of one node per character, each named by its position.
+    pub fn chain_of_string(s: &str) -> Chain {
+        let mut chain = Chain::Empty;
+        for (i, c) in s.chars().enumerate() {
+            let media = Media::Atom(Atom::String(c.to_string()));
+            chain.insert_end(name_of_usize(i), media).unwrap();
+        }
+        chain
+    }
+
+    /// the full chain that the editor state represents, from start to end.
+    pub fn chain_of_editor_state(editor: &EditorState) -> Chain {
+        let mut chain = editor.tail.clone();
+        if let Some(ref node) = editor.cursor {
+            chain.push_node(node.clone());
+        }
+        for node in editor.head.iter() {
+            chain.push_node(node.clone());
+        }
+        chain
+    }
+
+    /// an editor state focused on the first node of the given chain, if any.
+    pub fn editor_state_of_chain(chain: Chain) -> EditorState {
+        let mut tail = chain;
+        let cursor = tail.pop_node();
+        EditorState {
+            head: Chain::Empty,
+            cursor,
+            tail,
+        }
+    }
+
+    pub fn move_rel(editor: &mut EditorState, dir: &Dir1D) -> Result<(), Error> {
+        let (from, to) = match dir {
+            Dir1D::Forward => (&mut editor.tail, &mut editor.head),
+            Dir1D::Backward => (&mut editor.head, &mut editor.tail),
+        };
+        match from.pop_node() {
+            None => Err(Error::Chain(AutoError::AbsentMedia(None))),
+            Some(node) => {
+                if let Some(cursor) = editor.cursor.take() {
+                    to.push_node(cursor)
+                };
+                editor.cursor = Some(node);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn editor_state_eval(editor: &mut EditorState, command: &EditCommand) -> Result<(), Error> {
         trace!("editor_state_eval: {:?}", command);
-        unimplemented!()
+        let res = match command {
+            EditCommand::MoveRel(dir) => move_rel(editor, dir),
+            EditCommand::MoveBegin => {
+                while !editor.head.is_empty() {
+                    move_rel(editor, &Dir1D::Backward)?
+                }
+                Ok(())
+            }
+            EditCommand::MoveEnd => {
+                while !editor.tail.is_empty() {
+                    move_rel(editor, &Dir1D::Forward)?
+                }
+                Ok(())
+            }
+            EditCommand::MoveAbs(pos) => {
+                let len = chain_of_editor_state(editor).len();
+                if *pos < len {
+                    editor_state_eval(editor, &EditCommand::MoveBegin)?;
+                    for _ in 0..*pos {
+                        move_rel(editor, &Dir1D::Forward)?
+                    }
+                    Ok(())
+                } else {
+                    Err(Error::OutOfBounds(vec![*pos]))
+                }
+            }
+            EditCommand::Insert(ref dir, ref name, ref media) => {
+                let node = Node {
+                    name: name.clone(),
+                    media: Box::new(media.clone()),
+                };
+                if let Some(cursor) = editor.cursor.take() {
+                    match dir {
+                        Dir1D::Forward => editor.head.push_node(cursor),
+                        Dir1D::Backward => editor.tail.push_node(cursor),
+                    }
+                };
+                editor.cursor = Some(node);
+                Ok(())
+            }
+            EditCommand::Delete(ref dir) => match editor.cursor.take() {
+                None => Err(Error::Chain(AutoError::AbsentMedia(None))),
+                Some(_) => {
+                    editor.cursor = match dir {
+                        Dir1D::Forward => editor.tail.pop_node().or_else(|| editor.head.pop_node()),
+                        Dir1D::Backward => {
+                            editor.head.pop_node().or_else(|| editor.tail.pop_node())
+                        }
+                    };
+                    Ok(())
+                }
+            },
+            EditCommand::Replace(ref media) => match editor.cursor {
+                None => Err(Error::Chain(AutoError::AbsentMedia(None))),
+                Some(ref mut node) => {
+                    *node.media = media.clone();
+                    Ok(())
+                }
+            },
+        };
+        trace!("editor_state_eval {:?} ==> {:?}", command, res);
+        res
+    }
+
+    pub fn editor_eval(editor: &mut Editor, command: &Command) -> Result<(), Error> {
+        let num = editor.history.len();
+        debug!("#{}: editor_eval {:?}", num, command);
+        editor.history.push(command.clone());
+        let res = match command {
+            Command::Init(ref command) => {
+                editor.state = Some(editor_state_of_chain(match command {
+                    InitCommand::Empty => Chain::Empty,
+                    InitCommand::String(ref s) => chain_of_string(s),
+                }));
+                Ok(())
+            }
+            // auto commands operate on the chain on one side of the cursor:
+            // forward, the tail; backward, the head (whose start is nearest the cursor).
+            Command::Auto(ref dir, ref command) => match editor.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => {
+                    let chain = match dir {
+                        Dir1D::Forward => &mut st.tail,
+                        Dir1D::Backward => &mut st.head,
+                    };
+                    chain_eval(chain, command).map(|_| ()).map_err(Error::Chain)
+                }
+            },
+            Command::Edit(ref command) => match editor.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => editor_state_eval(st, command),
+            },
+        };
+        info!("#{}: editor_eval {:?} ==> {:?}", num, command, res);
+        res
+    }
+}
+
+pub mod io {
+    use super::{Dir1D, EditCommand, EditorState};
+    use render::{BitmapAtts, FlowAtts, FrameType, Render, TextAtts};
+    use types::event::{Event, Quit};
+    use types::lang::{Dir2D, Name};
+    use types::render::{self, Color, Dim, Fill};
+
+    pub fn edit_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, Quit> {
+        match event {
+            Event::Quit => Err(Quit),
+            Event::KeyDown(ref kei) => match kei.key.as_str() {
+                "Escape" => Err(Quit),
+                "ArrowLeft" => Ok(vec![EditCommand::MoveRel(Dir1D::Backward)]),
+                "ArrowRight" => Ok(vec![EditCommand::MoveRel(Dir1D::Forward)]),
+                "ArrowUp" => Ok(vec![EditCommand::MoveBegin]),
+                "ArrowDown" => Ok(vec![EditCommand::MoveEnd]),
+                "Backspace" => Ok(vec![EditCommand::Delete(Dir1D::Backward)]),
+                _ => Ok(vec![]),
+            },
+            _ => Ok(vec![]),
+        }
     }
 
-    #[allow(unused_variables, unused_mut)]
-    pub fn editor_eval(editor: &mut EditorState, command: &Command) -> Result<(), String> {
-        trace!("editor_eval: {:?}", command);
-        unimplemented!()
+    pub fn render_elms(edit_state: &EditorState) -> Result<render::Elms, String> {
+        let mut r = Render::new();
+
+        // to do -- get these constants from the editor state
+        fn glyph_flow() -> FlowAtts {
+            FlowAtts {
+                dir: Dir2D::Right,
+                intra_pad: 1,
+                inter_pad: 1,
+            }
+        }
+        fn name_atts() -> TextAtts {
+            TextAtts {
+                zoom: 2,
+                fg_fill: Fill::Closed(Color::RGB(200, 200, 255)),
+                bg_fill: Fill::None,
+                glyph_dim: Dim {
+                    width: 5,
+                    height: 5,
+                },
+                glyph_flow: glyph_flow(),
+            }
+        }
+        fn media_atts() -> TextAtts {
+            TextAtts {
+                zoom: 3,
+                fg_fill: Fill::Closed(Color::RGB(255, 255, 255)),
+                ..name_atts()
+            }
+        }
+        fn bitmap_atts() -> BitmapAtts {
+            BitmapAtts {
+                zoom: 4,
+                fill_isset: Fill::Closed(Color::RGB(255, 255, 255)),
+                fill_notset: Fill::Closed(Color::RGB(0, 0, 0)),
+            }
+        }
+        fn node_flow() -> FlowAtts {
+            FlowAtts {
+                dir: Dir2D::Down,
+                intra_pad: 2,
+                inter_pad: 4,
+            }
+        }
+        fn render_node(node: &super::Node, is_cursor: bool, r: &mut Render) {
+            r.begin(&node.name, FrameType::Flow(node_flow()));
+            if is_cursor {
+                r.fill(Fill::Open(Color::RGB(150, 255, 150), 1));
+            } else {
+                r.fill(Fill::Open(Color::RGB(100, 80, 100), 1));
+            }
+            r.name(&node.name, &name_atts());
+            r.media(&node.media, &media_atts(), &bitmap_atts());
+            r.end();
+        }
+
+        r.begin(
+            &Name::Void,
+            FrameType::Flow(FlowAtts {
+                dir: Dir2D::Right,
+                intra_pad: 2,
+                inter_pad: 2,
+            }),
+        );
+        let head: Vec<_> = edit_state.head.iter().collect();
+        for node in head.iter().rev() {
+            render_node(node, false, &mut r);
+        }
+        if let Some(ref node) = edit_state.cursor {
+            render_node(node, true, &mut r);
+        }
+        for node in edit_state.tail.iter() {
+            render_node(node, false, &mut r);
+        }
+        r.end();
+        Ok(r.into_elms())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::io::{edit_commands_of_event, render_elms};
+    use super::semantics::{chain_eval, chain_of_editor_state, chain_of_string, editor_eval};
+    use super::AutoCommand::*;
+    use super::{AutoCommand, AutoError, Chain, Command, EditCommand, Editor, EditorState};
+    use super::{InitCommand, Node};
+    use types::event::{Event, KeyEventInfo, Quit};
+    use types::lang::{Atom, Dir1D, Error, Media, Name};
+    use types::render::{Color, Elm, Fill};
+    use types::util::{name_of_str, name_of_usize};
+
+    fn text(s: &str) -> Media {
+        Media::Atom(Atom::String(s.to_string()))
+    }
+
+    fn names(chain: &Chain) -> Vec<Name> {
+        chain.iter().map(|node| node.name.clone()).collect()
+    }
+
+    /// run the commands in order, returning the media that each one removes (or replaces)
+    fn run(chain: &mut Chain, commands: &[AutoCommand]) -> Vec<Option<Media>> {
+        commands
+            .iter()
+            .map(|c| chain_eval(chain, c).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn compose_inserts() {
+        let mut chain = Chain::Empty;
+        run(
+            &mut chain,
+            &[
+                InsertEnd(name_of_str("b"), text("B")),
+                InsertStart(name_of_str("a"), text("A")),
+                InsertEnd(name_of_str("d"), text("D")),
+                InsertAfter(name_of_str("b"), name_of_str("c"), text("C")),
+                InsertBefore(name_of_str("a"), name_of_str("z"), text("Z")),
+            ],
+        );
+        let expected: Vec<Name> = ["z", "a", "b", "c", "d"]
+            .iter()
+            .map(|n| name_of_str(n))
+            .collect();
+        assert_eq!(names(&chain), expected);
+        assert_eq!(chain.len(), 5);
+    }
+
+    #[test]
+    fn compose_deletes_and_replaces() {
+        let mut chain = chain_of_string("abcde");
+        let removed = run(
+            &mut chain,
+            &[
+                DeleteStart,
+                DeleteEnd,
+                DeleteAfter(name_of_usize(1)),
+                ReplaceStart(text("B")),
+                ReplaceEnd(text("D")),
+            ],
+        );
+        assert_eq!(
+            removed,
+            vec![
+                Some(text("a")),
+                Some(text("e")),
+                Some(text("c")),
+                Some(text("b")),
+                Some(text("d")),
+            ]
+        );
+        assert_eq!(names(&chain), vec![name_of_usize(1), name_of_usize(3)]);
+        assert_eq!(
+            chain_eval(&mut chain, &Replace(name_of_usize(3), text("x"))),
+            Ok(Some(text("D")))
+        );
+        assert_eq!(
+            chain_eval(&mut chain, &DeleteBefore(name_of_usize(3))),
+            Ok(Some(text("B")))
+        );
+        assert_eq!(names(&chain), vec![name_of_usize(3)]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut chain = chain_of_string("ab");
+        let absent = name_of_str("x");
+        assert_eq!(
+            chain_eval(
+                &mut chain,
+                &InsertAfter(absent.clone(), absent.clone(), text("X"))
+            ),
+            Err(AutoError::AbsentName(absent.clone(), Some(text("X"))))
+        );
+        assert_eq!(
+            chain_eval(&mut chain, &DeleteAfter(absent.clone())),
+            Err(AutoError::AbsentName(absent, None))
+        );
+        assert_eq!(
+            chain_eval(&mut chain, &DeleteAfter(name_of_usize(1))),
+            Err(AutoError::AbsentMedia(Some(name_of_usize(1))))
+        );
+        assert_eq!(
+            chain_eval(&mut chain, &DeleteBefore(name_of_usize(0))),
+            Err(AutoError::AbsentMedia(Some(name_of_usize(0))))
+        );
+        let mut empty = Chain::Empty;
+        for c in &[
+            DeleteStart,
+            DeleteEnd,
+            ReplaceStart(text("X")),
+            ReplaceEnd(text("X")),
+        ] {
+            assert_eq!(chain_eval(&mut empty, c), Err(AutoError::AbsentMedia(None)))
+        }
+        // failed commands leave the chain as it was
+        assert_eq!(chain, chain_of_string("ab"));
+    }
+
+    /// the command that undoes the given one, from the media that it removed (or replaced)
+    fn inverse(command: &AutoCommand, removed: &Option<Media>, before: &Chain) -> AutoCommand {
+        let removed = || removed.clone().unwrap();
+        match command {
+            InsertStart(_, _) => DeleteStart,
+            InsertEnd(_, _) => DeleteEnd,
+            InsertAfter(n, _, _) => DeleteAfter(n.clone()),
+            InsertBefore(n, _, _) => DeleteBefore(n.clone()),
+            DeleteStart => InsertStart(before.iter().next().unwrap().name.clone(), removed()),
+            DeleteEnd => InsertEnd(before.iter().last().unwrap().name.clone(), removed()),
+            DeleteAfter(n) => {
+                let nodes: Vec<_> = before.iter().collect();
+                let i = nodes.iter().position(|node| &node.name == n).unwrap();
+                InsertAfter(n.clone(), nodes[i + 1].name.clone(), removed())
+            }
+            DeleteBefore(n) => {
+                let nodes: Vec<_> = before.iter().collect();
+                let i = nodes.iter().position(|node| &node.name == n).unwrap();
+                InsertBefore(n.clone(), nodes[i - 1].name.clone(), removed())
+            }
+            Replace(n, _) => Replace(n.clone(), removed()),
+            ReplaceStart(_) => ReplaceStart(removed()),
+            ReplaceEnd(_) => ReplaceEnd(removed()),
+        }
+    }
+
+    #[test]
+    fn undo_redo() {
+        let start = chain_of_string("abc");
+        let commands = vec![
+            InsertAfter(name_of_usize(0), name_of_str("x"), text("X")),
+            DeleteBefore(name_of_usize(2)),
+            Replace(name_of_str("x"), text("Y")),
+            InsertStart(name_of_str("s"), text("S")),
+            DeleteEnd,
+            ReplaceEnd(text("Z")),
+            InsertBefore(name_of_usize(0), name_of_str("w"), text("W")),
+            DeleteAfter(name_of_str("s")),
+            InsertEnd(name_of_str("e"), text("E")),
+            DeleteStart,
+            ReplaceStart(text("V")),
+        ];
+        // do, recording each state and the inverse of each command
+        let mut chain = start.clone();
+        let mut states = vec![chain.clone()];
+        let mut undos = vec![];
+        for c in commands.iter() {
+            let before = chain.clone();
+            let removed = chain_eval(&mut chain, c).unwrap();
+            undos.push(inverse(c, &removed, &before));
+            states.push(chain.clone());
+        }
+        // undo, back to the start
+        for (i, undo) in undos.iter().enumerate().rev() {
+            chain_eval(&mut chain, undo).unwrap();
+            assert_eq!(chain, states[i]);
+        }
+        assert_eq!(chain, start);
+        // redo, forward to the end
+        for (i, c) in commands.iter().enumerate() {
+            chain_eval(&mut chain, c).unwrap();
+            assert_eq!(chain, states[i + 1]);
+        }
+    }
+
+    /// an editor, initialized with one node per character of the string
+    fn editor_of_string(s: &str) -> Editor {
+        let mut editor = Editor {
+            history: vec![],
+            state: None,
+        };
+        editor_eval(
+            &mut editor,
+            &Command::Init(InitCommand::String(s.to_string())),
+        )
+        .unwrap();
+        editor
+    }
+
+    fn edit(editor: &mut Editor, command: EditCommand) -> Result<(), Error> {
+        editor_eval(editor, &Command::Edit(command))
+    }
+
+    /// the names of the head (nearest the cursor first), the cursor, and the tail
+    fn zipper(editor: &Editor) -> (Vec<Name>, Option<Name>, Vec<Name>) {
+        let st = editor.state.as_ref().unwrap();
+        (
+            names(&st.head),
+            st.cursor.as_ref().map(|node| node.name.clone()),
+            names(&st.tail),
+        )
+    }
+
+    fn nums(ns: &[usize]) -> Vec<Name> {
+        ns.iter().map(|&n| name_of_usize(n)).collect()
+    }
+
+    #[test]
+    fn editor_moves_within_both_ends() {
+        let mut editor = editor_of_string("abc");
+        assert_eq!(
+            zipper(&editor),
+            (vec![], Some(name_of_usize(0)), nums(&[1, 2]))
+        );
+
+        // nothing precedes the start
+        assert_eq!(
+            edit(&mut editor, EditCommand::MoveRel(Dir1D::Backward)),
+            Err(Error::Chain(AutoError::AbsentMedia(None)))
+        );
+        assert_eq!(
+            zipper(&editor),
+            (vec![], Some(name_of_usize(0)), nums(&[1, 2]))
+        );
+
+        edit(&mut editor, EditCommand::MoveRel(Dir1D::Forward)).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (nums(&[0]), Some(name_of_usize(1)), nums(&[2]))
+        );
+
+        edit(&mut editor, EditCommand::MoveEnd).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (nums(&[1, 0]), Some(name_of_usize(2)), vec![])
+        );
+
+        // nothing follows the end
+        assert_eq!(
+            edit(&mut editor, EditCommand::MoveRel(Dir1D::Forward)),
+            Err(Error::Chain(AutoError::AbsentMedia(None)))
+        );
+        assert_eq!(
+            zipper(&editor),
+            (nums(&[1, 0]), Some(name_of_usize(2)), vec![])
+        );
+
+        edit(&mut editor, EditCommand::MoveBegin).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (vec![], Some(name_of_usize(0)), nums(&[1, 2]))
+        );
+
+        edit(&mut editor, EditCommand::MoveAbs(2)).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (nums(&[1, 0]), Some(name_of_usize(2)), vec![])
+        );
+        assert_eq!(
+            edit(&mut editor, EditCommand::MoveAbs(3)),
+            Err(Error::OutOfBounds(vec![3]))
+        );
+
+        // moving never reorders the chain
+        let st = editor.state.as_ref().unwrap();
+        assert_eq!(chain_of_editor_state(st), chain_of_string("abc"));
+        // the history holds every command, including those that failed
+        assert_eq!(editor.history.len(), 8);
+    }
+
+    #[test]
+    fn editor_edits_at_both_ends() {
+        let mut editor = editor_of_string("ab");
+        let (x, y) = (name_of_str("x"), name_of_str("y"));
+
+        // insert before the start, and after the end
+        edit(
+            &mut editor,
+            EditCommand::Insert(Dir1D::Backward, x.clone(), text("X")),
+        )
+        .unwrap();
+        assert_eq!(zipper(&editor), (vec![], Some(x.clone()), nums(&[0, 1])));
+        edit(&mut editor, EditCommand::MoveEnd).unwrap();
+        edit(
+            &mut editor,
+            EditCommand::Insert(Dir1D::Forward, y.clone(), text("Y")),
+        )
+        .unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (
+                vec![name_of_usize(1), name_of_usize(0), x.clone()],
+                Some(y.clone()),
+                vec![]
+            )
+        );
+        edit(&mut editor, EditCommand::Replace(text("Z"))).unwrap();
+
+        // at the end, deleting forward falls back to the node before
+        edit(&mut editor, EditCommand::Delete(Dir1D::Forward)).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (
+                vec![name_of_usize(0), x.clone()],
+                Some(name_of_usize(1)),
+                vec![]
+            )
+        );
+
+        // at the start, deleting backward falls back to the node after
+        edit(&mut editor, EditCommand::MoveBegin).unwrap();
+        edit(&mut editor, EditCommand::Delete(Dir1D::Backward)).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (vec![], Some(name_of_usize(0)), nums(&[1]))
+        );
+
+        // deleting the last nodes leaves the cursor empty
+        edit(&mut editor, EditCommand::Delete(Dir1D::Forward)).unwrap();
+        edit(&mut editor, EditCommand::Delete(Dir1D::Forward)).unwrap();
+        assert_eq!(zipper(&editor), (vec![], None, vec![]));
+        for command in [
+            EditCommand::Delete(Dir1D::Backward),
+            EditCommand::Replace(text("Z")),
+            EditCommand::MoveRel(Dir1D::Forward),
+        ] {
+            assert_eq!(
+                edit(&mut editor, command),
+                Err(Error::Chain(AutoError::AbsentMedia(None)))
+            );
+        }
+        edit(
+            &mut editor,
+            EditCommand::Insert(Dir1D::Forward, x.clone(), text("X")),
+        )
+        .unwrap();
+        assert_eq!(zipper(&editor), (vec![], Some(x), vec![]));
+    }
+
+    #[test]
+    fn editor_auto_commands_act_beside_the_cursor() {
+        let mut editor = editor_of_string("abc");
+        edit(&mut editor, EditCommand::MoveRel(Dir1D::Forward)).unwrap();
+        let auto =
+            |editor: &mut Editor, dir, command| editor_eval(editor, &Command::Auto(dir, command));
+        // the start of the head is nearest the cursor
+        auto(
+            &mut editor,
+            Dir1D::Backward,
+            InsertStart(name_of_str("h"), text("H")),
+        )
+        .unwrap();
+        auto(&mut editor, Dir1D::Forward, DeleteEnd).unwrap();
+        assert_eq!(
+            zipper(&editor),
+            (
+                vec![name_of_str("h"), name_of_usize(0)],
+                Some(name_of_usize(1)),
+                vec![]
+            )
+        );
+        assert_eq!(
+            auto(&mut editor, Dir1D::Forward, DeleteStart),
+            Err(Error::Chain(AutoError::AbsentMedia(None)))
+        );
+        let mut editor = Editor {
+            history: vec![],
+            state: None,
+        };
+        assert_eq!(
+            auto(&mut editor, Dir1D::Forward, DeleteStart),
+            Err(Error::NoEditorState)
+        );
+    }
+
+    fn key(key: &str) -> Event {
+        Event::KeyDown(KeyEventInfo {
+            key: key.to_string(),
+            alt: false,
+            ctrl: false,
+            meta: false,
+            shift: false,
+        })
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!(
+            edit_commands_of_event(&key("ArrowLeft")),
+            Ok(vec![EditCommand::MoveRel(Dir1D::Backward)])
+        );
+        assert_eq!(
+            edit_commands_of_event(&key("ArrowDown")),
+            Ok(vec![EditCommand::MoveEnd])
+        );
+        assert_eq!(
+            edit_commands_of_event(&key("Backspace")),
+            Ok(vec![EditCommand::Delete(Dir1D::Backward)])
+        );
+        assert_eq!(edit_commands_of_event(&key("q")), Ok(vec![]));
+        assert_eq!(edit_commands_of_event(&key("Escape")), Err(Quit));
+        assert_eq!(edit_commands_of_event(&Event::Quit), Err(Quit));
+    }
+
+    #[test]
+    fn render_in_chain_order() {
+        let mut editor = editor_of_string("abc");
+        edit(&mut editor, EditCommand::MoveEnd).unwrap();
+        edit(&mut editor, EditCommand::MoveRel(Dir1D::Backward)).unwrap();
+        let elms = render_elms(editor.state.as_ref().unwrap()).unwrap();
+        let nodes = match elms.as_slice() {
+            [Elm::Node(frame)] => frame
+                .children
+                .iter()
+                .map(|elm| match elm {
+                    Elm::Node(node) => {
+                        let is_cursor = match node.fill {
+                            Fill::Open(Color::RGB(150, 255, 150), _) => true,
+                            _ => false,
+                        };
+                        (node.name.clone(), is_cursor)
+                    }
+                    elm => panic!("expected a node, not {:?}", elm),
+                })
+                .collect::<Vec<_>>(),
+            elms => panic!("expected one frame, not {:?}", elms),
+        };
+        assert_eq!(
+            nodes,
+            vec![
+                (name_of_usize(0), false),
+                (name_of_usize(1), true),
+                (name_of_usize(2), false),
+            ]
+        );
+        // an empty chain renders an empty frame
+        let empty = EditorState {
+            head: Chain::Empty,
+            cursor: None::<Node>,
+            tail: Chain::Empty,
+        };
+        match render_elms(&empty).unwrap().as_slice() {
+            [Elm::Node(frame)] => assert!(frame.children.is_empty()),
+            elms => panic!("expected one frame, not {:?}", elms),
+        }
     }
 }
@@ -1,15 +1,21 @@
 // to-do/question: rename this module to 'engine'?
 
 use bitmap;
+use chain;
+use grid;
 use menu;
+use pixmap;
 
 pub use super::types::{
-    event::Event,
+    event::{Event, Quit},
     lang::{Command, Editor, State},
     render,
 };
+use hashcons::merkle::Merkle;
+use types::adapton::{Closure, Context, Env, NodeId};
+use types::lang::{self, Exp, Media, Name};
 
-pub fn commands_of_event(state: &mut State, event: &Event) -> Result<Vec<Command>, ()> {
+pub fn commands_of_event(state: &mut State, event: &Event) -> Result<Vec<Command>, Quit> {
     debug!("commands_of_event {:?}", event);
     let res = match &mut state.editor {
         &mut Editor::Bitmap(ref _ed) => {
@@ -22,6 +28,12 @@ pub fn commands_of_event(state: &mut State, event: &Event) -> Result<Vec<Command
                     .collect()
             })
         }
+        Editor::Pixmap(_) => pixmap::io::edit_commands_of_event(event).map(|ed_cmds| {
+            ed_cmds
+                .into_iter()
+                .map(|ed_cmd| Command::Pixmap(pixmap::Command::Edit(ed_cmd)))
+                .collect()
+        }),
         &mut Editor::Menu(ref mut ed) => {
             // to do -- insert a name into each command that is unique,
             // but whose structure encodes a wallclock timestamp, among other sequence numbers.
@@ -38,49 +50,313 @@ pub fn commands_of_event(state: &mut State, event: &Event) -> Result<Vec<Command
                     _ => unreachable!()
             }
         }
-        &mut Editor::Chain(ref mut _ed) => unimplemented!(),
-        &mut Editor::Grid(ref mut _ed) => unimplemented!(),
+        &mut Editor::Chain(ref _ed) => chain::io::edit_commands_of_event(event).map(|ed_cmds| {
+            ed_cmds
+                .into_iter()
+                .map(|ed_cmd| Command::Chain(chain::Command::Edit(ed_cmd)))
+                .collect()
+        }),
+        &mut Editor::Grid(ref _ed) => grid::io::edit_commands_of_event(event).map(|ed_cmds| {
+            ed_cmds
+                .into_iter()
+                .map(|ed_cmd| Command::Grid(grid::Command::Edit(ed_cmd)))
+                .collect()
+        }),
     };
     debug!("commands_of_event {:?} ==> {:?}", event, res);
     res
 }
 
-pub fn command_eval(state: &mut State, command: &Command) -> Result<(), String> {
+pub fn command_eval(state: &mut State, command: &Command) -> Result<(), lang::Error> {
     debug!("command_eval {:?}", command);
     let res = match (command, &mut state.editor) {
         (&Command::Bitmap(ref bc), &mut Editor::Bitmap(ref mut be)) => {
             super::bitmap::semantics::editor_eval(be, bc)
         }
-        (&Command::Bitmap(ref _bc), _) => Err("bitmap editor expected bitmap command".to_string()),
-        (_, &mut Editor::Bitmap(ref mut _be)) => {
-            Err("bitmap command for non-bitmap editor".to_string())
-        }
+        (&Command::Bitmap(ref _bc), ed) => Err(editor_mismatch("bitmap", ed)),
+        (c, &mut Editor::Bitmap(ref mut _be)) => Err(command_mismatch(c, "bitmap")),
+
+        (Command::Pixmap(c), Editor::Pixmap(e)) => super::pixmap::semantics::editor_eval(e, c),
+        (Command::Pixmap(_), ed) => Err(editor_mismatch("pixmap", ed)),
+        (c, Editor::Pixmap(_)) => Err(command_mismatch(c, "pixmap")),
 
         (&Command::Menu(ref c), &mut Editor::Menu(ref mut e)) => {
             super::menu::semantics::editor_eval(e, c)
         }
-        (&Command::Menu(ref _c), _) => Err("menu editor expected menu command".to_string()),
-        (_, &mut Editor::Menu(ref mut _e)) => Err("menu command for non-menu editor".to_string()),
+        (&Command::Menu(ref _c), ed) => Err(editor_mismatch("menu", ed)),
+        (c, &mut Editor::Menu(ref mut _e)) => Err(command_mismatch(c, "menu")),
 
-        (&Command::Chain(ref _ch), _) => unimplemented!(),
-        (&Command::Grid(ref _gr), _) => unimplemented!(),
+        (&Command::Chain(ref c), &mut Editor::Chain(ref mut e)) => {
+            super::chain::semantics::editor_eval(e, c)
+        }
+        (&Command::Chain(ref _c), ed) => Err(editor_mismatch("chain", ed)),
+        (c, &mut Editor::Chain(ref mut _e)) => Err(command_mismatch(c, "chain")),
+
+        (Command::Grid(c), Editor::Grid(e)) => super::grid::semantics::editor_eval(e, c),
     };
     debug!("command_eval {:?} ==> {:?}", command, res);
     res
 }
 
+fn editor_kind(editor: &Editor) -> &'static str {
+    match editor {
+        Editor::Bitmap(_) => "bitmap",
+        Editor::Pixmap(_) => "pixmap",
+        Editor::Menu(_) => "menu",
+        Editor::Chain(_) => "chain",
+        Editor::Grid(_) => "grid",
+    }
+}
+
+fn command_kind(command: &Command) -> &'static str {
+    match command {
+        Command::Bitmap(_) => "bitmap",
+        Command::Pixmap(_) => "pixmap",
+        Command::Menu(_) => "menu",
+        Command::Chain(_) => "chain",
+        Command::Grid(_) => "grid",
+    }
+}
+
+fn editor_mismatch(command_kind: &str, editor: &Editor) -> lang::Error {
+    lang::Error::EditorMismatch(command_kind.to_string(), editor_kind(editor).to_string())
+}
+
+fn command_mismatch(command: &Command, editor_kind: &str) -> lang::Error {
+    lang::Error::EditorMismatch(command_kind(command).to_string(), editor_kind.to_string())
+}
+
 pub fn render_elms(state: &State) -> Result<render::Elms, String> {
     match &state.editor {
         &Editor::Bitmap(ref ed) => match ed.state {
             None => Ok(vec![]),
             Some(ref ed) => super::bitmap::io::render_elms(ed),
         },
+        Editor::Pixmap(ed) => match ed.state {
+            None => Ok(vec![]),
+            Some(ref st) => super::pixmap::io::render_elms(st),
+        },
         &Editor::Menu(ref ed) => match ed.state {
             None => Ok(vec![]),
-            Some(ref st) => super::menu::io::render_elms(st),
+            Some(ref st) => super::menu::io::render_elms(st, &ed.clipboard),
+        },
+        &Editor::Chain(ref ed) => match ed.state {
+            None => Ok(vec![]),
+            Some(ref st) => super::chain::io::render_elms(st),
+        },
+        &Editor::Grid(ref ed) => match ed.state {
+            None => Ok(vec![]),
+            Some(ref st) => super::grid::io::render_elms(st),
         },
-        &Editor::Chain(ref _ch) => unimplemented!(),
-        &Editor::Grid(ref _gr) => unimplemented!(),
+    }
+}
+
+/// evaluate the expression to media, in the given environment and adapton context.
+///
+/// an embedded command runs in a fresh editor; unless it is an `Init` command,
+/// this editor holds the most recently-bound media of the environment (see `Exp::Command`),
+/// and the command produces its successor.
+pub fn eval_exp(env: &Env, ctx: &mut Context, exp: &Exp) -> lang::Result {
+    trace!("eval_exp {:?}", exp);
+    let res = match exp {
+        Exp::Void => Ok(Media::Void),
+        Exp::Atom(a) => Ok(Media::Atom(a.clone())),
+        Exp::Name(n) => Ok(Media::Name(n.clone())),
+        Exp::Location(l) => Ok(Media::Location(l.clone())),
+        Exp::Bitmap(b) => Ok(Media::Bitmap(b.clone())),
+        Exp::Pixmap(p) => Ok(Media::Pixmap(p.clone())),
+        Exp::Chain(c) => Ok(Media::Chain(c.clone())),
+        Exp::Grid(g) => Ok(Media::Grid(g.clone())),
+        Exp::Store(s) => Ok(Media::Store(s.clone())),
+        Exp::StoreProj(e, n) => match eval_exp(env, ctx, e)? {
+            Media::Store(s) => store_proj(&s, n),
+            m => Err(lang::Error::TypeMismatch("store".to_string(), m)),
+        },
+        Exp::Named(n, e) => Ok(Media::Named(n.clone(), Box::new(eval_exp(env, ctx, e)?))),
+        Exp::Located(l, e) => Ok(Media::Located(l.clone(), Box::new(eval_exp(env, ctx, e)?))),
+        Exp::Merkle(e) => {
+            let media = eval_exp(env, ctx, e)?;
+            Ok(Media::Merkle(Merkle::new(media)))
+        }
+        Exp::MerkleFrom(e) => {
+            let media = eval_exp(env, ctx, e)?;
+            Ok(Media::Merkle(Merkle::new(media)))
+        }
+        Exp::Quote(e) => Ok(Media::Quote(e.clone())),
+        Exp::FixQuote(n, e) => {
+            // the body may refer to (a quote of) itself, by the given name
+            let mut env = env.clone();
+            env.bindings
+                .push((n.clone(), Media::Quote(Box::new(exp.clone()))));
+            eval_exp(&env, ctx, e)
+        }
+        Exp::StoreFrom(n, e) => {
+            let media = eval_exp(env, ctx, e)?;
+            store_from(n, media).map(Media::Store)
+        }
+        Exp::Command(c) => {
+            let media = env.bindings.last().map(|(_, m)| m.clone());
+            command_media(c, media)
+        }
+        Exp::Var(n) => match env.bindings.iter().rev().find(|(x, _)| x == n) {
+            Some((_, m)) => Ok(m.clone()),
+            None => Err(lang::Error::Unbound(n.clone())),
+        },
+        Exp::Block(block) => {
+            let mut env = env.clone();
+            let mut media = Media::Void;
+            for (n, e) in block.bindings.iter() {
+                media = eval_exp(&env, ctx, e)?;
+                env.bindings.push((n.clone(), media.clone()));
+            }
+            Ok(media)
+        }
+        Exp::Put(n, e) => {
+            let media = eval_exp(env, ctx, e)?;
+            match ctx.put(n.clone(), media) {
+                Ok(id) => Ok(Media::Name(id.name)),
+                Err(err) => Err(lang::Error::Put(err)),
+            }
+        }
+        Exp::Thunk(n, bindings) => {
+            let closure = Closure {
+                env: env.clone(),
+                exp: Exp::Block(lang::Block {
+                    bindings: bindings.clone(),
+                }),
+            };
+            match ctx.put_thunk(Some(n.clone()), closure) {
+                Ok(id) => Ok(Media::Name(id.name)),
+                Err(err) => Err(lang::Error::Put(err)),
+            }
+        }
+        Exp::Get(e) => match eval_exp(env, ctx, e)? {
+            Media::Name(n) => match ctx.get(n.clone(), NodeId { name: n }) {
+                Ok(res) => res,
+                Err(err) => Err(lang::Error::Get(err)),
+            },
+            m => Err(lang::Error::TypeMismatch("node name".to_string(), m)),
+        },
+    };
+    trace!("eval_exp {:?} ==> {:?}", exp, res);
+    res
+}
+
+fn store_proj(store: &lang::Store, name: &Name) -> lang::Result {
+    match store.table.iter().find(|(n, _)| **n == *name) {
+        Some((_, m)) => Ok((**m).clone()),
+        None => Err(lang::Error::AbsentName(name.clone())),
+    }
+}
+
+/// a store holds the named media of a chain, a single named media, or another store.
+fn store_from(name: &Name, media: Media) -> Result<lang::Store, lang::Error> {
+    let table = match media {
+        Media::Void => vec![],
+        Media::Store(s) => s.table,
+        Media::Named(n, m) => vec![(Merkle::new(n), Merkle::new(*m))],
+        Media::Chain(c) => c
+            .iter()
+            .map(|node| {
+                (
+                    Merkle::new(node.name.clone()),
+                    Merkle::new(*node.media.clone()),
+                )
+            })
+            .collect(),
+        m => return Err(lang::Error::TypeMismatch("named media".to_string(), m)),
+    };
+    Ok(lang::Store {
+        name: Merkle::new(name.clone()),
+        table,
+    })
+}
+
+/// run the command in a fresh editor, holding the given media (unless initializing);
+/// the media must be well-formed, since the editor assumes as much
+fn command_media(command: &Command, media: Option<Media>) -> lang::Result {
+    fn mismatch(expected: &str, media: Option<Media>) -> lang::Error {
+        lang::Error::TypeMismatch(expected.to_string(), media.unwrap_or(Media::Void))
+    }
+    match command {
+        Command::Bitmap(c) => {
+            let state = match (c, media) {
+                (bitmap::Command::Init(_), _) => None,
+                (_, Some(Media::Bitmap(b))) => {
+                    bitmap::semantics::bitmap_check(&b)?;
+                    Some(bitmap::EditorState {
+                        bitmap: *b,
+                        cursor: (0, 0),
+                        anchor: None,
+                        clipboard: None,
+                    })
+                }
+                (_, m) => return Err(mismatch("bitmap", m)),
+            };
+            let mut ed = bitmap::Editor {
+                history: vec![],
+                state,
+            };
+            bitmap::semantics::editor_eval(&mut ed, c)?;
+            Ok(Media::Bitmap(Box::new(ed.state.unwrap().bitmap)))
+        }
+        Command::Pixmap(c) => {
+            let state = match (c, media) {
+                (pixmap::Command::Init(_), _) => None,
+                (_, Some(Media::Pixmap(p))) => {
+                    pixmap::semantics::pixmap_check(&p)?;
+                    Some(pixmap::EditorState {
+                        pixmap: *p,
+                        cursor: (0, 0),
+                        color: 0,
+                    })
+                }
+                (_, m) => return Err(mismatch("pixmap", m)),
+            };
+            let mut ed = pixmap::Editor {
+                history: vec![],
+                state,
+            };
+            pixmap::semantics::editor_eval(&mut ed, c)?;
+            Ok(Media::Pixmap(Box::new(ed.state.unwrap().pixmap)))
+        }
+        Command::Chain(c) => {
+            let state = match (c, media) {
+                (chain::Command::Init(_), _) => None,
+                // every chain is well-formed (even an empty one)
+                (_, Some(Media::Chain(ch))) => Some(chain::semantics::editor_state_of_chain(*ch)),
+                (_, m) => return Err(mismatch("chain", m)),
+            };
+            let mut ed = chain::Editor {
+                history: vec![],
+                state,
+            };
+            chain::semantics::editor_eval(&mut ed, c)?;
+            let ch = chain::semantics::chain_of_editor_state(&ed.state.unwrap());
+            Ok(Media::Chain(Box::new(ch)))
+        }
+        Command::Grid(c) => {
+            let state = match (c, media) {
+                (grid::Command::Init(_), _) => None,
+                (_, Some(Media::Grid(g))) => {
+                    grid::semantics::grid_check(&g)?;
+                    Some(grid::EditorState {
+                        grid: *g,
+                        cursor: (0, 0),
+                    })
+                }
+                (_, m) => return Err(mismatch("grid", m)),
+            };
+            let mut ed = grid::Editor {
+                history: vec![],
+                state,
+            };
+            grid::semantics::editor_eval(&mut ed, c)?;
+            Ok(Media::Grid(Box::new(ed.state.unwrap().grid)))
+        }
+        Command::Menu(_) => Err(lang::Error::Invalid(
+            "menu commands do not produce media".to_string(),
+        )),
     }
 }
 
@@ -92,28 +368,222 @@ pub fn get_persis_state_path() -> String {
 use std::fs::{File, OpenOptions};
 use std::io::{BufReader, ErrorKind, Write};
 
-pub fn load_state() -> State {
+fn io_error<E: std::fmt::Display>(error: E) -> lang::Error {
+    lang::Error::Io(error.to_string())
+}
+
+pub fn load_state() -> Result<State, lang::Error> {
     let path = &get_persis_state_path();
     let file = match File::open(path) {
         Ok(f) => f,
         Err(error) => match error.kind() {
-            ErrorKind::NotFound => return crate::init::init_state(),
-            _ => unreachable!(),
+            ErrorKind::NotFound => return Ok(crate::init::init_state()),
+            _ => return Err(io_error(error)),
         },
     };
     info!("Loading from {:?}", path);
     let reader = BufReader::new(file);
-    serde_json::from_reader(reader).unwrap()
+    serde_json::from_reader(reader).map_err(io_error)
 }
 
-pub fn save_state(state: &State) -> () {
+pub fn save_state(state: &State) -> Result<(), lang::Error> {
     let path = get_persis_state_path();
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
         .open(path)
-        .unwrap();
-    let output: String = serde_json::to_string_pretty(&state).unwrap();
-    file.write_all(output.as_bytes()).unwrap();
+        .map_err(io_error)?;
+    let output: String = serde_json::to_string_pretty(&state).map_err(io_error)?;
+    file.write_all(output.as_bytes()).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::eval_exp;
+    use adapton::{self, GetError};
+    use pixmap::semantics::default_palette;
+    use pixmap::Pixmap;
+    use syntax::{parse_exp, parse_media};
+    use types::adapton::{Context, Env};
+    use types::lang::{Atom, Error, Media, Name, Result};
+    use types::util::name_of_str;
+
+    fn eval_in(ctx: &mut Context, s: &str) -> Result {
+        eval_exp(&Env { bindings: vec![] }, ctx, &parse_exp(s).unwrap())
+    }
+
+    fn eval(s: &str) -> Result {
+        eval_in(&mut adapton::init(), s)
+    }
+
+    fn media(s: &str) -> Media {
+        parse_media(s).unwrap()
+    }
+
+    fn var(s: &str) -> Name {
+        Name::Atom(Atom::String(s.to_string()))
+    }
+
+    #[test]
+    fn blocks_and_vars() {
+        assert_eq!(eval("{ let x = 1; let y = x }"), Ok(media("1")));
+        // a block is the media of its last binding; an empty one is void
+        assert_eq!(eval("{ }"), Ok(Media::Void));
+        // later bindings shadow earlier ones, and a block's bindings stay within it
+        assert_eq!(eval("{ let x = 1; let x = 2; let y = x }"), Ok(media("2")));
+        assert_eq!(
+            eval("{ let x = 1; let y = { let x = 2; let z = x }; let w = x }"),
+            Ok(media("1"))
+        );
+        assert_eq!(
+            eval("{ let x = 1; let y = z }"),
+            Err(Error::Unbound(var("z")))
+        );
+        assert_eq!(
+            eval("{ let x = { let y = 1 }; let z = y }"),
+            Err(Error::Unbound(var("y")))
+        );
+    }
+
+    #[test]
+    fn stores() {
+        assert_eq!(
+            eval("store_from(#s, chain[#a: 1, #b: 2]).#b"),
+            Ok(media("2"))
+        );
+        assert_eq!(eval("store_from(#s, #a: 1).#a"), Ok(media("1")));
+        assert_eq!(eval("store #s { #a: 1 }.#a"), Ok(media("1")));
+        assert_eq!(
+            eval("store_from(#s, store_from(#t, #a: 1)).#a"),
+            Ok(media("1"))
+        );
+        assert_eq!(eval("store_from(#s, void)"), Ok(media("store #s { }")));
+        assert_eq!(
+            eval("store_from(#s, #a: 1).#b"),
+            Err(Error::AbsentName(name_of_str("b")))
+        );
+        assert_eq!(
+            eval("store_from(#s, 1)"),
+            Err(Error::TypeMismatch("named media".to_string(), media("1")))
+        );
+        assert_eq!(
+            eval("{ let x = 1; let y = x.#a }"),
+            Err(Error::TypeMismatch("store".to_string(), media("1")))
+        );
+    }
+
+    #[test]
+    fn quotes() {
+        // a quote does not evaluate its body, so its variables need no bindings
+        assert_eq!(
+            eval("quote(x)"),
+            Ok(Media::Quote(Box::new(parse_exp("x").unwrap())))
+        );
+        // the body of a fixed quote sees (a quote of) the whole fixed quote
+        let fix = parse_exp("fix_quote(f, f)").unwrap();
+        assert_eq!(eval("fix_quote(f, f)"), Ok(Media::Quote(Box::new(fix))));
+        assert_eq!(
+            eval("fix_quote(f, { let x = 1; let y = x })"),
+            Ok(media("1"))
+        );
+    }
+
+    #[test]
+    fn embedded_commands() {
+        let invert = r#"command {"Bitmap": {"Auto": {"Transform": "Invert"}}}"#;
+        assert_eq!(
+            eval(&format!(
+                "{{ let b = bitmap[0 1; 1 0]; let r = {} }}",
+                invert
+            )),
+            Ok(media("bitmap[1 0; 0 1]"))
+        );
+        assert_eq!(
+            eval(r#"command {"Bitmap": {"Init": {"Make": [2, 1]}}}"#),
+            Ok(media("bitmap[0 0]"))
+        );
+        assert_eq!(
+            eval(
+                r#"{ let c = chain[#a: 1]; let r = command {"Chain": {"Edit": {"Delete": "Forward"}}} }"#
+            ),
+            Ok(media("chain[]"))
+        );
+        // the input is the media of the last binding, whatever its kind
+        assert_eq!(
+            eval(&format!(
+                "{{ let b = bitmap[0 1]; let c = 3; let r = {} }}",
+                invert
+            )),
+            Err(Error::TypeMismatch("bitmap".to_string(), media("3")))
+        );
+        assert_eq!(
+            eval(invert),
+            Err(Error::TypeMismatch("bitmap".to_string(), Media::Void))
+        );
+        // ill-formed input is an error, rather than a panic in the editor
+        for command in [
+            r#"{"Edit": "Toggle"}"#,
+            r#"{"Auto": {"Transform": "Invert"}}"#,
+        ]
+        .iter()
+        {
+            match eval(&format!(
+                r#"{{ let b = bitmap[;]; let r = command {{"Bitmap": {}}} }}"#,
+                command
+            )) {
+                Err(Error::Invalid(_)) => (),
+                res => panic!("expected an invalid bitmap, not {:?}", res),
+            }
+        }
+        let pixmap = Pixmap {
+            width: 1,
+            height: 1,
+            palette: default_palette(),
+            pixels: vec![vec![99]],
+        };
+        let env = Env {
+            bindings: vec![(var("p"), Media::Pixmap(Box::new(pixmap)))],
+        };
+        let paint = parse_exp(r#"command {"Pixmap": {"Edit": "PickAtCursor"}}"#).unwrap();
+        assert_eq!(
+            eval_exp(&env, &mut adapton::init(), &paint),
+            Err(Error::OutOfBounds(vec![99]))
+        );
+        match eval(r#"{ let g = grid[;]; let r = command {"Grid": {"Edit": "RemoveRow"}} }"#) {
+            Err(Error::Invalid(_)) => (),
+            res => panic!("expected an invalid grid, not {:?}", res),
+        }
+    }
+
+    #[test]
+    fn put_thunk_get() {
+        let mut ctx = adapton::init();
+        assert_eq!(eval_in(&mut ctx, "put #a 1"), Ok(media("#a")));
+        assert_eq!(
+            eval_in(&mut ctx, "thunk #t { let x = get #a; let y = #n: x }"),
+            Ok(media("#t"))
+        );
+        assert_eq!(eval_in(&mut ctx, "get #t"), Ok(media("#n: 1")));
+        // a new put re-evaluates the thunk that depends on it
+        eval_in(&mut ctx, "put #a 2").unwrap();
+        assert_eq!(eval_in(&mut ctx, "get #t"), Ok(media("#n: 2")));
+        // a new thunk, under the same name, replaces the old one
+        eval_in(&mut ctx, "thunk #t { let x = get #a; let y = #m: x }").unwrap();
+        assert_eq!(eval_in(&mut ctx, "get #t"), Ok(media("#m: 2")));
+        // a name, bound to a variable, names its node too
+        assert_eq!(
+            eval_in(&mut ctx, "{ let n = put #b 3; let v = get n }"),
+            Ok(media("3"))
+        );
+
+        assert_eq!(
+            eval_in(&mut ctx, "get #c"),
+            Err(Error::Get(GetError::Undefined(name_of_str("c"))))
+        );
+        assert_eq!(
+            eval_in(&mut ctx, "get 1"),
+            Err(Error::TypeMismatch("node name".to_string(), media("1")))
+        );
+    }
 }
@@ -386,6 +386,13 @@ pub mod cap5x5 {
             0 8 0 0 0 ;
             0 8 8 0 0
         ],
+        "?" => [
+            0 8 8 8 0 ;
+            0 0 0 8 0 ;
+            0 0 8 8 0 ;
+            0 0 0 0 0 ;
+            0 0 8 0 0
+        ],
         "\"" => [
             0 8 0 8 0 ;
             0 8 0 8 0 ;
@@ -1,11 +1,508 @@
 // Serde: Persistent state between invocations of ZQM
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
-pub struct Grid {}
+use types::lang::{Dir2D, Media, Nat};
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
-pub struct Editor {}
+// Step 1:
+// -------
+// Define the structure, in terms of "simplified, affine Rust"
+// (no references or lifetimes; everything is affine, so no Rc<_>s either.)
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
-pub enum Command {}
+/// a grid of media cells, represented as a 2D array
+/// (e.g., the tiles of a zoom quilt)
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
+pub struct Grid {
+    pub width: Nat,
+    pub height: Nat,
+    /// row major ordering (rows indexed first, then columns)
+    pub cells: Vec<Vec<Media>>,
+}
+
+// Step 2:
+// -------
+// Define the structure's "auto commands", as a DSL datatype.
+
+/// commands that advance the state of the grid,
+/// whose execution is independent of editor state
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum AutoCommand {
+    /// set the cell at the given coordinate to the given media
+    SetCell(Nat, Nat, Media),
+
+    /// clear the cell at the given coordinate (to `Media::Void`)
+    ClearCell(Nat, Nat),
+
+    /// insert a new row of void cells, at the given row index
+    InsertRow(Nat),
+
+    /// insert a new column of void cells, at the given column index
+    InsertCol(Nat),
+
+    /// remove the row at the given row index
+    RemoveRow(Nat),
+
+    /// remove the column at the given column index
+    RemoveCol(Nat),
+}
+
+// Step 3:
+// -------
+//
+// Define a canonical editor for the structure in question.  Again, use simplified, affine Rust.
+
+/// the history-_independent_ state of the editor
+#[derive(Debug, Serialize, Deserialize, Hash)]
+pub struct EditorState {
+    /// created by an Init command; affected by Auto and Edit commands
+    pub grid: Grid,
+
+    /// initialized by an Init command; affected by Edit commands (and row/column removal)
+    pub cursor: (Nat, Nat),
+}
+
+/// the history-_dependent_ state of the editor
+#[derive(Debug, Serialize, Deserialize, Hash)]
+pub struct Editor {
+    /// full linear history of this grid's evolution, as a sequence of commands
+    pub history: Vec<Command>,
+
+    /// current state of the grid and surrounding editor environment
+    pub state: Option<EditorState>,
+}
+
+// Step 4a:
+// -------
+// Define commands that initialize the editor state.
+
+/// commands that create new grids
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum InitCommand {
+    /// make a new grid of void cells, with the given width and height
+    Make(Nat, Nat),
+}
+
+// Step 4b:
+// -------
+// Define commands that evolve the editor state with edits,
+//   or changes to the edit state (cursor location).
+
+/// commands that advance the editor state,
+/// and possibly, its associated grid state.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum EditCommand {
+    /// move the grid cursor one unit in a relative direction
+    MoveRel(Dir2D),
+
+    /// move the grid cursor to a absolute position
+    MoveAbs(Nat, Nat),
+
+    /// set the media of the cell at the cursor's grid position
+    SetCell(Media),
+
+    /// clear the media of the cell at the cursor's grid position
+    ClearCell,
+
+    /// insert a row (`Up`, `Down`) or column (`Left`, `Right`) beside the cursor
+    Insert(Dir2D),
+
+    /// remove the row holding the cursor
+    RemoveRow,
+
+    /// remove the column holding the cursor
+    RemoveCol,
+}
+
+// Step 4c:
+// -------
+//
+// Define a combined language of commands that includes (distinct) Init, Auto
+// and Edit sublanguages.
+
+/// commands that advance the evolution of a grid
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum Command {
+    /// commands that create new grids
+    Init(InitCommand),
+
+    /// commands that advance the state of the grid,
+    /// whose execution is independent of editor state
+    Auto(AutoCommand),
+
+    /// commands that advance the editor state,
+    /// and possibly, its associated grid state
+    Edit(EditCommand),
+}
+
+// Step 5:
+// -------
+//
+// Define the state-change semantics for the command languages.
+
+/// semantic definitions for grids and grid editors.
+pub mod semantics {
+    use super::{AutoCommand, Grid, Media};
+    use super::{Command, Dir2D, EditCommand, Editor, EditorState, InitCommand};
+    use types::lang::Error;
+
+    pub fn grid_init(w: usize, h: usize) -> Grid {
+        let row = vec![Media::Void; w];
+        let cells = vec![row; h];
+        Grid {
+            width: w,
+            height: h,
+            cells,
+        }
+    }
+
+    /// check that the grid is non-empty, and that its cells agree with its width and height
+    pub fn grid_check(grid: &Grid) -> Result<(), Error> {
+        if grid.width == 0 || grid.height == 0 {
+            Err(Error::Invalid(
+                "grid dimensions must be positive".to_string(),
+            ))
+        } else if grid.cells.len() != grid.height
+            || grid.cells.iter().any(|row| row.len() != grid.width)
+        {
+            Err(Error::Invalid(format!(
+                "grid cells do not match its {}x{} size",
+                grid.width, grid.height
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn grid_get_size(grid: &Grid) -> (usize, usize) {
+        (grid.width, grid.height)
+    }
+
+    pub fn grid_get_cell(grid: &Grid, x: usize, y: usize) -> Result<&Media, Error> {
+        if x < grid.width && y < grid.height {
+            Ok(&grid.cells[y][x])
+        } else {
+            Err(Error::OutOfBounds(vec![x, y]))
+        }
+    }
+
+    /// set the cell to the given media, and return its former media
+    pub fn grid_set_cell(grid: &mut Grid, x: usize, y: usize, m: Media) -> Result<Media, Error> {
+        if x < grid.width && y < grid.height {
+            Ok(std::mem::replace(&mut grid.cells[y][x], m))
+        } else {
+            Err(Error::OutOfBounds(vec![x, y]))
+        }
+    }
+
+    pub fn grid_insert_row(grid: &mut Grid, y: usize) -> Result<(), Error> {
+        if y <= grid.height {
+            grid.cells.insert(y, vec![Media::Void; grid.width]);
+            grid.height += 1;
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds(vec![0, y]))
+        }
+    }
+
+    pub fn grid_insert_col(grid: &mut Grid, x: usize) -> Result<(), Error> {
+        if x <= grid.width {
+            for row in grid.cells.iter_mut() {
+                row.insert(x, Media::Void)
+            }
+            grid.width += 1;
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds(vec![x, 0]))
+        }
+    }
+
+    pub fn grid_remove_row(grid: &mut Grid, y: usize) -> Result<Vec<Media>, Error> {
+        if grid.height <= 1 {
+            Err(Error::Invalid(
+                "cannot remove the only grid row".to_string(),
+            ))
+        } else if y < grid.height {
+            grid.height -= 1;
+            Ok(grid.cells.remove(y))
+        } else {
+            Err(Error::OutOfBounds(vec![0, y]))
+        }
+    }
+
+    pub fn grid_remove_col(grid: &mut Grid, x: usize) -> Result<Vec<Media>, Error> {
+        if grid.width <= 1 {
+            Err(Error::Invalid(
+                "cannot remove the only grid column".to_string(),
+            ))
+        } else if x < grid.width {
+            grid.width -= 1;
+            Ok(grid.cells.iter_mut().map(|row| row.remove(x)).collect())
+        } else {
+            Err(Error::OutOfBounds(vec![x, 0]))
+        }
+    }
+
+    pub fn grid_eval(grid: &mut Grid, command: &AutoCommand) -> Result<(), Error> {
+        debug!("grid_eval {:?}", command);
+        let res = match command {
+            AutoCommand::SetCell(x, y, m) => grid_set_cell(grid, *x, *y, m.clone()).map(|_| ()),
+            AutoCommand::ClearCell(x, y) => grid_set_cell(grid, *x, *y, Media::Void).map(|_| ()),
+            AutoCommand::InsertRow(y) => grid_insert_row(grid, *y),
+            AutoCommand::InsertCol(x) => grid_insert_col(grid, *x),
+            AutoCommand::RemoveRow(y) => grid_remove_row(grid, *y).map(|_| ()),
+            AutoCommand::RemoveCol(x) => grid_remove_col(grid, *x).map(|_| ()),
+        };
+        debug!("grid_eval {:?} ==> {:?}", command, res);
+        res
+    }
+
+    /// keep the cursor within the grid, e.g., after removing a row or column
+    fn clamp_cursor(editor: &mut EditorState) {
+        let (x, y) = editor.cursor;
+        editor.cursor = (
+            x.min(editor.grid.width.saturating_sub(1)),
+            y.min(editor.grid.height.saturating_sub(1)),
+        );
+    }
+
+    pub fn editor_state_eval(editor: &mut EditorState, command: &EditCommand) -> Result<(), Error> {
+        debug!("editor_state_eval {:?}", command);
+        let (x, y) = editor.cursor;
+        let res = match command {
+            EditCommand::MoveRel(dir) => {
+                let (w, h) = (editor.grid.width, editor.grid.height);
+                editor.cursor = match dir {
+                    Dir2D::Left => (if x == 0 { 0 } else { x - 1 }, y),
+                    Dir2D::Right => (
+                        if x + 1 >= w {
+                            w.saturating_sub(1)
+                        } else {
+                            x + 1
+                        },
+                        y,
+                    ),
+                    Dir2D::Up => (x, if y == 0 { 0 } else { y - 1 }),
+                    Dir2D::Down => (
+                        x,
+                        if y + 1 >= h {
+                            h.saturating_sub(1)
+                        } else {
+                            y + 1
+                        },
+                    ),
+                };
+                Ok(())
+            }
+            EditCommand::MoveAbs(x, y) => {
+                if *x < editor.grid.width && *y < editor.grid.height {
+                    editor.cursor = (*x, *y);
+                    Ok(())
+                } else {
+                    Err(Error::OutOfBounds(vec![*x, *y]))
+                }
+            }
+            EditCommand::SetCell(m) => grid_set_cell(&mut editor.grid, x, y, m.clone()).map(|_| ()),
+            EditCommand::ClearCell => {
+                grid_set_cell(&mut editor.grid, x, y, Media::Void).map(|_| ())
+            }
+            EditCommand::Insert(dir) => match dir {
+                Dir2D::Up => {
+                    grid_insert_row(&mut editor.grid, y)?;
+                    editor.cursor = (x, y + 1);
+                    Ok(())
+                }
+                Dir2D::Down => grid_insert_row(&mut editor.grid, y + 1),
+                Dir2D::Left => {
+                    grid_insert_col(&mut editor.grid, x)?;
+                    editor.cursor = (x + 1, y);
+                    Ok(())
+                }
+                Dir2D::Right => grid_insert_col(&mut editor.grid, x + 1),
+            },
+            EditCommand::RemoveRow => {
+                grid_remove_row(&mut editor.grid, y)?;
+                clamp_cursor(editor);
+                Ok(())
+            }
+            EditCommand::RemoveCol => {
+                grid_remove_col(&mut editor.grid, x)?;
+                clamp_cursor(editor);
+                Ok(())
+            }
+        };
+        debug!("editor_state_eval {:?} ==> {:?}", command, res);
+        res
+    }
+
+    pub fn editor_eval(editor: &mut Editor, command: &Command) -> Result<(), Error> {
+        let num = editor.history.len();
+        debug!("#{}: editor_eval {:?}", num, command);
+        // save the command in the history
+        editor.history.push(command.clone());
+        // evaluate the command in the appropriate evaluation context:
+        let res = match command {
+            Command::Init(InitCommand::Make(w, h)) => {
+                if *w == 0 || *h == 0 {
+                    Err(Error::Invalid(
+                        "grid dimensions must be positive".to_string(),
+                    ))
+                } else {
+                    editor.state = Some(EditorState {
+                        grid: grid_init(*w, *h),
+                        cursor: (0, 0),
+                    });
+                    Ok(())
+                }
+            }
+            Command::Auto(command) => match editor.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => {
+                    grid_eval(&mut st.grid, command)?;
+                    clamp_cursor(st);
+                    Ok(())
+                }
+            },
+            Command::Edit(command) => match editor.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => editor_state_eval(st, command),
+            },
+        };
+        info!("#{}: editor_eval {:?} ==> {:?}", num, command, res);
+        res
+    }
+}
+
+// Step 6:
+// -------
+//
+// Define the IO for the Editor using the abstract `render` module, and associated types.
+
+pub mod io {
+    use super::{Dir2D, EditCommand, EditorState};
+    use render::{BitmapAtts, FlowAtts, FrameType, Render, TextAtts};
+    use types::event::{Event, Quit};
+    use types::lang::Name;
+    use types::render::{self, Color, Dim, Fill};
+
+    pub fn edit_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, Quit> {
+        match event {
+            Event::Quit => Err(Quit),
+            Event::KeyDown(ref kei) => match kei.key.as_str() {
+                "Escape" => Err(Quit),
+                "Backspace" => Ok(vec![EditCommand::ClearCell]),
+                "Enter" => Ok(vec![EditCommand::Insert(Dir2D::Down)]),
+                "Tab" => Ok(vec![EditCommand::Insert(Dir2D::Right)]),
+                "ArrowLeft" => Ok(vec![EditCommand::MoveRel(Dir2D::Left)]),
+                "ArrowRight" => Ok(vec![EditCommand::MoveRel(Dir2D::Right)]),
+                "ArrowUp" => Ok(vec![EditCommand::MoveRel(Dir2D::Up)]),
+                "ArrowDown" => Ok(vec![EditCommand::MoveRel(Dir2D::Down)]),
+                _ => Ok(vec![]),
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    pub fn render_elms(edit_state: &EditorState) -> Result<render::Elms, String> {
+        let mut r = Render::new();
+
+        // to do -- get these constants from the editor state
+        fn text_atts() -> TextAtts {
+            TextAtts {
+                zoom: 3,
+                fg_fill: Fill::Closed(Color::RGB(255, 255, 255)),
+                bg_fill: Fill::None,
+                glyph_dim: Dim {
+                    width: 5,
+                    height: 5,
+                },
+                glyph_flow: FlowAtts {
+                    dir: Dir2D::Right,
+                    intra_pad: 1,
+                    inter_pad: 1,
+                },
+            }
+        }
+        fn bitmap_atts() -> BitmapAtts {
+            BitmapAtts {
+                zoom: 4,
+                fill_isset: Fill::Closed(Color::RGB(255, 255, 255)),
+                fill_notset: Fill::Closed(Color::RGB(0, 0, 0)),
+            }
+        }
+        fn flow(dir: Dir2D) -> FrameType {
+            FrameType::Flow(FlowAtts {
+                dir,
+                intra_pad: 2,
+                inter_pad: 2,
+            })
+        }
+
+        let grid_border_color = Color::RGB(100, 80, 100);
+        let cursor_border_color = Color::RGB(150, 255, 150);
+
+        r.begin(&Name::Void, flow(Dir2D::Down));
+        for (y, row) in edit_state.grid.cells.iter().enumerate() {
+            r.begin(&Name::Void, flow(Dir2D::Right));
+            for (x, cell) in row.iter().enumerate() {
+                r.begin(&Name::Void, flow(Dir2D::Right));
+                if (x, y) == edit_state.cursor {
+                    r.fill(Fill::Open(cursor_border_color.clone(), 1));
+                } else {
+                    r.fill(Fill::Open(grid_border_color.clone(), 1));
+                }
+                r.media(cell, &text_atts(), &bitmap_atts());
+                r.end();
+            }
+            r.end();
+        }
+        r.end();
+        Ok(r.into_elms())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::semantics::{editor_state_eval, grid_check, grid_init};
+    use super::{Dir2D, EditCommand, EditorState, Grid};
+    use types::lang::{Error, Media};
+
+    #[test]
+    fn check_rejects_empty_and_ragged_grids() {
+        assert_eq!(grid_check(&grid_init(2, 3)), Ok(()));
+        for g in [grid_init(0, 3), grid_init(2, 0), grid_init(0, 0)].iter() {
+            assert!(grid_check(g).is_err());
+        }
+        let ragged = Grid {
+            width: 2,
+            height: 2,
+            cells: vec![vec![Media::Void; 2], vec![Media::Void; 1]],
+        };
+        assert!(grid_check(&ragged).is_err());
+    }
+
+    #[test]
+    fn cursor_stays_in_bounds() {
+        let mut ed = EditorState {
+            grid: grid_init(2, 2),
+            cursor: (1, 1),
+        };
+        editor_state_eval(&mut ed, &EditCommand::RemoveCol).unwrap();
+        assert_eq!(ed.cursor, (0, 1));
+        editor_state_eval(&mut ed, &EditCommand::RemoveRow).unwrap();
+        assert_eq!(ed.cursor, (0, 0));
+        assert_eq!(
+            editor_state_eval(&mut ed, &EditCommand::RemoveRow),
+            Err(Error::Invalid(
+                "cannot remove the only grid row".to_string()
+            ))
+        );
+
+        // an empty grid (which eval rejects) must not underflow the cursor
+        let mut empty = EditorState {
+            grid: grid_init(0, 0),
+            cursor: (0, 0),
+        };
+        editor_state_eval(&mut empty, &EditCommand::MoveRel(Dir2D::Right)).unwrap();
+        editor_state_eval(&mut empty, &EditCommand::MoveRel(Dir2D::Down)).unwrap();
+        assert_eq!(empty.cursor, (0, 0));
+    }
+}
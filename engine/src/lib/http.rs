@@ -0,0 +1,258 @@
+/*!
+
+HTTP client: send Candid calls, and push and pull media, to and from a canister
+
+A minimal HTTP/1.1 client (over `std::net`, without TLS) that posts
+Candid messages (see `candid::binary`) to an endpoint: a server, and
+the canister that it serves.  Each query (or update) call posts its
+arguments to `/api/query/<canister>/<method>` (or to
+`/api/call/<canister>/<method>`), and the response holds its results.
+
+To save media, the canister offers two methods:
+
+```text
+put : (text, text) -> ();
+get : (text) -> (opt text) query;
+```
+
+which store and retrieve the editor state (as JSON) under a name.
+The `mock` server is a local stand-in for such a canister, so that
+the round trip works offline.
+
+*/
+
+pub mod mock;
+
+use candid::{binary, Call};
+use menu::semantics::tree_tag;
+use menu::{MenuTree, MenuType, PrimType, Tag};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use types::lang::{Atom, Error, Name, State};
+
+const CONTENT_TYPE: &str = "application/octet-stream";
+
+/// how long to wait for the other end, when reading a message
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the longest body that a message may have (16 MiB)
+const MAX_BODY: usize = 16 << 20;
+
+/// a server, and the canister that it serves
+#[derive(Clone, Debug, PartialEq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    pub canister: String,
+}
+
+impl Endpoint {
+    /// the endpoint at an `http://host:port` URL (by default, port 80)
+    pub fn new(url: &str, canister: &str) -> Result<Endpoint, Error> {
+        let invalid = || {
+            Err(Error::Invalid(format!(
+                "expected http://host:port, not {}",
+                url
+            )))
+        };
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest.trim_end_matches('/'),
+            None => return invalid(),
+        };
+        let (host, port) = match rest.rfind(':') {
+            Some(i) => match rest[i + 1..].parse() {
+                Ok(port) => (&rest[..i], port),
+                Err(_) => return invalid(),
+            },
+            None => (rest, 80),
+        };
+        if host.is_empty() || host.contains('/') {
+            return invalid();
+        };
+        Ok(Endpoint {
+            host: host.to_string(),
+            port,
+            canister: canister.to_string(),
+        })
+    }
+}
+
+/// an update call of the method, with its arguments and results as Candid messages
+pub fn update(endpoint: &Endpoint, method: &str, args: &[u8]) -> Result<Vec<u8>, Error> {
+    let path = format!("/api/call/{}/{}", endpoint.canister, method);
+    post(endpoint, &path, args)
+}
+
+/// a query call of the method, with its arguments and results as Candid messages
+pub fn query(endpoint: &Endpoint, method: &str, args: &[u8]) -> Result<Vec<u8>, Error> {
+    let path = format!("/api/query/{}/{}", endpoint.canister, method);
+    post(endpoint, &path, args)
+}
+
+/// send the call: as a query, if its method is one; otherwise, as an update
+pub fn send(endpoint: &Endpoint, call: &Call) -> Result<Vec<u8>, Error> {
+    if call.modes.iter().any(|m| m == "query") {
+        query(endpoint, &call.method, &call.args)
+    } else {
+        update(endpoint, &call.method, &call.args)
+    }
+}
+
+/// save the state under the name, with the canister's `put` method
+pub fn push_state(endpoint: &Endpoint, name: &str, state: &State) -> Result<(), Error> {
+    let json = serde_json::to_string(state).map_err(io_error)?;
+    let args = binary::encode(&[text_arg(name), text_arg(&json)])?;
+    binary::decode_as(&update(endpoint, "put", &args)?, &[])?;
+    Ok(())
+}
+
+/// the state saved under the name, from the canister's `get` method
+pub fn pull_state(endpoint: &Endpoint, name: &str) -> Result<State, Error> {
+    let args = binary::encode(&[text_arg(name)])?;
+    let reply = query(endpoint, "get", &args)?;
+    match binary::decode_as(&reply, &[opt_text()])?.pop() {
+        Some(MenuTree::Option(true, json, _)) => {
+            serde_json::from_str(&text_of_tree(*json)?).map_err(io_error)
+        }
+        _ => Err(Error::AbsentName(Name::Atom(Atom::String(
+            name.to_string(),
+        )))),
+    }
+}
+
+fn io_error<E: std::fmt::Display>(error: E) -> Error {
+    Error::Io(error.to_string())
+}
+
+fn text_arg(text: &str) -> (MenuTree, MenuType) {
+    (
+        MenuTree::Text(text.to_string()),
+        MenuType::Prim(PrimType::Text),
+    )
+}
+
+fn opt_text() -> MenuType {
+    MenuType::Option(Box::new(MenuType::Prim(PrimType::Text)))
+}
+
+fn text_of_tree(tree: MenuTree) -> Result<String, Error> {
+    match tree {
+        MenuTree::Text(text) => Ok(text),
+        tree => Err(Error::MenuTag(Tag::Prim(PrimType::Text), tree_tag(&tree))),
+    }
+}
+
+/// post the body to the path, and return the body of the response (whose status must be 200)
+fn post(endpoint: &Endpoint, path: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut stream =
+        TcpStream::connect((endpoint.host.as_str(), endpoint.port)).map_err(io_error)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(io_error)?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path,
+        endpoint.host,
+        endpoint.port,
+        CONTENT_TYPE,
+        body.len()
+    )
+    .map_err(io_error)?;
+    stream.write_all(body).map_err(io_error)?;
+    debug!("POST {} ({} bytes)", path, body.len());
+    let (status, body) = read_message(&mut BufReader::new(stream))?;
+    // the status line, e.g., `HTTP/1.1 200 OK`
+    match status.split(' ').nth(1) {
+        Some("200") => Ok(body),
+        _ => Err(Error::Io(format!(
+            "{}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ))),
+    }
+}
+
+/// an HTTP request or response: its start line, and its body (as long as its
+/// `Content-Length` header says, up to `MAX_BODY`; without one, the body is empty)
+fn read_message<R: BufRead>(r: &mut R) -> Result<(String, Vec<u8>), Error> {
+    let mut start = String::new();
+    r.read_line(&mut start).map_err(io_error)?;
+    let mut len = 0;
+    loop {
+        let mut line = String::new();
+        if r.read_line(&mut line).map_err(io_error)? == 0 {
+            return Err(Error::Io("incomplete HTTP header".to_string()));
+        };
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        };
+        if let Some(i) = line.find(':') {
+            let (name, value) = (&line[..i], line[i + 1..].trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value.parse().map_err(io_error)?
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                return Err(Error::Io(format!(
+                    "unsupported transfer encoding: {}",
+                    value
+                )));
+            }
+        }
+    }
+    if len > MAX_BODY {
+        return Err(Error::Io(format!(
+            "HTTP body too long: {} bytes (at most {})",
+            len, MAX_BODY
+        )));
+    };
+    let mut body = vec![0; len];
+    r.read_exact(&mut body).map_err(io_error)?;
+    Ok((start.trim_end().to_string(), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::Server;
+    use super::{pull_state, push_state, read_message, Endpoint, MAX_BODY};
+    use init::init_state;
+    use std::thread;
+    use types::lang::{Error, Name};
+
+    #[test]
+    fn body_length_is_bounded() {
+        let msg = "POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi";
+        assert_eq!(
+            read_message(&mut msg.as_bytes()),
+            Ok(("POST / HTTP/1.1".to_string(), b"hi".to_vec()))
+        );
+        let msg = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        match read_message(&mut msg.as_bytes()) {
+            Err(Error::Io(_)) => (),
+            res => panic!("expected an error, not {:?}", res),
+        }
+    }
+
+    #[test]
+    fn push_then_pull() {
+        let mut server = Server::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", server.port().unwrap());
+        let handle = thread::spawn(move || server.serve(Some(3)).is_ok());
+        let endpoint = Endpoint::new(&url, "media").unwrap();
+
+        let state = init_state();
+        push_state(&endpoint, "start", &state).unwrap();
+        let pulled = pull_state(&endpoint, "start").unwrap();
+        assert_eq!(
+            serde_json::to_string(&pulled).unwrap(),
+            serde_json::to_string(&state).unwrap()
+        );
+        match pull_state(&endpoint, "other") {
+            Err(Error::AbsentName(Name::Atom(_))) => (),
+            res => panic!("expected an absent name, not {:?}", res),
+        }
+        assert!(handle.join().unwrap());
+    }
+}
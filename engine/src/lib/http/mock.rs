@@ -0,0 +1,118 @@
+/*!
+
+A local stand-in for a canister that saves media
+
+Serves the `put` and `get` methods (see `http`), for any canister
+name, and keeps what they store in memory, until the server stops.
+
+*/
+
+use super::{io_error, opt_text, read_message, text_arg, text_of_tree, CONTENT_TYPE, TIMEOUT};
+use candid::binary;
+use menu::{MenuTree, MenuType, PrimType};
+use std::collections::HashMap;
+use std::io::{BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use types::lang::Error;
+
+pub struct Server {
+    listener: TcpListener,
+    /// the saved media, by canister and name
+    store: HashMap<(String, String), String>,
+}
+
+impl Server {
+    /// a server listening at the address, e.g., `127.0.0.1:8000` (where port 0 picks any free port)
+    pub fn bind(addr: &str) -> Result<Server, Error> {
+        Ok(Server {
+            listener: TcpListener::bind(addr).map_err(io_error)?,
+            store: HashMap::new(),
+        })
+    }
+
+    /// the port where the server listens
+    pub fn port(&self) -> Result<u16, Error> {
+        let addr = self.listener.local_addr().map_err(io_error)?;
+        Ok(addr.port())
+    }
+
+    /// serve requests, one at a time: forever, or for the given number of requests
+    pub fn serve(&mut self, requests: Option<usize>) -> Result<(), Error> {
+        let mut served = 0;
+        while requests != Some(served) {
+            let (stream, _) = self.listener.accept().map_err(io_error)?;
+            if let Err(err) = self.respond(&stream) {
+                warn!("Failed to respond: {:?}", err)
+            };
+            served += 1
+        }
+        Ok(())
+    }
+
+    fn respond(&mut self, mut stream: &TcpStream) -> Result<(), Error> {
+        // a client that stops sending must not stall the server
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(io_error)?;
+        let (start, body) = read_message(&mut BufReader::new(stream))?;
+        let (status, body) = self.reply(&start, &body);
+        info!("{} ==> {}", start, status);
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            CONTENT_TYPE,
+            body.len()
+        )
+        .map_err(io_error)?;
+        stream.write_all(&body).map_err(io_error)
+    }
+
+    /// the status and body that reply to the request
+    fn reply(&mut self, start: &str, body: &[u8]) -> (&'static str, Vec<u8>) {
+        let words: Vec<&str> = start.split(' ').collect();
+        let path = match words.as_slice() {
+            ["POST", path, _] => path,
+            _ => return ("405 Method Not Allowed", b"expected a POST".to_vec()),
+        };
+        let steps: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        match steps.as_slice() {
+            ["api", kind, canister, method] => match self.call(kind, canister, method, body) {
+                Ok(reply) => ("200 OK", reply),
+                Err(err) => ("400 Bad Request", format!("{:?}", err).into_bytes()),
+            },
+            _ => (
+                "404 Not Found",
+                format!("no such path: {}", path).into_bytes(),
+            ),
+        }
+    }
+
+    /// the results of the call, as a Candid message
+    fn call(
+        &mut self,
+        kind: &str,
+        canister: &str,
+        method: &str,
+        args: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let text = MenuType::Prim(PrimType::Text);
+        match (kind, method) {
+            ("call", "put") => {
+                let mut args = binary::decode_as(args, &[text.clone(), text])?;
+                let json = text_of_tree(args.pop().unwrap())?;
+                let name = text_of_tree(args.pop().unwrap())?;
+                self.store.insert((canister.to_string(), name), json);
+                binary::encode(&[])
+            }
+            ("query", "get") | ("call", "get") => {
+                let name =
+                    text_of_tree(binary::decode_as(args, std::slice::from_ref(&text))?.remove(0))?;
+                let tree = match self.store.get(&(canister.to_string(), name)) {
+                    Some(json) => MenuTree::Option(true, Box::new(text_arg(json).0), text),
+                    None => MenuTree::Option(false, Box::new(MenuTree::Blank(text.clone())), text),
+                };
+                binary::encode(&[(tree, opt_text())])
+            }
+            _ => Err(Error::Invalid(format!("no {} method: {}", kind, method))),
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use candid;
 use eval;
 use menu;
-use types::lang::{Atom, Command, Editor, Name, State};
+use types::lang::{Atom, Command, Editor, Error, Name, State};
 
 pub fn init_state() -> State {
     let (mut state_init, init_command) = {
@@ -82,6 +83,7 @@ pub fn init_state() -> State {
                     editor: Editor::Menu(Box::new(menu::Editor {
                         state: None,
                         history: vec![],
+                        clipboard: None,
                     })),
                 },
                 Command::Menu(menu::Command::Init(menu::InitCommand::Default(
@@ -98,3 +100,34 @@ pub fn init_state() -> State {
     };
     state_init
 }
+
+/// the menu editor, for a value of the Candid type loaded from the given `.did` file:
+/// the named type, if given; otherwise, the file's service or its last type definition.
+pub fn init_state_of_candid_file(path: &str, type_name: Option<&str>) -> Result<State, Error> {
+    let text = std::fs::read_to_string(path).map_err(|err| Error::Io(err.to_string()))?;
+    init_state_of_candid_prog(&candid::parse_prog(&text)?, type_name)
+}
+
+/// the menu editor, for a value of the Candid program's type (as chosen by `Prog::menu_type`);
+/// a service offers its methods to choose among, each with its tuple of arguments to edit.
+pub fn init_state_of_candid_prog(
+    prog: &candid::Prog,
+    type_name: Option<&str>,
+) -> Result<State, Error> {
+    let typ = prog.menu_type(type_name)?;
+    let tree = if type_name.is_none() && prog.service.is_some() {
+        menu::semantics::auto_fill(&typ, 2)
+    } else {
+        menu::MenuTree::Blank(typ.clone())
+    };
+    let mut state = State {
+        editor: Editor::Menu(Box::new(menu::Editor {
+            state: None,
+            history: vec![],
+            clipboard: None,
+        })),
+    };
+    let init_command = Command::Menu(menu::Command::Init(menu::InitCommand::Default(tree, typ)));
+    eval::command_eval(&mut state, &init_command)?;
+    Ok(state)
+}
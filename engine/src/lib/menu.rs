@@ -1,9 +1,10 @@
+use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 use types::lang::{Atom, Dir1D, Name};
 
 pub type Text = String;
-pub type Nat = usize; // todo -- use a bignum rep
+pub type Nat = BigUint;
 pub type Label = Name;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
@@ -14,6 +15,10 @@ pub enum MenuType {
     Option(Box<MenuType>),
     Vec(Box<MenuType>),
     Tup(Vec<MenuType>),
+    /// a named type and its definition, in which `Ref` to the name (or to an enclosing name) recurs
+    Named(Label, Box<MenuType>),
+    /// a reference to the definition of an enclosing `Named` type
+    Ref(Label),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
@@ -24,7 +29,7 @@ pub enum PrimType {
     Bool,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum MenuTree {
     Product(Vec<(Label, MenuTree, MenuType)>),
     Variant(Box<LabelChoice>),
@@ -48,22 +53,35 @@ pub struct LabelSelect {
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
 pub struct PosSelect {
-    before: Vec<(Label, MenuTree, MenuType)>,
+    before: Vec<(MenuTree, MenuType)>,
     ctx: MenuCtx,
-    after: Vec<(Label, MenuTree, MenuType)>,
+    after: Vec<(MenuTree, MenuType)>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub struct LabelChoice {
-    before: Vec<(Label, MenuTree, MenuType)>,
-    choice: Option<(Label, MenuTree, MenuType)>,
-    after: Vec<(Label, MenuTree, MenuType)>,
+    pub before: Vec<(Label, MenuTree, MenuType)>,
+    pub choice: Option<(Label, MenuTree, MenuType)>,
+    pub after: Vec<(Label, MenuTree, MenuType)>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+/// a step from a tree to one of its subtrees
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub enum Step {
+    Field(Label),  // product field
+    Choice(Label), // variant arm
+    Some,          // option content
+    Pos(usize),    // vector element, or tuple position
+}
+
+/// the steps from the root tree to a subtree
+pub type Path = Vec<Step>;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum Error {
-    MenuTypeMismatch(MenuType, Tag), // found vs expected
-    Blank(Tag),                      // found blank vs expected completed 'tag'
+    MenuTypeMismatch(Path, MenuType, MenuType), // at path: expected vs found
+    Blanks(Vec<(Path, MenuType)>),              // found blanks vs expected completed trees
+    Conflict(Path, MenuType, MenuType),         // at path: merged trees' types disagree
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
@@ -77,39 +95,70 @@ pub enum Tag {
     Blank,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum InitCommand {
     Default(MenuTree, MenuType),
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum AutoCommand {
     CheckMenuType,
     CheckComplete,
     Replace(MenuTree),
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
 pub enum EditCommand {
     Descend,
     Ascend,
     PrevSibling,
     NextSibling,
-    GotoRoot,       // ---?
-    AutoFill,       // Tab
-    Clear,          // Backspace
-    NextTree,       // ArrowRight
-    PrevTree,       // ArrowLeft
-    NextBlank,      // ---?
-    PrevBlank,      // ---?
-    NextVariant,    // ArrowRight
-    PrevVariant,    // ArrowLefet
-    AcceptVariant,  // Enter
-    VecInsertBlank, // ---?
-    VecInsertAuto,  // ---?
+    GotoRoot,           // ---?
+    AutoFill,           // Tab
+    Clear,              // Backspace
+    NextTree,           // ArrowRight
+    PrevTree,           // ArrowLeft
+    NextBlank,          // ---?
+    PrevBlank,          // ---?
+    NextVariant,        // ArrowRight
+    PrevVariant,        // ArrowLefet
+    AcceptVariant,      // Enter
+    VecInsertBlank,     // Insert
+    VecInsertAuto,      // ---?
+    VecDelete,          // Delete, on a vector element
+    VecMoveBackward,    // PageUp, on a vector element
+    VecMoveForward,     // PageDown, on a vector element
+    OptionToggle,       // Space, on option
+    TextBegin,          // Enter, or any printable key, on text
+    TextInsert(Text),   // any printable key, while editing text
+    TextPrevChar,       // ArrowLeft, while editing text
+    TextNextChar,       // ArrowRight, while editing text
+    TextStart,          // Home (or ArrowUp), while editing text
+    TextEnd,            // End (or ArrowDown), while editing text
+    TextDeleteBack,     // Backspace, while editing text
+    TextDeleteForward,  // Delete, while editing text
+    TextCommit,         // Enter (or Tab), while editing text
+    TextCancel,         // Escape, while editing text
+    NatPushDigit(u8),   // 0-9, on nat
+    NatPopDigit,        // Backspace, on nat
+    NatIncrement,       // +, on nat
+    NatDecrement,       // -, on nat
+    BoolToggle,         // Space (or Enter), on bool
+    SearchBegin,        // /
+    SearchInsert(Text), // any printable key, while searching
+    SearchDeleteBack,   // Backspace, while searching
+    SearchNext,         // ArrowDown (or Tab), while searching
+    SearchPrev,         // ArrowUp, while searching
+    SearchCommit,       // Enter, while searching
+    SearchCancel,       // Escape, while searching
+    GotoPath(Path),     // ---?
+    Copy,               // Ctrl-c
+    Cut,                // Ctrl-x
+    Paste,              // Ctrl-v
+    PasteMerge,         // Ctrl-Shift-v
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
 pub enum Command {
     Init(InitCommand),
     Auto(AutoCommand),
@@ -132,18 +181,38 @@ pub struct MenuState {
     pub ctx: MenuCtx,           // invariant: see typ.
     pub tree: MenuTree,         // invariant: tree has type tree_typ.
     pub tree_typ: MenuType,
+    pub text_edit: Option<TextEdit>, // invariant: if Some, tree_typ is text.
+    pub search: Option<Search>,      // invariant: if Some, text_edit is None.
+}
+
+/// text being entered for the current tree, with the caret between `before` and `after`
+#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+pub struct TextEdit {
+    pub before: Text,
+    pub after: Text,
+}
+
+/// an incremental search for labels and primitive values, begun with the focus at `origin`
+#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+pub struct Search {
+    pub query: Text,
+    pub origin: Path,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
 pub struct Editor {
     pub state: Option<MenuState>,
     pub history: Vec<Command>,
+    /// the last copied (or cut) tree, with its type; it outlives each `Init`
+    pub clipboard: Option<(MenuTree, MenuType)>,
 }
 
 pub mod semantics {
     use super::*;
+    use num_traits::Zero;
+    use types::lang::Error;
 
-    pub type Err = String;
+    pub type Err = Error;
     pub type Res = Result<(), Err>;
 
     pub fn editor_eval(menu: &mut Editor, command: &Command) -> Res {
@@ -155,27 +224,43 @@ pub mod semantics {
                     ctx: MenuCtx::Root(typ.clone()),
                     tree: default_choice.clone(),
                     tree_typ: typ.clone(),
+                    text_edit: None,
+                    search: None,
                 });
                 Ok(())
             }
             Command::Edit(ref c) => match menu.state {
-                None => Err("Invalid editor state".to_string()),
-                Some(ref mut st) => state_eval_command(st, c),
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => state_eval_command(st, &mut menu.clipboard, c),
+            },
+            Command::Auto(ref c) => match menu.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => state_eval_auto(st, c),
             },
-            Command::Auto(ref _c) => unimplemented!(),
         };
         debug!("editor_eval({:?}) ==> {:?}", command, res);
         menu.history.push(command.clone());
         res
     }
 
-    pub fn state_eval_command(menu: &mut MenuState, command: &EditCommand) -> Res {
+    pub fn state_eval_command(
+        menu: &mut MenuState,
+        clipboard: &mut Option<(MenuTree, MenuType)>,
+        command: &EditCommand,
+    ) -> Res {
+        if menu.text_edit.is_some() {
+            return text_eval_command(menu, command);
+        };
+        if menu.search.is_some() {
+            return search_eval_command(menu, command);
+        };
         match command {
             &EditCommand::GotoRoot => goto_root(menu),
             &EditCommand::AutoFill => {
+                // fill blanks, but keep what is already edited
                 let tree = auto_fill(&menu.tree_typ, 1);
-                drop(tree_union(&menu.tree, &tree));
-                menu.tree = tree;
+                menu.tree = tree_union(&tree, &menu.tree, &mut focus_path(&menu.ctx))
+                    .map_err(Error::Menu)?;
                 Ok(())
             }
             &EditCommand::Clear => {
@@ -201,15 +286,504 @@ pub mod semantics {
             &EditCommand::NextBlank => next_blank(menu).map(|_| ()),
             &EditCommand::PrevBlank => prev_blank(menu).map(|_| ()),
 
-            &EditCommand::VecInsertBlank => {
-                assert_tree_tag(&menu.tree, &Tag::Vec)?;
-                unimplemented!()
-            }
+            &EditCommand::VecInsertBlank => vec_insert_blank(menu),
             &EditCommand::VecInsertAuto => {
-                assert_tree_tag(&menu.tree, &Tag::Vec)?;
-                state_eval_command(menu, &EditCommand::VecInsertBlank)?;
-                state_eval_command(menu, &EditCommand::AutoFill)
+                state_eval_command(menu, clipboard, &EditCommand::VecInsertBlank)?;
+                state_eval_command(menu, clipboard, &EditCommand::AutoFill)
+            }
+            &EditCommand::VecDelete => vec_delete(menu),
+            &EditCommand::VecMoveBackward => vec_move(menu, Dir1D::Backward),
+            &EditCommand::VecMoveForward => vec_move(menu, Dir1D::Forward),
+            &EditCommand::OptionToggle => match menu.tree {
+                MenuTree::Option(ref mut flag, _, _) => {
+                    *flag = !*flag;
+                    Ok(())
+                }
+                ref tree => Err(Error::MenuTag(Tag::Option, tree_tag(tree))),
+            },
+            &EditCommand::TextBegin => {
+                if unfold(&menu.tree_typ) != MenuType::Prim(PrimType::Text) {
+                    return Err(Error::Menu(super::Error::MenuTypeMismatch(
+                        focus_path(&menu.ctx),
+                        MenuType::Prim(PrimType::Text),
+                        menu.tree_typ.clone(),
+                    )));
+                };
+                let before = match menu.tree {
+                    MenuTree::Text(ref t) => t.clone(),
+                    _ => "".to_string(),
+                };
+                menu.text_edit = Some(TextEdit {
+                    before,
+                    after: "".to_string(),
+                });
+                Ok(())
+            }
+            &EditCommand::NatPushDigit(d) => {
+                if d > 9 {
+                    return Err(Error::Invalid(format!("not a decimal digit: {}", d)));
+                };
+                let n = nat_of_tree(menu)?;
+                menu.tree = MenuTree::Nat(n * 10u8 + d);
+                Ok(())
+            }
+            &EditCommand::NatPopDigit => {
+                let n = nat_of_tree(menu)?;
+                // removing the only digit leaves a blank
+                menu.tree = if n < Nat::from(10u8) {
+                    MenuTree::Blank(menu.tree_typ.clone())
+                } else {
+                    MenuTree::Nat(n / 10u8)
+                };
+                Ok(())
+            }
+            &EditCommand::NatIncrement => {
+                let n = nat_of_tree(menu)?;
+                menu.tree = MenuTree::Nat(n + 1u8);
+                Ok(())
+            }
+            &EditCommand::NatDecrement => {
+                let n = nat_of_tree(menu)?;
+                if n.is_zero() {
+                    return Err(Error::Invalid("cannot decrement zero".to_string()));
+                };
+                menu.tree = MenuTree::Nat(n - 1u8);
+                Ok(())
+            }
+            &EditCommand::BoolToggle => {
+                let b = match menu.tree {
+                    MenuTree::Bool(b) => b,
+                    MenuTree::Blank(ref t) if unfold(t) == MenuType::Prim(PrimType::Bool) => false,
+                    ref tree => {
+                        return Err(Error::MenuTag(Tag::Prim(PrimType::Bool), tree_tag(tree)))
+                    }
+                };
+                menu.tree = MenuTree::Bool(!b);
+                Ok(())
+            }
+            &EditCommand::SearchBegin => {
+                menu.search = Some(Search {
+                    query: "".to_string(),
+                    origin: focus_path(&menu.ctx),
+                });
+                Ok(())
+            }
+            &EditCommand::GotoPath(ref path) => goto_path(menu, path),
+            &EditCommand::Copy => {
+                *clipboard = Some((menu.tree.clone(), menu.tree_typ.clone()));
+                Ok(())
+            }
+            &EditCommand::Cut => {
+                let tree =
+                    std::mem::replace(&mut menu.tree, MenuTree::Blank(menu.tree_typ.clone()));
+                *clipboard = Some((tree, menu.tree_typ.clone()));
+                Ok(())
+            }
+            &EditCommand::Paste => {
+                menu.tree = clipboard_tree(menu, clipboard)?;
+                Ok(())
+            }
+            &EditCommand::PasteMerge => {
+                // fill blanks, but keep what is already edited (as with AutoFill)
+                let tree = clipboard_tree(menu, clipboard)?;
+                menu.tree = tree_union(&tree, &menu.tree, &mut focus_path(&menu.ctx))
+                    .map_err(Error::Menu)?;
+                Ok(())
+            }
+            &EditCommand::SearchInsert(_)
+            | &EditCommand::SearchDeleteBack
+            | &EditCommand::SearchNext
+            | &EditCommand::SearchPrev
+            | &EditCommand::SearchCommit
+            | &EditCommand::SearchCancel => {
+                Err(Error::Invalid("no search in progress".to_string()))
+            }
+            _ => Err(Error::Invalid("no text entry in progress".to_string())),
+        }
+    }
+
+    /// the tree on the clipboard, if it has the type of the current tree
+    fn clipboard_tree(
+        menu: &MenuState,
+        clipboard: &Option<(MenuTree, MenuType)>,
+    ) -> Result<MenuTree, Err> {
+        match *clipboard {
+            Some((ref tree, _)) => {
+                check_tree(tree, &menu.tree_typ, &mut focus_path(&menu.ctx))
+                    .map_err(Error::Menu)?;
+                Ok(tree.clone())
+            }
+            None => Err(Error::Invalid("nothing to paste".to_string())),
+        }
+    }
+
+    /// the number of the current nat tree, where a blank counts as zero
+    fn nat_of_tree(menu: &MenuState) -> Result<Nat, Err> {
+        match menu.tree {
+            MenuTree::Nat(ref n) => Ok(n.clone()),
+            MenuTree::Blank(ref t) if unfold(t) == MenuType::Prim(PrimType::Nat) => Ok(Nat::zero()),
+            ref tree => Err(Error::MenuTag(Tag::Prim(PrimType::Nat), tree_tag(tree))),
+        }
+    }
+
+    pub fn state_eval_auto(menu: &mut MenuState, command: &AutoCommand) -> Res {
+        match command {
+            AutoCommand::CheckMenuType => {
+                let (tree, _) = root_tree(menu)?;
+                check_tree(&tree, &menu.root_typ, &mut vec![]).map_err(Error::Menu)
+            }
+            AutoCommand::CheckComplete => {
+                let (tree, _) = root_tree(menu)?;
+                let mut blanks = vec![];
+                tree_blanks(&tree, &mut vec![], &mut blanks);
+                if blanks.is_empty() {
+                    Ok(())
+                } else {
+                    Err(Error::Menu(super::Error::Blanks(blanks)))
+                }
+            }
+            AutoCommand::Replace(tree) => {
+                check_tree(tree, &menu.tree_typ, &mut focus_path(&menu.ctx))
+                    .map_err(Error::Menu)?;
+                menu.tree = tree.clone();
+                Ok(())
+            }
+        }
+    }
+
+    /// check that the tree has the type, and that each subtree has the type that the tree gives it.
+    /// (the path locates the tree; on success, it is restored.)
+    pub fn check_tree(
+        tree: &MenuTree,
+        typ: &MenuType,
+        path: &mut Path,
+    ) -> Result<(), super::Error> {
+        let found = tree_typ(tree);
+        if !typ_equiv(&found, typ) {
+            return Err(super::Error::MenuTypeMismatch(
+                path.clone(),
+                typ.clone(),
+                found,
+            ));
+        };
+        let mut check = |step: Step, tree: &MenuTree, typ: &MenuType| {
+            path.push(step);
+            let res = check_tree(tree, typ, path);
+            path.pop();
+            res
+        };
+        match tree {
+            MenuTree::Product(fields) => {
+                for (l, t, ty) in fields.iter() {
+                    check(Step::Field(l.clone()), t, ty)?
+                }
+            }
+            MenuTree::Variant(arms) => {
+                for (l, t, ty) in arms
+                    .before
+                    .iter()
+                    .chain(arms.choice.iter())
+                    .chain(arms.after.iter())
+                {
+                    check(Step::Choice(l.clone()), t, ty)?
+                }
+            }
+            MenuTree::Option(_, t, ty) => check(Step::Some, t, ty)?,
+            MenuTree::Vec(trees, ty) => {
+                for (i, t) in trees.iter().enumerate() {
+                    check(Step::Pos(i), t, ty)?
+                }
+            }
+            MenuTree::Tup(trees) => {
+                for (i, (t, ty)) in trees.iter().enumerate() {
+                    check(Step::Pos(i), t, ty)?
+                }
+            }
+            MenuTree::Blank(_)
+            | MenuTree::Nat(_)
+            | MenuTree::Text(_)
+            | MenuTree::Bool(_)
+            | MenuTree::Unit => (),
+        };
+        Ok(())
+    }
+
+    /// the blanks that a complete tree must fill, with their paths: those of the fields,
+    /// the chosen variant arm, the content of a present option, and each vector or tuple
+    /// element.  (a variant without a choice is itself a blank.)
+    pub fn tree_blanks(tree: &MenuTree, path: &mut Path, blanks: &mut Vec<(Path, MenuType)>) {
+        let mut visit = |step: Step, tree: &MenuTree, blanks: &mut Vec<(Path, MenuType)>| {
+            path.push(step);
+            tree_blanks(tree, path, blanks);
+            path.pop();
+        };
+        match tree {
+            MenuTree::Blank(typ) => blanks.push((path.clone(), typ.clone())),
+            MenuTree::Product(fields) => {
+                for (l, t, _) in fields.iter() {
+                    visit(Step::Field(l.clone()), t, blanks)
+                }
+            }
+            MenuTree::Variant(arms) => match arms.choice {
+                Some((ref l, ref t, _)) => visit(Step::Choice(l.clone()), t, blanks),
+                None => blanks.push((path.clone(), tree_typ(tree))),
+            },
+            MenuTree::Option(true, t, _) => visit(Step::Some, t, blanks),
+            MenuTree::Vec(trees, _) => {
+                for (i, t) in trees.iter().enumerate() {
+                    visit(Step::Pos(i), t, blanks)
+                }
+            }
+            MenuTree::Tup(trees) => {
+                for (i, (t, _)) in trees.iter().enumerate() {
+                    visit(Step::Pos(i), t, blanks)
+                }
+            }
+            MenuTree::Option(false, _, _)
+            | MenuTree::Nat(_)
+            | MenuTree::Text(_)
+            | MenuTree::Bool(_)
+            | MenuTree::Unit => (),
+        }
+    }
+
+    /// evaluate a command while entering text; only text commands are valid.
+    fn text_eval_command(menu: &mut MenuState, command: &EditCommand) -> Res {
+        let mut edit = match menu.text_edit.take() {
+            Some(edit) => edit,
+            None => return Err(Error::Invalid("no text entry in progress".to_string())),
+        };
+        let res = match command {
+            EditCommand::TextInsert(t) => {
+                edit.before.push_str(t);
+                Ok(())
+            }
+            EditCommand::TextPrevChar => match edit.before.pop() {
+                Some(c) => {
+                    edit.after.insert(0, c);
+                    Ok(())
+                }
+                None => Err(Error::OutOfBounds(vec![0])),
+            },
+            EditCommand::TextNextChar => match edit.after.chars().next() {
+                Some(c) => {
+                    edit.after.remove(0);
+                    edit.before.push(c);
+                    Ok(())
+                }
+                None => Err(Error::OutOfBounds(vec![edit.before.chars().count() + 1])),
+            },
+            EditCommand::TextStart => {
+                edit.after = format!("{}{}", edit.before, edit.after);
+                edit.before.clear();
+                Ok(())
+            }
+            EditCommand::TextEnd => {
+                edit.before.push_str(&edit.after);
+                edit.after.clear();
+                Ok(())
+            }
+            EditCommand::TextDeleteBack => match edit.before.pop() {
+                Some(_) => Ok(()),
+                None => Err(Error::OutOfBounds(vec![0])),
+            },
+            EditCommand::TextDeleteForward => match edit.after.chars().next() {
+                Some(_) => {
+                    edit.after.remove(0);
+                    Ok(())
+                }
+                None => Err(Error::OutOfBounds(vec![edit.before.chars().count() + 1])),
+            },
+            EditCommand::TextCommit => {
+                menu.tree = MenuTree::Text(format!("{}{}", edit.before, edit.after));
+                return Ok(());
+            }
+            EditCommand::TextCancel => return Ok(()),
+            _ => Err(Error::Invalid("text entry in progress".to_string())),
+        };
+        menu.text_edit = Some(edit);
+        res
+    }
+
+    /// evaluate a command while searching; only search commands are valid.
+    fn search_eval_command(menu: &mut MenuState, command: &EditCommand) -> Res {
+        let mut search = match menu.search.take() {
+            Some(search) => search,
+            None => return Err(Error::Invalid("no search in progress".to_string())),
+        };
+        let res = match command {
+            EditCommand::SearchInsert(t) => {
+                search.query.push_str(t);
+                search_goto(menu, &search.query, Dir1D::Forward, true)
+            }
+            EditCommand::SearchDeleteBack => match search.query.pop() {
+                // search again from the origin, so that a shorter query may match earlier
+                Some(_) => goto_path(menu, &search.origin).and_then(|()| {
+                    if search.query.is_empty() {
+                        Ok(())
+                    } else {
+                        search_goto(menu, &search.query, Dir1D::Forward, true)
+                    }
+                }),
+                None => Err(Error::OutOfBounds(vec![0])),
+            },
+            EditCommand::SearchNext => search_goto(menu, &search.query, Dir1D::Forward, false),
+            EditCommand::SearchPrev => search_goto(menu, &search.query, Dir1D::Backward, false),
+            EditCommand::SearchCommit => return Ok(()),
+            EditCommand::SearchCancel => return goto_path(menu, &search.origin),
+            _ => Err(Error::Invalid("search in progress".to_string())),
+        };
+        menu.search = Some(search);
+        res
+    }
+
+    /// move the focus to the next (or previous) subtree whose label or primitive value
+    /// contains the query, ignoring case, and wrapping around the root tree.
+    /// (the focused subtree itself counts first only if `at_focus`.)
+    pub fn search_goto(menu: &mut MenuState, query: &str, dir: Dir1D, at_focus: bool) -> Res {
+        let (tree, _) = root_tree(menu)?;
+        let mut paths = vec![];
+        search_tree(&tree, &query.to_lowercase(), None, &mut vec![], &mut paths);
+        let focus = focus_path(&menu.ctx);
+        let i = paths.iter().position(|(p, _)| *p == focus).unwrap_or(0);
+        let n = paths.len();
+        let start = if at_focus { 0 } else { 1 };
+        for k in start..n + start {
+            let j = match dir {
+                Dir1D::Forward => (i + k) % n,
+                Dir1D::Backward => (i + n - k % n) % n,
+            };
+            if paths[j].1 {
+                let path = paths[j].0.clone();
+                return goto_path(menu, &path);
+            }
+        }
+        Err(Error::Invalid(format!("no match: {:?}", query)))
+    }
+
+    /// the paths of the (navigable) subtrees, in order, each with whether its label (given
+    /// by its parent) or its primitive value contains the lower-case query
+    fn search_tree(
+        tree: &MenuTree,
+        query: &str,
+        label: Option<&Label>,
+        path: &mut Path,
+        paths: &mut Vec<(Path, bool)>,
+    ) {
+        let contains = |s: String| !query.is_empty() && s.to_lowercase().contains(query);
+        // (not `is_some_and`, which older toolchains lack)
+        #[allow(clippy::unnecessary_map_or)]
+        let hit = label.map_or(false, |l| contains(l.to_string()))
+            || match tree {
+                MenuTree::Nat(n) => contains(n.to_string()),
+                MenuTree::Text(t) => contains(t.clone()),
+                MenuTree::Bool(b) => contains(b.to_string()),
+                _ => false,
+            };
+        paths.push((path.clone(), hit));
+        let mut visit =
+            |step: Step, label: Option<&Label>, tree: &MenuTree, paths: &mut Vec<(Path, bool)>| {
+                path.push(step);
+                search_tree(tree, query, label, path, paths);
+                path.pop();
+            };
+        match tree {
+            MenuTree::Product(fields) => {
+                for (l, t, _) in fields.iter() {
+                    visit(Step::Field(l.clone()), Some(l), t, paths)
+                }
+            }
+            MenuTree::Variant(arms) => {
+                if let Some((ref l, ref t, _)) = arms.choice {
+                    visit(Step::Choice(l.clone()), Some(l), t, paths)
+                }
+            }
+            MenuTree::Option(true, t, _) => visit(Step::Some, None, t, paths),
+            MenuTree::Vec(trees, _) => {
+                for (i, t) in trees.iter().enumerate() {
+                    visit(Step::Pos(i), None, t, paths)
+                }
+            }
+            MenuTree::Tup(trees) => {
+                for (i, (t, _)) in trees.iter().enumerate() {
+                    visit(Step::Pos(i), None, t, paths)
+                }
+            }
+            MenuTree::Option(false, _, _)
+            | MenuTree::Blank(_)
+            | MenuTree::Nat(_)
+            | MenuTree::Text(_)
+            | MenuTree::Bool(_)
+            | MenuTree::Unit => (),
+        }
+    }
+
+    /// the path from the root tree to the focused subtree
+    pub fn focus_path(ctx: &MenuCtx) -> Path {
+        let mut path = vec![];
+        let mut ctx = ctx;
+        loop {
+            let (step, outer) = match ctx {
+                MenuCtx::Root(_) => break,
+                MenuCtx::Product(sel) => (Step::Field(sel.label.clone()), &sel.ctx),
+                MenuCtx::Variant(sel) => (Step::Choice(sel.label.clone()), &sel.ctx),
+                MenuCtx::Option(_, ctx) => (Step::Some, &**ctx),
+                MenuCtx::Vec(sel) | MenuCtx::Tup(sel) => (Step::Pos(sel.before.len()), &sel.ctx),
+            };
+            path.push(step);
+            ctx = outer;
+        }
+        path.reverse();
+        path
+    }
+
+    /// move the focus to the subtree at the path from the root tree; if there is none,
+    /// the focus stays put.
+    pub fn goto_path(menu: &mut MenuState, path: &[Step]) -> Res {
+        let mut st = menu.clone();
+        goto_root(&mut st)?;
+        for step in path.iter() {
+            descend_step(&mut st, step)?
+        }
+        *menu = st;
+        Ok(())
+    }
+
+    /// move the focus to the subtree at the step from the focused tree
+    fn descend_step(menu: &mut MenuState, step: &Step) -> Res {
+        match (step, menu.tree.clone()) {
+            (Step::Field(l), MenuTree::Product(mut fields)) => {
+                match fields.iter().position(|(l2, _, _)| l2 == l) {
+                    Some(i) => {
+                        let after = fields.split_off(i + 1);
+                        let (label, tree, tree_typ) = fields.pop().unwrap();
+                        menu.tree = tree;
+                        menu.tree_typ = tree_typ;
+                        menu.ctx = MenuCtx::Product(Box::new(LabelSelect {
+                            before: fields,
+                            ctx: menu.ctx.clone(),
+                            label,
+                            after,
+                        }));
+                        Ok(())
+                    }
+                    None => Err(Error::Invalid(format!("no subtree at step {:?}", step))),
+                }
+            }
+            (Step::Choice(l), MenuTree::Variant(ref arms))
+                if arms.choice.as_ref().map(|(l2, _, _)| l2) == Some(l) =>
+            {
+                descend(menu, Dir1D::Forward)
             }
+            (Step::Some, MenuTree::Option(true, _, _)) => descend(menu, Dir1D::Forward),
+            (Step::Pos(i), MenuTree::Vec(trees, typ)) if *i < trees.len() => {
+                let trees = trees.into_iter().map(|t| (t, typ.clone())).collect();
+                focus_pos_at(menu, trees, *i, MenuCtx::Vec)
+            }
+            (Step::Pos(i), MenuTree::Tup(trees)) if *i < trees.len() => {
+                focus_pos_at(menu, trees, *i, MenuCtx::Tup)
+            }
+            (step, _) => Err(Error::Invalid(format!("no subtree at step {:?}", step))),
         }
     }
 
@@ -247,7 +821,138 @@ pub mod semantics {
             MenuType::Option(_) => Tag::Option,
             MenuType::Tup(_) => Tag::Tup,
             MenuType::Vec(_) => Tag::Vec,
+            MenuType::Named(_, _) => match unfold(typ) {
+                // a definition without structure; no tree has this type
+                MenuType::Named(_, _) => Tag::Blank,
+                unfolded => typ_tag(&unfolded),
+            },
+            // unbound; no tree has this type
+            MenuType::Ref(_) => Tag::Blank,
+        }
+    }
+
+    /// unfold the named type(s) at the head of the type, once each, until reaching its structure.
+    /// (types are unfolded lazily, as trees take their structure; the subtrees' types stay named.)
+    /// a definition without structure (e.g., `type T = T`, or `type T = U; type U = T`) stays
+    /// named, once unfolding reaches a named type for the second time.
+    pub fn unfold(typ: &MenuType) -> MenuType {
+        let mut typ = typ.clone();
+        // the named types unfolded so far; by their definitions, not just their names, which
+        // inner definitions may shadow
+        let mut unfolded: Vec<MenuType> = vec![];
+        while let MenuType::Named(ref name, ref def) = typ.clone() {
+            if unfolded.contains(&typ) {
+                break;
+            };
+            unfolded.push(typ.clone());
+            typ = subst(def, name, &typ)
         }
+        typ
+    }
+
+    /// replace each reference to the name (not shadowed by another definition) with the type
+    fn subst(typ: &MenuType, name: &Label, with: &MenuType) -> MenuType {
+        let fields = |fs: &Vec<(Label, MenuType)>| {
+            fs.iter()
+                .map(|(l, t)| (l.clone(), subst(t, name, with)))
+                .collect()
+        };
+        match typ {
+            MenuType::Ref(n) if n == name => with.clone(),
+            MenuType::Named(n, _) if n == name => typ.clone(),
+            MenuType::Named(n, def) => MenuType::Named(n.clone(), Box::new(subst(def, name, with))),
+            MenuType::Ref(_) | MenuType::Prim(_) => typ.clone(),
+            MenuType::Variant(fs) => MenuType::Variant(fields(fs)),
+            MenuType::Product(fs) => MenuType::Product(fields(fs)),
+            MenuType::Option(t) => MenuType::Option(Box::new(subst(t, name, with))),
+            MenuType::Vec(t) => MenuType::Vec(Box::new(subst(t, name, with))),
+            MenuType::Tup(ts) => MenuType::Tup(ts.iter().map(|t| subst(t, name, with)).collect()),
+        }
+    }
+
+    /// do the types have the same structure, after unfolding any named types?
+    pub fn typ_equiv(typ1: &MenuType, typ2: &MenuType) -> bool {
+        typ_equiv_rec(typ1, typ2, &mut vec![])
+    }
+
+    // each pair of named types assumed equivalent, while comparing their structures.
+    fn typ_equiv_rec(
+        typ1: &MenuType,
+        typ2: &MenuType,
+        assumed: &mut Vec<(MenuType, MenuType)>,
+    ) -> bool {
+        if typ1 == typ2 {
+            return true;
+        };
+        let named = |t: &MenuType| matches!(t, MenuType::Named(_, _));
+        if named(typ1) || named(typ2) {
+            let pair = (typ1.clone(), typ2.clone());
+            if assumed.contains(&pair) {
+                return true;
+            };
+            assumed.push(pair);
+            let (typ1, typ2) = (unfold(typ1), unfold(typ2));
+            if named(&typ1) || named(&typ2) {
+                return false;
+            };
+            return typ_equiv_rec(&typ1, &typ2, assumed);
+        };
+        let mut fields_equiv = |fs1: &Vec<(Label, MenuType)>, fs2: &Vec<(Label, MenuType)>| {
+            fs1.len() == fs2.len()
+                && fs1
+                    .iter()
+                    .zip(fs2.iter())
+                    .all(|((l1, t1), (l2, t2))| l1 == l2 && typ_equiv_rec(t1, t2, assumed))
+        };
+        match (typ1, typ2) {
+            (MenuType::Variant(fs1), MenuType::Variant(fs2)) => fields_equiv(fs1, fs2),
+            (MenuType::Product(fs1), MenuType::Product(fs2)) => fields_equiv(fs1, fs2),
+            (MenuType::Option(t1), MenuType::Option(t2)) => typ_equiv_rec(t1, t2, assumed),
+            (MenuType::Vec(t1), MenuType::Vec(t2)) => typ_equiv_rec(t1, t2, assumed),
+            (MenuType::Tup(ts1), MenuType::Tup(ts2)) => {
+                ts1.len() == ts2.len()
+                    && ts1
+                        .iter()
+                        .zip(ts2.iter())
+                        .all(|(t1, t2)| typ_equiv_rec(t1, t2, assumed))
+            }
+            (_, _) => false,
+        }
+    }
+
+    /// the type of the tree, from the types that it holds for its subtrees
+    pub fn tree_typ(tree: &MenuTree) -> MenuType {
+        match tree {
+            MenuTree::Product(fields) => MenuType::Product(
+                fields
+                    .iter()
+                    .map(|(l, _, t)| (l.clone(), t.clone()))
+                    .collect(),
+            ),
+            MenuTree::Variant(arms) => MenuType::Variant(
+                arms.before
+                    .iter()
+                    .chain(arms.choice.iter())
+                    .chain(arms.after.iter())
+                    .map(|(l, _, t)| (l.clone(), t.clone()))
+                    .collect(),
+            ),
+            MenuTree::Option(_, _, t) => MenuType::Option(Box::new(t.clone())),
+            MenuTree::Vec(_, t) => MenuType::Vec(Box::new(t.clone())),
+            MenuTree::Tup(trees) => MenuType::Tup(trees.iter().map(|(_, t)| t.clone()).collect()),
+            MenuTree::Blank(t) => t.clone(),
+            MenuTree::Nat(_) => MenuType::Prim(PrimType::Nat),
+            MenuTree::Text(_) => MenuType::Prim(PrimType::Text),
+            MenuTree::Bool(_) => MenuType::Prim(PrimType::Bool),
+            MenuTree::Unit => MenuType::Prim(PrimType::Unit),
+        }
+    }
+
+    /// the entire tree of the menu (not just its focus), and its type
+    pub fn root_tree(menu: &MenuState) -> Result<(MenuTree, MenuType), Err> {
+        let mut menu = menu.clone();
+        goto_root(&mut menu)?;
+        Ok((menu.tree, menu.tree_typ))
     }
 
     pub fn assert_tree_tag(tree: &MenuTree, tag: &Tag) -> Res {
@@ -255,7 +960,7 @@ pub mod semantics {
         if &tt == tag {
             Ok(())
         } else {
-            Err(format!("expected {:?} but found {:?}: {:?}", tag, tt, tree))
+            Err(Error::MenuTag(tag.clone(), tt))
         }
     }
 
@@ -317,7 +1022,7 @@ pub mod semantics {
 
     pub fn ascend(menu: &mut MenuState) -> Res {
         match menu.ctx.clone() {
-            MenuCtx::Root(_) => Err("cannot ascend: already at root".to_string()),
+            MenuCtx::Root(_) => Err(Error::Invalid("cannot ascend: already at root".to_string())),
             MenuCtx::Product(mut sel) => {
                 let mut arms = sel.before;
                 arms.push((sel.label, menu.tree.clone(), menu.tree_typ.clone()));
@@ -350,16 +1055,185 @@ pub mod semantics {
                 menu.ctx = sel.ctx;
                 Ok(())
             }
-            MenuCtx::Option(_flag, _menu) => unimplemented!(),
-            MenuCtx::Vec(_sel) => unimplemented!(),
-            MenuCtx::Tup(_sel) => unimplemented!(),
+            MenuCtx::Option(flag, ctx) => {
+                menu.tree =
+                    MenuTree::Option(flag, Box::new(menu.tree.clone()), menu.tree_typ.clone());
+                menu.tree_typ = MenuType::Option(Box::new(menu.tree_typ.clone()));
+                menu.ctx = *ctx;
+                Ok(())
+            }
+            MenuCtx::Vec(sel) => {
+                let typ = menu.tree_typ.clone();
+                let (trees, ctx) = unfocus_pos(*sel, menu.tree.clone(), typ.clone());
+                menu.tree = MenuTree::Vec(trees.into_iter().map(|(t, _)| t).collect(), typ.clone());
+                menu.tree_typ = MenuType::Vec(Box::new(typ));
+                menu.ctx = ctx;
+                Ok(())
+            }
+            MenuCtx::Tup(sel) => {
+                let (trees, ctx) = unfocus_pos(*sel, menu.tree.clone(), menu.tree_typ.clone());
+                menu.tree_typ = MenuType::Tup(trees.iter().map(|(_, t)| t.clone()).collect());
+                menu.tree = MenuTree::Tup(trees);
+                menu.ctx = ctx;
+                Ok(())
+            }
+        }
+    }
+
+    /// the positions of a vector or tuple context, filled by the given tree and type
+    fn unfocus_pos(
+        mut sel: PosSelect,
+        tree: MenuTree,
+        typ: MenuType,
+    ) -> (Vec<(MenuTree, MenuType)>, MenuCtx) {
+        let mut trees = sel.before;
+        trees.push((tree, typ));
+        trees.append(&mut sel.after);
+        (trees, sel.ctx)
+    }
+
+    /// focus on the first (or last) position of a vector or tuple
+    fn focus_pos(
+        menu: &mut MenuState,
+        trees: Vec<(MenuTree, MenuType)>,
+        dir: Dir1D,
+        ctx: fn(Box<PosSelect>) -> MenuCtx,
+    ) -> Res {
+        if trees.is_empty() {
+            return Err(Error::Invalid("no subtrees".to_string()));
+        };
+        let i = match dir {
+            Dir1D::Forward => 0,
+            Dir1D::Backward => trees.len() - 1,
+        };
+        focus_pos_at(menu, trees, i, ctx)
+    }
+
+    /// focus on the given position of a vector or tuple (which must have it)
+    fn focus_pos_at(
+        menu: &mut MenuState,
+        mut trees: Vec<(MenuTree, MenuType)>,
+        i: usize,
+        ctx: fn(Box<PosSelect>) -> MenuCtx,
+    ) -> Res {
+        let after = trees.split_off(i + 1);
+        let (tree, tree_typ) = trees.pop().unwrap();
+        menu.tree = tree;
+        menu.tree_typ = tree_typ;
+        menu.ctx = ctx(Box::new(PosSelect {
+            before: trees,
+            ctx: menu.ctx.clone(),
+            after,
+        }));
+        Ok(())
+    }
+
+    /// move the focus to the next (or previous) position of a vector or tuple context
+    fn sibling_pos(
+        menu: &mut MenuState,
+        mut sel: PosSelect,
+        dir: Dir1D,
+        ctx: fn(Box<PosSelect>) -> MenuCtx,
+    ) -> Res {
+        let next = match dir {
+            Dir1D::Forward if !sel.after.is_empty() => {
+                sel.before.push((menu.tree.clone(), menu.tree_typ.clone()));
+                sel.after.remove(0)
+            }
+            Dir1D::Backward if !sel.before.is_empty() => {
+                sel.after
+                    .insert(0, (menu.tree.clone(), menu.tree_typ.clone()));
+                sel.before.pop().unwrap()
+            }
+            _ => {
+                // wrap around, as with product fields
+                ascend(menu)?;
+                return descend(menu, dir);
+            }
+        };
+        menu.tree = next.0;
+        menu.tree_typ = next.1;
+        menu.ctx = ctx(Box::new(sel));
+        Ok(())
+    }
+
+    /// insert a blank element at the end of the focused vector, or else after the focused vector
+    /// element.  (a focused vector that is itself an element gets the new element, so that an
+    /// empty, nested vector can get its first one.)
+    pub fn vec_insert_blank(menu: &mut MenuState) -> Res {
+        match (menu.tree.clone(), menu.ctx.clone()) {
+            (MenuTree::Vec(trees, typ), _) => {
+                let before = trees.into_iter().map(|t| (t, typ.clone())).collect();
+                menu.tree = MenuTree::Blank(typ.clone());
+                menu.tree_typ = typ;
+                menu.ctx = MenuCtx::Vec(Box::new(PosSelect {
+                    before,
+                    ctx: menu.ctx.clone(),
+                    after: vec![],
+                }));
+                Ok(())
+            }
+            (_, MenuCtx::Vec(mut sel)) => {
+                sel.before.push((menu.tree.clone(), menu.tree_typ.clone()));
+                menu.tree = MenuTree::Blank(menu.tree_typ.clone());
+                menu.ctx = MenuCtx::Vec(sel);
+                Ok(())
+            }
+            (ref tree, _) => Err(Error::MenuTag(Tag::Vec, tree_tag(tree))),
+        }
+    }
+
+    /// delete the focused vector element, focusing on the next (or else previous) element, if any
+    pub fn vec_delete(menu: &mut MenuState) -> Res {
+        match menu.ctx.clone() {
+            MenuCtx::Vec(mut sel) => {
+                let next = if !sel.after.is_empty() {
+                    Some(sel.after.remove(0))
+                } else {
+                    sel.before.pop()
+                };
+                match next {
+                    Some((tree, _)) => {
+                        menu.tree = tree;
+                        menu.ctx = MenuCtx::Vec(sel);
+                    }
+                    None => {
+                        menu.tree = MenuTree::Vec(vec![], menu.tree_typ.clone());
+                        menu.tree_typ = MenuType::Vec(Box::new(menu.tree_typ.clone()));
+                        menu.ctx = sel.ctx;
+                    }
+                };
+                Ok(())
+            }
+            ref ctx => Err(Error::MenuTag(Tag::Vec, ctx_tag(ctx))),
+        }
+    }
+
+    /// swap the focused vector element with its next (or previous) element, keeping the focus on it
+    pub fn vec_move(menu: &mut MenuState, dir: Dir1D) -> Res {
+        match menu.ctx {
+            MenuCtx::Vec(ref mut sel) => match dir {
+                Dir1D::Forward if !sel.after.is_empty() => {
+                    let next = sel.after.remove(0);
+                    sel.before.push(next);
+                    Ok(())
+                }
+                Dir1D::Backward if !sel.before.is_empty() => {
+                    let prev = sel.before.pop().unwrap();
+                    sel.after.insert(0, prev);
+                    Ok(())
+                }
+                Dir1D::Forward => Err(Error::OutOfBounds(vec![sel.before.len() + 1])),
+                Dir1D::Backward => Err(Error::OutOfBounds(vec![0])),
+            },
+            ref ctx => Err(Error::MenuTag(Tag::Vec, ctx_tag(ctx))),
         }
     }
 
     pub fn descend(menu: &mut MenuState, dir: Dir1D) -> Res {
         // navigate product field structure; ignore unchosen variant options.
         match menu.tree {
-            MenuTree::Blank(_) => Err("no subtrees".to_string()),
+            MenuTree::Blank(_) => Err(Error::Invalid("no subtrees".to_string())),
             MenuTree::Product(ref trees) => {
                 let mut trees = trees.clone();
                 if trees.len() > 0 {
@@ -391,7 +1265,7 @@ pub mod semantics {
                         }
                     }
                 } else {
-                    Err("no subtrees".to_string())
+                    Err(Error::Invalid("no subtrees".to_string()))
                 }
             }
             MenuTree::Variant(ref trees) => {
@@ -410,12 +1284,26 @@ pub mod semantics {
                             Ok(())
                         }
                     },
-                    None => Err("no choice subtree".to_string()),
+                    None => Err(Error::Invalid("no choice subtree".to_string())),
                 }
             }
-            _ => {
-                // to do
-                Err("not implemented".to_string())
+            MenuTree::Option(true, ref tree, ref tree_t) => {
+                menu.ctx = MenuCtx::Option(true, Box::new(menu.ctx.clone()));
+                menu.tree_typ = tree_t.clone();
+                menu.tree = (**tree).clone();
+                Ok(())
+            }
+            MenuTree::Option(false, _, _) => Err(Error::Invalid("option is none".to_string())),
+            MenuTree::Vec(ref trees, ref typ) => {
+                let trees = trees.iter().map(|t| (t.clone(), typ.clone())).collect();
+                focus_pos(menu, trees, dir, MenuCtx::Vec)
+            }
+            MenuTree::Tup(ref trees) => {
+                let trees = trees.clone();
+                focus_pos(menu, trees, dir, MenuCtx::Tup)
+            }
+            MenuTree::Nat(_) | MenuTree::Text(_) | MenuTree::Bool(_) | MenuTree::Unit => {
+                Err(Error::Invalid("no subtrees".to_string()))
             }
         }
     }
@@ -470,7 +1358,7 @@ pub mod semantics {
                     }
                 }
             }
-            _ => Err("expected tree to be a variant".to_string()),
+            _ => Err(Error::MenuTag(Tag::Variant, tree_tag(&menu.tree))),
         }
     }
 
@@ -493,11 +1381,12 @@ pub mod semantics {
                 }
             }
             MenuCtx::Root(_) => Ok(()),
-            MenuCtx::Variant(_) => next_sibling(menu),
-            _ => {
-                error!("{:?}", menu.ctx);
-                Ok(())
+            MenuCtx::Variant(_) | MenuCtx::Option(_, _) => {
+                ascend(menu)?;
+                next_sibling(menu)
             }
+            MenuCtx::Vec(sel) => sibling_pos(menu, *sel, Dir1D::Forward, MenuCtx::Vec),
+            MenuCtx::Tup(sel) => sibling_pos(menu, *sel, Dir1D::Forward, MenuCtx::Tup),
         }
     }
 
@@ -505,8 +1394,7 @@ pub mod semantics {
         match menu.ctx.clone() {
             MenuCtx::Product(mut sel) => {
                 sel.after
-                    .push((sel.label, menu.tree.clone(), menu.tree_typ.clone()));
-                sel.after.rotate_left(1);
+                    .insert(0, (sel.label, menu.tree.clone(), menu.tree_typ.clone()));
                 if sel.before.len() > 0 {
                     let (label, tree, tree_typ) = sel.before.pop().unwrap();
                     sel.label = label;
@@ -520,40 +1408,104 @@ pub mod semantics {
                 }
             }
             MenuCtx::Root(_) => Ok(()),
-            MenuCtx::Variant(_) => {
+            MenuCtx::Variant(_) | MenuCtx::Option(_, _) => {
                 ascend(menu)?;
                 prev_sibling(menu)
             }
-            _ => {
-                error!("{:?}", menu.ctx);
-                Ok(())
-            }
+            MenuCtx::Vec(sel) => sibling_pos(menu, *sel, Dir1D::Backward, MenuCtx::Vec),
+            MenuCtx::Tup(sel) => sibling_pos(menu, *sel, Dir1D::Backward, MenuCtx::Tup),
         }
     }
 
-    // to do -- change name to `tree_update`? -- prefers second tree when they are each non-blank and disagree
-    pub fn tree_union(tree1: &MenuTree, tree2: &MenuTree) -> MenuTree {
-        // todo -- assert that the blanks' types agree
-        match (tree1, tree2) {
-            (&MenuTree::Blank(_), _) => tree2.clone(),
-            (_, &MenuTree::Blank(_)) => tree1.clone(),
-            (&MenuTree::Variant(ref _arms1), &MenuTree::Variant(ref _arms2)) => {
-                // to do
-                unimplemented!()
-            }
-            (&MenuTree::Product(ref fields1), &MenuTree::Product(ref fields2)) => {
-                let mut fields3 = vec![];
-                for ((l1, t1, tt1), (l2, t2, tt2)) in fields1.iter().zip(fields2.iter()) {
-                    assert_eq!(l1, l2);
-                    assert_eq!(tt1, tt2);
-                    let t3 = tree_union(t1, t2);
-                    fields3.push((l1.clone(), t3, tt1.clone()));
+    /// merge two trees of the same type, filling the blanks of each with the subtrees of the
+    /// other; where each is non-blank and they disagree, prefer the second tree (its variant
+    /// choice, option flag, primitive value and leading vector elements).
+    /// (the path locates the trees; on success, it is restored.)
+    pub fn tree_union(
+        tree1: &MenuTree,
+        tree2: &MenuTree,
+        path: &mut Path,
+    ) -> Result<MenuTree, super::Error> {
+        let (typ1, typ2) = (tree_typ(tree1), tree_typ(tree2));
+        if !typ_equiv(&typ1, &typ2) {
+            return Err(super::Error::Conflict(path.clone(), typ1, typ2));
+        };
+        let mut union = |step: Step, tree1: &MenuTree, tree2: &MenuTree| {
+            path.push(step);
+            let res = tree_union(tree1, tree2, path);
+            path.pop();
+            res
+        };
+        Ok(match (tree1, tree2) {
+            (MenuTree::Blank(_), _) => tree2.clone(),
+            (_, MenuTree::Blank(_)) => tree1.clone(),
+            (MenuTree::Product(fields1), MenuTree::Product(fields2)) => {
+                let mut fields = vec![];
+                for ((l, t1, ty), (_, t2, _)) in fields1.iter().zip(fields2.iter()) {
+                    fields.push((
+                        l.clone(),
+                        union(Step::Field(l.clone()), t1, t2)?,
+                        ty.clone(),
+                    ))
                 }
-                MenuTree::Product(fields3)
+                MenuTree::Product(fields)
             }
-            // to do -- handle other cases by preferring second tree?
-            (_, _) => unimplemented!(),
-        }
+            (MenuTree::Variant(arms1), MenuTree::Variant(arms2)) => {
+                let all = |arms: &LabelChoice| -> Vec<(Label, MenuTree, MenuType)> {
+                    arms.before
+                        .iter()
+                        .chain(arms.choice.iter())
+                        .chain(arms.after.iter())
+                        .cloned()
+                        .collect()
+                };
+                let mut merged = vec![];
+                for ((l, t1, ty), (_, t2, _)) in all(arms1).into_iter().zip(all(arms2)) {
+                    merged.push((l.clone(), union(Step::Choice(l), &t1, &t2)?, ty))
+                }
+                let choice = match (&arms1.choice, &arms2.choice) {
+                    (_, Some(_)) => Some(arms2.before.len()),
+                    (Some(_), None) => Some(arms1.before.len()),
+                    (None, None) => None,
+                };
+                let mut after = match choice {
+                    Some(i) => merged.split_off(i),
+                    None => vec![],
+                };
+                let choice = choice.map(|_| after.remove(0));
+                MenuTree::Variant(Box::new(LabelChoice {
+                    before: merged,
+                    choice,
+                    after,
+                }))
+            }
+            (MenuTree::Option(_, t1, ty), MenuTree::Option(flag2, t2, _)) => {
+                MenuTree::Option(*flag2, Box::new(union(Step::Some, t1, t2)?), ty.clone())
+            }
+            (MenuTree::Vec(trees1, ty), MenuTree::Vec(trees2, _)) => {
+                let mut trees = vec![];
+                for (i, (t1, t2)) in trees1.iter().zip(trees2.iter()).enumerate() {
+                    trees.push(union(Step::Pos(i), t1, t2)?)
+                }
+                // the longer vector contributes its remaining elements
+                let rest = if trees1.len() > trees2.len() {
+                    &trees1[trees.len()..]
+                } else {
+                    &trees2[trees.len()..]
+                };
+                trees.extend_from_slice(rest);
+                MenuTree::Vec(trees, ty.clone())
+            }
+            (MenuTree::Tup(trees1), MenuTree::Tup(trees2)) => {
+                let mut trees = vec![];
+                for (i, ((t1, ty), (t2, _))) in trees1.iter().zip(trees2.iter()).enumerate() {
+                    trees.push((union(Step::Pos(i), t1, t2)?, ty.clone()))
+                }
+                MenuTree::Tup(trees)
+            }
+            // primitives of the same type
+            (_, _) => tree2.clone(),
+        })
     }
 
     pub fn auto_fill(typ: &MenuType, depth: usize) -> MenuTree {
@@ -562,7 +1514,7 @@ pub mod semantics {
         } else {
             match typ {
                 &MenuType::Prim(PrimType::Unit) => MenuTree::Unit,
-                &MenuType::Prim(PrimType::Nat) => MenuTree::Nat(0),
+                &MenuType::Prim(PrimType::Nat) => MenuTree::Nat(Nat::from(0u8)),
                 &MenuType::Prim(PrimType::Text) => MenuTree::Text("".to_string()),
                 &MenuType::Prim(PrimType::Bool) => MenuTree::Bool(false),
                 &MenuType::Variant(ref labtyps) => {
@@ -601,6 +1553,11 @@ pub mod semantics {
                         .collect();
                     MenuTree::Tup(menus)
                 }
+                &MenuType::Named(_, _) => match unfold(typ) {
+                    MenuType::Named(_, _) => MenuTree::Blank(typ.clone()),
+                    unfolded => auto_fill(&unfolded, depth),
+                },
+                &MenuType::Ref(_) => MenuTree::Blank(typ.clone()),
             }
         }
     }
@@ -608,23 +1565,117 @@ pub mod semantics {
 
 pub mod io {
     use super::{
-        semantics::{ctx_tag, tree_tag},
-        EditCommand, Label, MenuCtx, MenuState, MenuTree, Tag,
+        semantics::{ctx_tag, tree_tag, unfold},
+        EditCommand, Label, MenuCtx, MenuState, MenuTree, MenuType, PosSelect, PrimType, Tag,
+        TextEdit,
     };
     use render::Render;
-    use types::event::Event;
+    use types::event::{Event, Quit};
     use types::{
         lang::{Dir2D, Name},
         render::{Color, Dim, Elms, Fill},
     };
 
-    pub fn edit_commands_of_event(menu: &MenuState, event: &Event) -> Result<Vec<EditCommand>, ()> {
+    /// a key that enters text, rather than naming an action
+    fn is_printable(key: &str) -> bool {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => !c.is_control(),
+            _ => false,
+        }
+    }
+
+    fn text_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, Quit> {
+        match event {
+            Event::Quit => Err(Quit),
+            Event::KeyDown(ref kei) => match kei.key.as_str() {
+                "Escape" => Ok(vec![EditCommand::TextCancel]),
+                "Enter" | "Tab" => Ok(vec![EditCommand::TextCommit]),
+                "ArrowLeft" => Ok(vec![EditCommand::TextPrevChar]),
+                "ArrowRight" => Ok(vec![EditCommand::TextNextChar]),
+                "Home" | "ArrowUp" => Ok(vec![EditCommand::TextStart]),
+                "End" | "ArrowDown" => Ok(vec![EditCommand::TextEnd]),
+                "Backspace" => Ok(vec![EditCommand::TextDeleteBack]),
+                "Delete" => Ok(vec![EditCommand::TextDeleteForward]),
+                key if is_printable(key) => Ok(vec![EditCommand::TextInsert(key.to_string())]),
+                key => {
+                    warn!("unrecognized key while editing text: {:?}", key);
+                    Ok(vec![])
+                }
+            },
+            ev => {
+                warn!("unrecognized event: {:?}", ev);
+                Ok(vec![])
+            }
+        }
+    }
+
+    fn search_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, Quit> {
+        match event {
+            Event::Quit => Err(Quit),
+            Event::KeyDown(ref kei) => match kei.key.as_str() {
+                "Escape" => Ok(vec![EditCommand::SearchCancel]),
+                "Enter" => Ok(vec![EditCommand::SearchCommit]),
+                "ArrowDown" | "Tab" => Ok(vec![EditCommand::SearchNext]),
+                "ArrowUp" => Ok(vec![EditCommand::SearchPrev]),
+                "Backspace" => Ok(vec![EditCommand::SearchDeleteBack]),
+                key if is_printable(key) => Ok(vec![EditCommand::SearchInsert(key.to_string())]),
+                key => {
+                    warn!("unrecognized key while searching: {:?}", key);
+                    Ok(vec![])
+                }
+            },
+            ev => {
+                warn!("unrecognized event: {:?}", ev);
+                Ok(vec![])
+            }
+        }
+    }
+
+    pub fn edit_commands_of_event(
+        menu: &MenuState,
+        event: &Event,
+    ) -> Result<Vec<EditCommand>, Quit> {
+        if menu.text_edit.is_some() {
+            return text_commands_of_event(event);
+        };
+        if menu.search.is_some() {
+            return search_commands_of_event(event);
+        };
+        let typ = unfold(&menu.tree_typ);
+        let is_text = typ == MenuType::Prim(PrimType::Text);
+        let is_nat = typ == MenuType::Prim(PrimType::Nat);
+        let is_bool = typ == MenuType::Prim(PrimType::Bool);
         match (event, ctx_tag(&menu.ctx), tree_tag(&menu.tree)) {
-            (&Event::Quit { .. }, _, _) => Err(()),
+            (&Event::Quit { .. }, _, _) => Err(Quit),
             (&Event::KeyDown(ref kei), ref ctx, ref tree) => match (kei.key.as_str(), ctx, tree) {
-                ("Escape", _, _) => Err(()),
+                ("Escape", _, _) => Err(Quit),
+
+                ("c", _, _) if kei.ctrl => Ok(vec![EditCommand::Copy]),
+                ("x", _, _) if kei.ctrl => Ok(vec![EditCommand::Cut]),
+                ("v", _, _) if kei.ctrl => Ok(vec![EditCommand::Paste]),
+                ("V", _, _) if kei.ctrl => Ok(vec![EditCommand::PasteMerge]),
+
+                ("Backspace", _, Tag::Prim(PrimType::Nat)) => Ok(vec![EditCommand::NatPopDigit]),
                 ("Backspace", _, _) => Ok(vec![EditCommand::Clear]),
 
+                // (so, to enter text that begins with a slash, press Enter first)
+                ("/", _, _) => Ok(vec![EditCommand::SearchBegin]),
+
+                (key, _, _) if is_nat && key.len() == 1 && key.as_bytes()[0].is_ascii_digit() => {
+                    Ok(vec![EditCommand::NatPushDigit(key.as_bytes()[0] - b'0')])
+                }
+                ("+", _, _) | ("=", _, _) if is_nat => Ok(vec![EditCommand::NatIncrement]),
+                ("-", _, _) if is_nat => Ok(vec![EditCommand::NatDecrement]),
+
+                (" ", _, _) | ("Enter", _, _) if is_bool => Ok(vec![EditCommand::BoolToggle]),
+
+                ("Enter", _, _) if is_text => Ok(vec![EditCommand::TextBegin]),
+                (key, _, _) if is_text && is_printable(key) => Ok(vec![
+                    EditCommand::TextBegin,
+                    EditCommand::TextInsert(key.to_string()),
+                ]),
+
                 ("Tab", _, Tag::Blank) => Ok(vec![EditCommand::AutoFill]),
                 ("ArrowRight", _, Tag::Blank) => Ok(vec![EditCommand::AutoFill]),
 
@@ -637,6 +1688,20 @@ pub mod io {
                 ("ArrowUp", Tag::Product, _) => Ok(vec![EditCommand::PrevSibling]),
                 ("ArrowDown", Tag::Product, _) => Ok(vec![EditCommand::NextSibling]),
 
+                ("ArrowUp", Tag::Vec, _) | ("ArrowUp", Tag::Tup, _) => {
+                    Ok(vec![EditCommand::PrevSibling])
+                }
+                ("ArrowDown", Tag::Vec, _) | ("ArrowDown", Tag::Tup, _) => {
+                    Ok(vec![EditCommand::NextSibling])
+                }
+
+                ("Insert", _, _) => Ok(vec![EditCommand::VecInsertBlank]),
+                ("Delete", Tag::Vec, _) => Ok(vec![EditCommand::VecDelete]),
+                ("PageUp", Tag::Vec, _) => Ok(vec![EditCommand::VecMoveBackward]),
+                ("PageDown", Tag::Vec, _) => Ok(vec![EditCommand::VecMoveForward]),
+
+                (" ", _, Tag::Option) => Ok(vec![EditCommand::OptionToggle]),
+
                 ("Enter", _, _) => Ok(vec![EditCommand::Descend]),
 
                 (key, ctx, tree) => {
@@ -654,7 +1719,10 @@ pub mod io {
         }
     }
 
-    pub fn render_elms(menu: &MenuState) -> Result<Elms, String> {
+    pub fn render_elms(
+        menu: &MenuState,
+        clipboard: &Option<(MenuTree, MenuType)>,
+    ) -> Result<Elms, String> {
         use crate::render::{FlowAtts, FrameType, TextAtts};
 
         fn black_fill() -> Fill {
@@ -796,6 +1864,16 @@ pub mod io {
             }
         };
 
+        fn caret_atts() -> TextAtts {
+            TextAtts {
+                zoom: text_zoom(),
+                fg_fill: Fill::Closed(Color::RGB(0, 0, 0)),
+                bg_fill: Fill::Closed(Color::RGB(255, 255, 255)),
+                glyph_dim: glyph_dim(),
+                glyph_flow: glyph_flow(),
+            }
+        }
+
         fn render_choice_label(label: &Label, r: &mut Render) {
             r.str("#", &kw_atts());
             r.name(label, &text_atts());
@@ -819,6 +1897,47 @@ pub mod io {
             r.str("=", &kw_atts());
         }
 
+        fn render_vec_label(pos: usize, r: &mut Render) {
+            r.str("[", &kw_atts());
+            r.text(&format!("{}", pos), &text_atts());
+            r.str("]=", &kw_atts());
+        }
+
+        fn render_tup_label(pos: usize, r: &mut Render) {
+            r.str(".", &kw_atts());
+            r.text(&format!("{}", pos), &text_atts());
+            r.str("=", &kw_atts());
+        }
+
+        /// the positions of a vector or tuple context, around the focused tree
+        fn render_pos_ctx(
+            sel: &PosSelect,
+            render_label: fn(usize, &mut Render),
+            r: &mut Render,
+            r_tree: Render,
+        ) {
+            r.begin(&Name::Void, FrameType::Flow(vert_flow()));
+            for (i, (t, _ty)) in sel.before.iter().enumerate() {
+                begin_item(r);
+                render_label(i, r);
+                render_tree(t, false, &ctx_box_fill(), r);
+                r.end();
+            }
+            {
+                begin_item(r);
+                render_label(sel.before.len(), r);
+                r.nest(&Name::Void, r_tree);
+                r.end();
+            }
+            for (i, (t, _ty)) in sel.after.iter().enumerate() {
+                begin_item(r);
+                render_label(sel.before.len() + 1 + i, r);
+                render_tree(t, false, &ctx_box_fill(), r);
+                r.end();
+            }
+            r.end();
+        }
+
         fn begin_item(r: &mut Render) {
             r.begin(&Name::Void, FrameType::Flow(horz_flow()))
         }
@@ -875,9 +1994,21 @@ pub mod io {
                     r.nest(&Name::Void, r_tree);
                     r.end();
                 }
-                &MenuCtx::Option(_flag, ref _body) => unimplemented!(),
-                &MenuCtx::Vec(ref _ch) => unimplemented!(),
-                &MenuCtx::Tup(ref _ch) => unimplemented!(),
+                &MenuCtx::Option(_flag, ref ctx) => {
+                    next_ctx = Some((**ctx).clone());
+                    begin_item(&mut r);
+                    r.str("?", &kw_atts());
+                    r.nest(&Name::Void, r_tree);
+                    r.end();
+                }
+                &MenuCtx::Vec(ref sel) => {
+                    next_ctx = Some(sel.ctx.clone());
+                    render_pos_ctx(sel, render_vec_label, &mut r, r_tree);
+                }
+                &MenuCtx::Tup(ref sel) => {
+                    next_ctx = Some(sel.ctx.clone());
+                    render_pos_ctx(sel, render_tup_label, &mut r, r_tree);
+                }
             };
             r.end();
             // continue rendering the rest of the context, in whatever flow we are using for that purpose.
@@ -922,7 +2053,7 @@ pub mod io {
                         r.str("}", text);
                         r.end();
                     } else {
-                        unimplemented!()
+                        r.str("{#}", text)
                     }
                     r.end()
                 }
@@ -945,13 +2076,39 @@ pub mod io {
                         r.str("}", text);
                         r.end();
                     } else {
-                        unimplemented!()
+                        r.str("{}", text)
                     }
                     r.end()
                 }
-                MenuType::Option(_t) => unimplemented!(),
-                MenuType::Vec(_t) => unimplemented!(),
-                MenuType::Tup(_fields) => unimplemented!(),
+                MenuType::Option(t) => {
+                    begin_flow(r, hflow);
+                    r.str("?", text);
+                    render_type(t, text, vflow, hflow, r);
+                    r.end()
+                }
+                MenuType::Vec(t) => {
+                    begin_flow(r, hflow);
+                    r.str("[", text);
+                    render_type(t, text, vflow, hflow, r);
+                    r.str("]", text);
+                    r.end()
+                }
+                MenuType::Tup(typs) => {
+                    begin_flow(r, hflow);
+                    r.str("(", text);
+                    for t in typs.iter() {
+                        if first {
+                            first = false;
+                        } else {
+                            r.str(", ", text);
+                        };
+                        render_type(t, text, vflow, hflow, r);
+                    }
+                    r.str(")", text);
+                    r.end()
+                }
+                // show the name, rather than unfold its definition (without end)
+                MenuType::Named(name, _) | MenuType::Ref(name) => r.name(name, text),
             }
         }
 
@@ -1028,44 +2185,100 @@ pub mod io {
                 }
                 &MenuTree::Option(flag, ref tree, ref _typ) => {
                     if flag {
-                        r.str("?", &text_atts())
-                    };
-                    render_tree(&*tree, false, box_fill, r)
+                        r.str("?", &kw_atts());
+                        render_tree(&*tree, false, box_fill, r)
+                    } else {
+                        r.str("null", &text_atts());
+                        if show_detailed {
+                            r.str(" Toggle with Space", &msg_atts());
+                        }
+                    }
                 }
                 &MenuTree::Vec(ref trees, ref _typ) => {
-                    r.begin(&Name::Void, FrameType::Flow(horz_flow()));
-                    for tree in trees.iter() {
-                        render_tree(tree, false, box_fill, r)
+                    if trees.is_empty() {
+                        r.str("[]", &kw_atts());
+                        if show_detailed {
+                            r.str(" Insert an element with Insert", &msg_atts());
+                        }
+                    } else {
+                        r.begin(&Name::Void, FrameType::Flow(vert_flow()));
+                        for (i, tree) in trees.iter().enumerate() {
+                            begin_item(r);
+                            render_vec_label(i, r);
+                            render_tree(tree, false, box_fill, r);
+                            r.end()
+                        }
+                        r.end();
                     }
-                    r.end();
                 }
                 &MenuTree::Tup(ref trees) => {
-                    r.begin(&Name::Void, FrameType::Flow(horz_flow()));
-                    for (tree, _typ) in trees.iter() {
-                        render_tree(tree, false, box_fill, r)
+                    if trees.is_empty() {
+                        r.str("()", &text_atts());
+                    } else {
+                        r.begin(&Name::Void, FrameType::Flow(vert_flow()));
+                        for (i, (tree, _typ)) in trees.iter().enumerate() {
+                            begin_item(r);
+                            render_tup_label(i, r);
+                            render_tree(tree, false, box_fill, r);
+                            r.end()
+                        }
+                        r.end();
                     }
-                    r.end();
                 }
                 &MenuTree::Blank(ref _typ) => r.text(&format!("___"), &blank_atts()),
-                &MenuTree::Nat(n) => r.text(&format!("{}", n), &text_atts()),
+                &MenuTree::Nat(ref n) => r.text(&format!("{}", n), &text_atts()),
                 &MenuTree::Bool(b) => r.text(&format!("{}", b), &text_atts()),
                 &MenuTree::Text(ref t) => r.text(&format!("{:?}", t), &text_atts()),
                 &MenuTree::Unit => r.str("()", &text_atts()),
             };
             r.end();
         };
+        fn render_text_edit(edit: &TextEdit, box_fill: &Fill, r: &mut Render) {
+            // the caret highlights the character after it, or a space at the end.
+            let mut after = edit.after.chars();
+            let caret = after.next().unwrap_or(' ');
+            r.begin(&Name::Void, FrameType::Flow(horz_flow()));
+            r.fill(box_fill.clone());
+            r.text(&format!("\"{}", edit.before), &text_atts());
+            r.text(&caret.to_string(), &caret_atts());
+            r.text(&format!("{}\"", after.as_str()), &text_atts());
+            r.end();
+        }
         let mut r = Render::new();
         r.begin(&Name::Void, FrameType::Flow(vert_flow()));
         if true {
             r.str("hello world!", &text_atts());
             r.str(" please, enter a value to submit:", &msg_atts());
             r.str(" (Auto-fill and navigate with arrow keys)", &msg_atts());
+            match menu.search {
+                Some(ref search) => {
+                    begin_item(&mut r);
+                    r.str("/", &kw_atts());
+                    r.text(&search.query, &text_atts());
+                    r.str(" ", &caret_atts());
+                    r.str(" Next with Down; previous with Up", &msg_atts());
+                    r.end();
+                }
+                None => r.str(" (Search with /)", &msg_atts()),
+            };
+            if let Some((_, ref typ)) = *clipboard {
+                begin_item(&mut r);
+                r.str("Clipboard: ", &msg_atts());
+                render_type(typ, &typ_atts(), &typ_vflow(), &typ_hflow(), &mut r);
+                r.str(" (Paste with Ctrl-v)", &msg_atts());
+                r.end();
+            };
 
             let r_tree = {
                 let mut r_tree = Render::new();
                 r_tree.begin(&Name::Void, FrameType::Flow(vert_flow()));
                 r_tree.fill(active_cursor_fill());
-                render_tree(&menu.tree, true, &detailed_tree_box_fill(), &mut r_tree);
+                match menu.text_edit {
+                    Some(ref edit) => {
+                        render_text_edit(edit, &detailed_tree_box_fill(), &mut r_tree)
+                    }
+                    None => render_tree(&menu.tree, true, &detailed_tree_box_fill(), &mut r_tree),
+                };
                 render_type(
                     &menu.tree_typ,
                     &typ_atts(),
@@ -1153,6 +2366,850 @@ impl fmt::Display for MenuType {
                 }
                 write!(f, ")")
             }
+            MenuType::Named(name, _) | MenuType::Ref(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::semantics::{
+        auto_fill, check_tree, editor_eval, focus_path, tree_blanks, tree_typ, tree_union,
+        typ_equiv, typ_tag, unfold,
+    };
+    use super::{AutoCommand, Command, EditCommand, Editor, InitCommand, LabelChoice};
+    use super::{Error as MenuError, MenuState, Step};
+    use super::{MenuTree, MenuType, Nat, PrimType, Tag};
+    use types::lang::{Atom, Error, Name};
+
+    fn init(tree: MenuTree, typ: &MenuType) -> Command {
+        Command::Init(InitCommand::Default(tree, typ.clone()))
+    }
+
+    /// an editor of the tree
+    fn editor(tree: MenuTree, typ: &MenuType) -> Editor {
+        let mut ed = Editor {
+            state: None,
+            history: vec![],
+            clipboard: None,
+        };
+        editor_eval(&mut ed, &init(tree, typ)).unwrap();
+        ed
+    }
+
+    fn edit(ed: &mut Editor, command: EditCommand) -> Result<(), Error> {
+        editor_eval(ed, &Command::Edit(command))
+    }
+
+    fn state(ed: &Editor) -> &MenuState {
+        ed.state.as_ref().unwrap()
+    }
+
+    fn path(ed: &Editor) -> Vec<Step> {
+        focus_path(&state(ed).ctx)
+    }
+
+    /// the whole tree, and its type (leaving the focus at the root)
+    fn root(ed: &mut Editor) -> (MenuTree, MenuType) {
+        edit(ed, EditCommand::GotoRoot).unwrap();
+        (state(ed).tree.clone(), state(ed).tree_typ.clone())
+    }
+
+    fn text(s: &str) -> MenuTree {
+        MenuTree::Text(s.to_string())
+    }
+
+    /// the text before and after the caret
+    fn caret(ed: &Editor) -> (&str, &str) {
+        let edit = state(ed).text_edit.as_ref().unwrap();
+        (&edit.before, &edit.after)
+    }
+
+    #[test]
+    fn clipboard_survives_init() {
+        let nat = MenuType::Prim(PrimType::Nat);
+        let typ = MenuType::Product(vec![
+            (Name::Atom(Atom::String("a".to_string())), nat.clone()),
+            (Name::Atom(Atom::String("b".to_string())), nat.clone()),
+        ]);
+        let mut ed = Editor {
+            state: None,
+            history: vec![],
+            clipboard: None,
+        };
+        let five = MenuTree::Nat(Nat::from(5u8));
+        editor_eval(&mut ed, &init(five.clone(), &nat)).unwrap();
+        editor_eval(&mut ed, &Command::Edit(EditCommand::Copy)).unwrap();
+        assert_eq!(ed.clipboard, Some((five.clone(), nat.clone())));
+
+        // a new tree, whose first field takes the copied tree
+        editor_eval(&mut ed, &init(MenuTree::Blank(typ.clone()), &typ)).unwrap();
+        editor_eval(&mut ed, &Command::Edit(EditCommand::AutoFill)).unwrap();
+        editor_eval(&mut ed, &Command::Edit(EditCommand::Descend)).unwrap();
+        editor_eval(&mut ed, &Command::Edit(EditCommand::Paste)).unwrap();
+        assert_eq!(ed.state.as_ref().unwrap().tree, five);
+
+        // the clipboard's type must match the current tree's type
+        editor_eval(&mut ed, &Command::Edit(EditCommand::Ascend)).unwrap();
+        assert!(editor_eval(&mut ed, &Command::Edit(EditCommand::Paste)).is_err());
+    }
+
+    #[test]
+    fn text_begin_needs_text() {
+        let nat = MenuType::Prim(PrimType::Nat);
+        let a = Name::Atom(Atom::String("a".to_string()));
+        let typ = MenuType::Product(vec![(a.clone(), nat.clone())]);
+        let mut ed = editor(MenuTree::Blank(typ.clone()), &typ);
+        edit(&mut ed, EditCommand::AutoFill).unwrap();
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        assert_eq!(
+            edit(&mut ed, EditCommand::TextBegin),
+            Err(Error::Menu(MenuError::MenuTypeMismatch(
+                vec![Step::Field(a)],
+                MenuType::Prim(PrimType::Text),
+                nat
+            )))
+        );
+    }
+
+    #[test]
+    fn text_entry() {
+        let typ = MenuType::Prim(PrimType::Text);
+        let mut ed = editor(MenuTree::Blank(typ.clone()), &typ);
+        edit(&mut ed, EditCommand::TextBegin).unwrap();
+        assert_eq!(caret(&ed), ("", ""));
+        // at both ends of empty text, the caret cannot move, nor delete
+        for command in [EditCommand::TextPrevChar, EditCommand::TextDeleteBack] {
+            assert_eq!(edit(&mut ed, command), Err(Error::OutOfBounds(vec![0])));
+        }
+        for command in [EditCommand::TextNextChar, EditCommand::TextDeleteForward] {
+            assert_eq!(edit(&mut ed, command), Err(Error::OutOfBounds(vec![1])));
+        }
+
+        // the caret moves over whole (multi-byte) characters
+        edit(&mut ed, EditCommand::TextInsert("añ".to_string())).unwrap();
+        edit(&mut ed, EditCommand::TextInsert("€".to_string())).unwrap();
+        assert_eq!(caret(&ed), ("añ€", ""));
+        edit(&mut ed, EditCommand::TextPrevChar).unwrap();
+        edit(&mut ed, EditCommand::TextPrevChar).unwrap();
+        assert_eq!(caret(&ed), ("a", "ñ€"));
+        edit(&mut ed, EditCommand::TextNextChar).unwrap();
+        assert_eq!(caret(&ed), ("añ", "€"));
+        edit(&mut ed, EditCommand::TextInsert("b".to_string())).unwrap();
+        assert_eq!(caret(&ed), ("añb", "€"));
+        edit(&mut ed, EditCommand::TextStart).unwrap();
+        assert_eq!(caret(&ed), ("", "añb€"));
+        edit(&mut ed, EditCommand::TextEnd).unwrap();
+        assert_eq!(caret(&ed), ("añb€", ""));
+
+        // delete at the end, then at the start
+        edit(&mut ed, EditCommand::TextDeleteBack).unwrap();
+        assert_eq!(caret(&ed), ("añb", ""));
+        assert_eq!(
+            edit(&mut ed, EditCommand::TextDeleteForward),
+            Err(Error::OutOfBounds(vec![4]))
+        );
+        edit(&mut ed, EditCommand::TextStart).unwrap();
+        edit(&mut ed, EditCommand::TextDeleteForward).unwrap();
+        assert_eq!(caret(&ed), ("", "ñb"));
+        assert_eq!(
+            edit(&mut ed, EditCommand::TextDeleteBack),
+            Err(Error::OutOfBounds(vec![0]))
+        );
+        edit(&mut ed, EditCommand::TextNextChar).unwrap();
+        edit(&mut ed, EditCommand::TextDeleteBack).unwrap();
+        assert_eq!(caret(&ed), ("", "b"));
+
+        // other commands wait for the text entry to end
+        assert_eq!(
+            edit(&mut ed, EditCommand::Ascend),
+            Err(Error::Invalid("text entry in progress".to_string()))
+        );
+        assert_eq!(caret(&ed), ("", "b"));
+
+        // commit the text, around the caret
+        edit(&mut ed, EditCommand::TextInsert("a".to_string())).unwrap();
+        edit(&mut ed, EditCommand::TextCommit).unwrap();
+        assert!(state(&ed).text_edit.is_none());
+        assert_eq!(state(&ed).tree, text("ab"));
+
+        // resume at the end of the committed text, then cancel the changes
+        edit(&mut ed, EditCommand::TextBegin).unwrap();
+        assert_eq!(caret(&ed), ("ab", ""));
+        edit(&mut ed, EditCommand::TextDeleteBack).unwrap();
+        edit(&mut ed, EditCommand::TextCancel).unwrap();
+        assert!(state(&ed).text_edit.is_none());
+        assert_eq!(state(&ed).tree, text("ab"));
+        assert_eq!(
+            edit(&mut ed, EditCommand::TextCommit),
+            Err(Error::Invalid("no text entry in progress".to_string()))
+        );
+    }
+
+    fn nat(n: u32) -> MenuTree {
+        MenuTree::Nat(Nat::from(n))
+    }
+
+    #[test]
+    fn nat_and_bool_entry() {
+        let typ = MenuType::Prim(PrimType::Nat);
+        let mut ed = editor(MenuTree::Blank(typ.clone()), &typ);
+        // a blank counts as zero, which has no predecessor
+        assert_eq!(
+            edit(&mut ed, EditCommand::NatDecrement),
+            Err(Error::Invalid("cannot decrement zero".to_string()))
+        );
+        assert_eq!(state(&ed).tree, MenuTree::Blank(typ.clone()));
+        edit(&mut ed, EditCommand::NatPushDigit(4)).unwrap();
+        edit(&mut ed, EditCommand::NatPushDigit(2)).unwrap();
+        assert_eq!(state(&ed).tree, nat(42));
+        assert_eq!(
+            edit(&mut ed, EditCommand::NatPushDigit(10)),
+            Err(Error::Invalid("not a decimal digit: 10".to_string()))
+        );
+        edit(&mut ed, EditCommand::NatIncrement).unwrap();
+        assert_eq!(state(&ed).tree, nat(43));
+        edit(&mut ed, EditCommand::NatPopDigit).unwrap();
+        assert_eq!(state(&ed).tree, nat(4));
+        edit(&mut ed, EditCommand::NatDecrement).unwrap();
+        assert_eq!(state(&ed).tree, nat(3));
+        // popping the last digit leaves a blank
+        edit(&mut ed, EditCommand::NatPopDigit).unwrap();
+        assert_eq!(state(&ed).tree, MenuTree::Blank(typ.clone()));
+        edit(&mut ed, EditCommand::NatIncrement).unwrap();
+        edit(&mut ed, EditCommand::NatDecrement).unwrap();
+        assert_eq!(state(&ed).tree, nat(0));
+        assert_eq!(
+            edit(&mut ed, EditCommand::NatDecrement),
+            Err(Error::Invalid("cannot decrement zero".to_string()))
+        );
+        assert_eq!(state(&ed).tree, nat(0));
+        assert!(edit(&mut ed, EditCommand::BoolToggle).is_err());
+
+        let typ = MenuType::Prim(PrimType::Bool);
+        let mut ed = editor(MenuTree::Blank(typ.clone()), &typ);
+        // a blank counts as false
+        edit(&mut ed, EditCommand::BoolToggle).unwrap();
+        assert_eq!(state(&ed).tree, MenuTree::Bool(true));
+        edit(&mut ed, EditCommand::BoolToggle).unwrap();
+        assert_eq!(state(&ed).tree, MenuTree::Bool(false));
+        assert!(edit(&mut ed, EditCommand::NatIncrement).is_err());
+        assert_eq!(state(&ed).tree, MenuTree::Bool(false));
+    }
+
+    fn nats(ns: &[u32]) -> MenuTree {
+        MenuTree::Vec(
+            ns.iter().map(|n| nat(*n)).collect(),
+            MenuType::Prim(PrimType::Nat),
+        )
+    }
+
+    #[test]
+    fn vec_insert_into_nested_vectors() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let inner_t = MenuType::Vec(Box::new(nat_t.clone()));
+        let typ = MenuType::Vec(Box::new(inner_t.clone()));
+        let empty = MenuTree::Vec(vec![], nat_t.clone());
+        let mut ed = editor(MenuTree::Vec(vec![empty.clone()], inner_t.clone()), &typ);
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        // the empty vector has no element to descend to, so insert one into it
+        assert!(edit(&mut ed, EditCommand::Descend).is_err());
+        edit(&mut ed, EditCommand::VecInsertBlank).unwrap();
+        assert_eq!(path(&ed), vec![Step::Pos(0), Step::Pos(0)]);
+        // on an element that is not a vector, insert a sibling after it
+        edit(&mut ed, EditCommand::NatPushDigit(7)).unwrap();
+        edit(&mut ed, EditCommand::VecInsertBlank).unwrap();
+        assert_eq!(path(&ed), vec![Step::Pos(0), Step::Pos(1)]);
+        let blank = MenuTree::Blank(nat_t.clone());
+        assert_eq!(
+            root(&mut ed),
+            (
+                MenuTree::Vec(vec![MenuTree::Vec(vec![nat(7), blank], nat_t)], inner_t),
+                typ
+            )
+        );
+        let text_t = MenuType::Prim(PrimType::Text);
+        let mut ed = editor(text("a"), &text_t);
+        assert!(edit(&mut ed, EditCommand::VecInsertBlank).is_err());
+    }
+
+    #[test]
+    fn vec_delete_and_move() {
+        let typ = MenuType::Vec(Box::new(MenuType::Prim(PrimType::Nat)));
+        let mut ed = editor(nats(&[1, 2, 3]), &typ);
+        assert!(edit(&mut ed, EditCommand::VecDelete).is_err());
+        edit(&mut ed, EditCommand::Descend).unwrap();
+
+        // move the first element forward, to the end, and no further
+        assert_eq!(
+            edit(&mut ed, EditCommand::VecMoveBackward),
+            Err(Error::OutOfBounds(vec![0]))
+        );
+        edit(&mut ed, EditCommand::VecMoveForward).unwrap();
+        edit(&mut ed, EditCommand::VecMoveForward).unwrap();
+        assert_eq!(path(&ed), vec![Step::Pos(2)]);
+        assert_eq!(state(&ed).tree, nat(1));
+        assert_eq!(
+            edit(&mut ed, EditCommand::VecMoveForward),
+            Err(Error::OutOfBounds(vec![3]))
+        );
+        edit(&mut ed, EditCommand::VecMoveBackward).unwrap();
+        assert_eq!(root(&mut ed).0, nats(&[2, 1, 3]));
+
+        // delete the middle element, then the last, focusing on the next (or else previous)
+        edit(&mut ed, EditCommand::GotoPath(vec![Step::Pos(1)])).unwrap();
+        edit(&mut ed, EditCommand::VecDelete).unwrap();
+        assert_eq!((path(&ed), &state(&ed).tree), (vec![Step::Pos(1)], &nat(3)));
+        edit(&mut ed, EditCommand::VecDelete).unwrap();
+        assert_eq!((path(&ed), &state(&ed).tree), (vec![Step::Pos(0)], &nat(2)));
+        // deleting the only element focuses on the empty vector
+        edit(&mut ed, EditCommand::VecDelete).unwrap();
+        assert_eq!((path(&ed), &state(&ed).tree), (vec![], &nats(&[])));
+        assert_eq!(state(&ed).tree_typ, typ);
+    }
+
+    #[test]
+    fn siblings_wrap_around() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let typ = MenuType::Vec(Box::new(nat_t.clone()));
+        let mut ed = editor(nats(&[1, 2, 3]), &typ);
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        edit(&mut ed, EditCommand::PrevSibling).unwrap();
+        assert_eq!((path(&ed), &state(&ed).tree), (vec![Step::Pos(2)], &nat(3)));
+        edit(&mut ed, EditCommand::NextSibling).unwrap();
+        assert_eq!((path(&ed), &state(&ed).tree), (vec![Step::Pos(0)], &nat(1)));
+        edit(&mut ed, EditCommand::NextSibling).unwrap();
+        assert_eq!((path(&ed), &state(&ed).tree), (vec![Step::Pos(1)], &nat(2)));
+        assert_eq!(root(&mut ed).0, nats(&[1, 2, 3]));
+
+        let text_t = MenuType::Prim(PrimType::Text);
+        let typ = MenuType::Tup(vec![nat_t.clone(), text_t.clone()]);
+        let tup = MenuTree::Tup(vec![(nat(1), nat_t.clone()), (text("a"), text_t.clone())]);
+        let mut ed = editor(tup.clone(), &typ);
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        edit(&mut ed, EditCommand::NextSibling).unwrap();
+        assert_eq!(path(&ed), vec![Step::Pos(1)]);
+        assert_eq!(
+            (&state(&ed).tree, &state(&ed).tree_typ),
+            (&text("a"), &text_t)
+        );
+        edit(&mut ed, EditCommand::NextSibling).unwrap();
+        assert_eq!(path(&ed), vec![Step::Pos(0)]);
+        assert_eq!((&state(&ed).tree, &state(&ed).tree_typ), (&nat(1), &nat_t));
+        // a tuple is not a vector
+        assert!(edit(&mut ed, EditCommand::VecDelete).is_err());
+        assert!(edit(&mut ed, EditCommand::VecMoveForward).is_err());
+        edit(&mut ed, EditCommand::Ascend).unwrap();
+        assert_eq!(
+            (state(&ed).tree.clone(), state(&ed).tree_typ.clone()),
+            (tup, typ)
+        );
+    }
+
+    #[test]
+    fn option_ascend_and_descend() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let typ = MenuType::Option(Box::new(nat_t.clone()));
+        let none = MenuTree::Option(
+            false,
+            Box::new(MenuTree::Blank(nat_t.clone())),
+            nat_t.clone(),
+        );
+        let mut ed = editor(none, &typ);
+        assert_eq!(
+            edit(&mut ed, EditCommand::Descend),
+            Err(Error::Invalid("option is none".to_string()))
+        );
+        edit(&mut ed, EditCommand::OptionToggle).unwrap();
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        assert_eq!(path(&ed), vec![Step::Some]);
+        assert_eq!(state(&ed).tree_typ, nat_t);
+        edit(&mut ed, EditCommand::NatPushDigit(5)).unwrap();
+        edit(&mut ed, EditCommand::Ascend).unwrap();
+        assert_eq!(path(&ed), vec![]);
+        assert_eq!(
+            (state(&ed).tree.clone(), state(&ed).tree_typ.clone()),
+            (MenuTree::Option(true, Box::new(nat(5)), nat_t), typ)
+        );
+        assert!(edit(&mut ed, EditCommand::Ascend).is_err());
+    }
+
+    fn label(s: &str) -> Name {
+        Name::Atom(Atom::String(s.to_string()))
+    }
+
+    #[test]
+    fn check_trees() {
+        let (nat_t, text_t) = (
+            MenuType::Prim(PrimType::Nat),
+            MenuType::Prim(PrimType::Text),
+        );
+        let texts_t = MenuType::Vec(Box::new(text_t.clone()));
+        let typ = MenuType::Product(vec![
+            (label("a"), nat_t.clone()),
+            (label("b"), texts_t.clone()),
+        ]);
+        let tree = |b: Vec<MenuTree>| {
+            MenuTree::Product(vec![
+                (label("a"), nat(1), nat_t.clone()),
+                (
+                    label("b"),
+                    MenuTree::Vec(b, text_t.clone()),
+                    texts_t.clone(),
+                ),
+            ])
+        };
+        let mut path = vec![Step::Some];
+        assert_eq!(check_tree(&tree(vec![text("x")]), &typ, &mut path), Ok(()));
+        assert_eq!(path, vec![Step::Some]);
+        // a blank of the right type checks
+        let blank = MenuTree::Blank(text_t.clone());
+        assert_eq!(check_tree(&tree(vec![blank]), &typ, &mut vec![]), Ok(()));
+        // a subtree whose type differs from the one its parent gives it
+        assert_eq!(
+            check_tree(&tree(vec![text("x"), nat(2)]), &typ, &mut vec![]),
+            Err(MenuError::MenuTypeMismatch(
+                vec![Step::Field(label("b")), Step::Pos(1)],
+                text_t.clone(),
+                nat_t.clone()
+            ))
+        );
+        assert_eq!(
+            check_tree(&nat(1), &text_t, &mut vec![Step::Pos(3)]),
+            Err(MenuError::MenuTypeMismatch(
+                vec![Step::Pos(3)],
+                text_t,
+                nat_t
+            ))
+        );
+    }
+
+    #[test]
+    fn blanks() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let opt_t = MenuType::Option(Box::new(nat_t.clone()));
+        let var_t = MenuType::Variant(vec![(label("x"), nat_t.clone())]);
+        let blank = || MenuTree::Blank(nat_t.clone());
+        let unchosen = MenuTree::Variant(Box::new(LabelChoice {
+            before: vec![(label("x"), nat(0), nat_t.clone())],
+            choice: None,
+            after: vec![],
+        }));
+        let typ = MenuType::Tup(vec![
+            nat_t.clone(),
+            opt_t.clone(),
+            opt_t.clone(),
+            var_t.clone(),
+        ]);
+        let tree = MenuTree::Tup(vec![
+            (blank(), nat_t.clone()),
+            // an absent option has no blanks, but a present one may
+            (
+                MenuTree::Option(false, Box::new(blank()), nat_t.clone()),
+                opt_t.clone(),
+            ),
+            (
+                MenuTree::Option(true, Box::new(blank()), nat_t.clone()),
+                opt_t,
+            ),
+            // a variant without a choice is a blank, even if its arms are not
+            (unchosen, var_t.clone()),
+        ]);
+        let mut found = vec![];
+        tree_blanks(&tree, &mut vec![], &mut found);
+        assert_eq!(
+            found,
+            vec![
+                (vec![Step::Pos(0)], nat_t.clone()),
+                (vec![Step::Pos(2), Step::Some], nat_t.clone()),
+                (vec![Step::Pos(3)], var_t),
+            ]
+        );
+
+        // checks of the whole tree, from a focused subtree
+        let mut ed = editor(tree, &typ);
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        let auto = |ed: &mut Editor, c| editor_eval(ed, &Command::Auto(c));
+        assert_eq!(auto(&mut ed, AutoCommand::CheckMenuType), Ok(()));
+        assert_eq!(
+            auto(&mut ed, AutoCommand::CheckComplete),
+            Err(Error::Menu(MenuError::Blanks(found)))
+        );
+        assert_eq!(path(&ed), vec![Step::Pos(0)]);
+        edit(&mut ed, EditCommand::NatPushDigit(1)).unwrap();
+        edit(
+            &mut ed,
+            EditCommand::GotoPath(vec![Step::Pos(2), Step::Some]),
+        )
+        .unwrap();
+        edit(&mut ed, EditCommand::NatPushDigit(2)).unwrap();
+        edit(&mut ed, EditCommand::GotoPath(vec![Step::Pos(3)])).unwrap();
+        edit(&mut ed, EditCommand::PrevVariant).unwrap();
+        assert_eq!(auto(&mut ed, AutoCommand::CheckComplete), Ok(()));
+    }
+
+    #[test]
+    fn replace_checks_at_the_focus() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let typ = MenuType::Product(vec![(label("a"), nat_t.clone())]);
+        let tree = MenuTree::Product(vec![(label("a"), nat(1), nat_t.clone())]);
+        let mut ed = editor(tree, &typ);
+        edit(&mut ed, EditCommand::Descend).unwrap();
+        let replace =
+            |ed: &mut Editor, tree| editor_eval(ed, &Command::Auto(AutoCommand::Replace(tree)));
+        assert_eq!(
+            replace(&mut ed, text("x")),
+            Err(Error::Menu(MenuError::MenuTypeMismatch(
+                vec![Step::Field(label("a"))],
+                nat_t,
+                MenuType::Prim(PrimType::Text)
+            )))
+        );
+        assert_eq!(state(&ed).tree, nat(1));
+        replace(&mut ed, nat(2)).unwrap();
+        assert_eq!(state(&ed).tree, nat(2));
+    }
+
+    #[test]
+    fn union_of_variants() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let blank = || MenuTree::Blank(nat_t.clone());
+        // arms x and y, where `choice` is the index of the chosen one, if any
+        let variant = |x: MenuTree, y: MenuTree, choice: Option<usize>| {
+            let mut arms = vec![
+                (label("x"), x, nat_t.clone()),
+                (label("y"), y, nat_t.clone()),
+            ];
+            let (before, choice, after) = match choice {
+                None => (arms, None, vec![]),
+                Some(i) => {
+                    let after = arms.split_off(i + 1);
+                    let choice = arms.pop();
+                    (arms, choice, after)
+                }
+            };
+            MenuTree::Variant(Box::new(LabelChoice {
+                before,
+                choice,
+                after,
+            }))
+        };
+        let union = |t1: &MenuTree, t2: &MenuTree| tree_union(t1, t2, &mut vec![]).unwrap();
+        // the arms of both sides fill each other's blanks, whichever side chose
+        let (t1, t2) = (
+            variant(nat(1), blank(), Some(0)),
+            variant(blank(), nat(2), None),
+        );
+        assert_eq!(union(&t1, &t2), variant(nat(1), nat(2), Some(0)));
+        assert_eq!(union(&t2, &t1), variant(nat(1), nat(2), Some(0)));
+        let t1 = variant(nat(1), blank(), None);
+        let t2 = variant(blank(), nat(2), Some(1));
+        assert_eq!(union(&t1, &t2), variant(nat(1), nat(2), Some(1)));
+        // where both sides chose, the second wins
+        let t1 = variant(nat(1), nat(3), Some(0));
+        assert_eq!(union(&t1, &t2), variant(nat(1), nat(2), Some(1)));
+        assert_eq!(union(&t2, &t1), variant(nat(1), nat(3), Some(0)));
+        assert_eq!(
+            union(
+                &variant(blank(), blank(), None),
+                &variant(blank(), blank(), None)
+            ),
+            variant(blank(), blank(), None)
+        );
+    }
+
+    #[test]
+    fn union_of_options_vectors_and_tuples() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        let blank = || MenuTree::Blank(nat_t.clone());
+        let union = |t1: &MenuTree, t2: &MenuTree| tree_union(t1, t2, &mut vec![]).unwrap();
+        let option = |flag, t| MenuTree::Option(flag, Box::new(t), nat_t.clone());
+        // the second flag wins, while the content merges
+        assert_eq!(
+            union(&option(true, nat(1)), &option(false, blank())),
+            option(false, nat(1))
+        );
+        assert_eq!(
+            union(&option(false, blank()), &option(true, nat(2))),
+            option(true, nat(2))
+        );
+        assert_eq!(
+            union(&option(true, nat(1)), &option(true, nat(2))),
+            option(true, nat(2))
+        );
+
+        // leading elements merge, and the longer vector gives the rest
+        let vec = |trees| MenuTree::Vec(trees, nat_t.clone());
+        let t1 = vec(vec![nat(1), blank(), nat(3), nat(4)]);
+        let t2 = vec(vec![blank(), nat(2)]);
+        assert_eq!(union(&t1, &t2), nats(&[1, 2, 3, 4]));
+        assert_eq!(union(&t2, &t1), nats(&[1, 2, 3, 4]));
+        assert_eq!(union(&nats(&[1, 2]), &nats(&[5])), nats(&[5, 2]));
+        assert_eq!(union(&nats(&[]), &nats(&[5])), nats(&[5]));
+
+        let tup = |a, b| MenuTree::Tup(vec![(a, nat_t.clone()), (b, nat_t.clone())]);
+        assert_eq!(
+            union(&tup(nat(1), blank()), &tup(blank(), nat(2))),
+            tup(nat(1), nat(2))
+        );
+        assert_eq!(
+            union(&tup(nat(1), nat(3)), &tup(nat(5), blank())),
+            tup(nat(5), nat(3))
+        );
+        // a blank takes the whole of the other tree
+        let t = tup(nat(1), blank());
+        assert_eq!(union(&MenuTree::Blank(tree_typ(&t)), &t), t);
+        assert_eq!(union(&t, &MenuTree::Blank(tree_typ(&t))), t);
+    }
+
+    #[test]
+    fn union_conflicts() {
+        let (nat_t, text_t) = (
+            MenuType::Prim(PrimType::Nat),
+            MenuType::Prim(PrimType::Text),
+        );
+        let mut path = vec![Step::Pos(2)];
+        assert_eq!(
+            tree_union(&nats(&[1]), &nats(&[2, 3]), &mut path),
+            Ok(nats(&[2, 3]))
+        );
+        assert_eq!(path, vec![Step::Pos(2)]);
+        assert_eq!(
+            tree_union(&nat(1), &text("x"), &mut path),
+            Err(MenuError::Conflict(
+                vec![Step::Pos(2)],
+                nat_t.clone(),
+                text_t.clone()
+            ))
+        );
+        // trees that claim the same type, but disagree within
+        let t1 = MenuTree::Product(vec![(label("a"), nat(1), nat_t.clone())]);
+        let t2 = MenuTree::Product(vec![(label("a"), text("x"), nat_t.clone())]);
+        assert_eq!(
+            tree_union(&t1, &t2, &mut vec![]),
+            Err(MenuError::Conflict(
+                vec![Step::Field(label("a"))],
+                nat_t,
+                text_t
+            ))
+        );
+    }
+
+    fn named(name: &str, def: MenuType) -> MenuType {
+        MenuType::Named(label(name), Box::new(def))
+    }
+
+    fn reference(name: &str) -> MenuType {
+        MenuType::Ref(label(name))
+    }
+
+    #[test]
+    fn unfold_named_types() {
+        let nat_t = MenuType::Prim(PrimType::Nat);
+        assert_eq!(unfold(&nat_t), nat_t);
+        // type L = vec L
+        let l = named("L", MenuType::Vec(Box::new(reference("L"))));
+        assert_eq!(unfold(&l), MenuType::Vec(Box::new(l.clone())));
+        // type T = (type T = nat), where the inner definition is the one that counts
+        assert_eq!(unfold(&named("T", named("T", nat_t.clone()))), nat_t);
+        // an inner definition shadows the outer name within it, but not beside it
+        let inner = named("T", MenuType::Option(Box::new(reference("T"))));
+        let t = named(
+            "T",
+            MenuType::Product(vec![
+                (label("a"), inner.clone()),
+                (label("b"), reference("T")),
+            ]),
+        );
+        assert_eq!(
+            unfold(&t),
+            MenuType::Product(vec![(label("a"), inner), (label("b"), t.clone())])
+        );
+        // mutually recursive: type A = opt B; type B = vec A
+        let a = named(
+            "A",
+            MenuType::Option(Box::new(named(
+                "B",
+                MenuType::Vec(Box::new(reference("A"))),
+            ))),
+        );
+        let b = named("B", MenuType::Vec(Box::new(a.clone())));
+        assert_eq!(unfold(&a), MenuType::Option(Box::new(b.clone())));
+        assert_eq!(unfold(&b), MenuType::Vec(Box::new(a)));
+    }
+
+    #[test]
+    fn unfold_stops_at_cycles() {
+        // type T = T
+        let t = named("T", reference("T"));
+        assert_eq!(unfold(&t), t);
+        // type T = U; type U = T
+        let t = named("T", named("U", reference("T")));
+        assert_eq!(unfold(&t), t);
+        let u = named("U", named("T", named("U", reference("T"))));
+        assert!(matches!(unfold(&u), MenuType::Named(_, _)));
+        // no tree has such a type
+        assert_eq!(typ_tag(&t), Tag::Blank);
+        assert_eq!(auto_fill(&t, 3), MenuTree::Blank(t.clone()));
+        assert!(typ_equiv(&t, &t));
+        assert!(!typ_equiv(&t, &u));
+        assert!(!typ_equiv(&t, &MenuType::Prim(PrimType::Nat)));
+    }
+
+    #[test]
+    fn equivalent_types() {
+        let (nat_t, text_t) = (
+            MenuType::Prim(PrimType::Nat),
+            MenuType::Prim(PrimType::Text),
+        );
+        // type L = opt (elem, L), under any name
+        let list = |name: &str, elem: &MenuType| {
+            named(
+                name,
+                MenuType::Option(Box::new(MenuType::Tup(vec![elem.clone(), reference(name)]))),
+            )
+        };
+        assert!(typ_equiv(&list("L", &nat_t), &list("M", &nat_t)));
+        assert!(!typ_equiv(&list("L", &nat_t), &list("L", &text_t)));
+        // the same, unrolled once
+        let unrolled = MenuType::Option(Box::new(MenuType::Tup(vec![
+            nat_t.clone(),
+            list("M", &nat_t),
+        ])));
+        assert!(typ_equiv(&list("L", &nat_t), &unrolled));
+        assert!(typ_equiv(&unrolled, &list("L", &nat_t)));
+
+        // mutually recursive: type A = opt B; type B = opt A, which is type C = opt C
+        let opt = |t| MenuType::Option(Box::new(t));
+        let a = named("A", opt(named("B", opt(reference("A")))));
+        let c = named("C", opt(reference("C")));
+        assert!(typ_equiv(&a, &c));
+        assert!(typ_equiv(&c, &unfold(&a)));
+        // type A = opt B; type B = vec A, which is type C = opt (vec C), but not opt (vec (opt C))
+        let a = named(
+            "A",
+            opt(named("B", MenuType::Vec(Box::new(reference("A"))))),
+        );
+        let c = named("C", opt(MenuType::Vec(Box::new(reference("C")))));
+        assert!(typ_equiv(&a, &c));
+        let c = named("C", opt(MenuType::Vec(Box::new(opt(reference("C"))))));
+        assert!(!typ_equiv(&a, &c));
+        // field labels count, too
+        let product = |l: &str| MenuType::Product(vec![(label(l), nat_t.clone())]);
+        assert!(!typ_equiv(&product("a"), &product("b")));
+    }
+
+    /// a record whose values `Bob`, `Tom` and `joe` (but no labels) contain `o`
+    fn people() -> Editor {
+        let (nat_t, text_t) = (
+            MenuType::Prim(PrimType::Nat),
+            MenuType::Prim(PrimType::Text),
+        );
+        let texts_t = MenuType::Vec(Box::new(text_t.clone()));
+        let tree = MenuTree::Product(vec![
+            (label("first"), text("Bob"), text_t.clone()),
+            (
+                label("list"),
+                MenuTree::Vec(vec![text("Tom"), text("ann")], text_t.clone()),
+                texts_t,
+            ),
+            (label("n"), nat(10), nat_t),
+            (label("last"), text("joe"), text_t),
+        ]);
+        let typ = tree_typ(&tree);
+        editor(tree, &typ)
+    }
+
+    #[test]
+    fn search_wraps_around() {
+        let (first, last) = (Step::Field(label("first")), Step::Field(label("last")));
+        let tom = vec![Step::Field(label("list")), Step::Pos(0)];
+        let mut ed = people();
+        edit(&mut ed, EditCommand::SearchBegin).unwrap();
+        // the first match, from the focus itself
+        edit(&mut ed, EditCommand::SearchInsert("o".to_string())).unwrap();
+        assert_eq!(path(&ed), vec![first.clone()]);
+        edit(&mut ed, EditCommand::SearchNext).unwrap();
+        assert_eq!(path(&ed), tom);
+        edit(&mut ed, EditCommand::SearchNext).unwrap();
+        assert_eq!(path(&ed), vec![last.clone()]);
+        edit(&mut ed, EditCommand::SearchNext).unwrap();
+        assert_eq!(path(&ed), vec![first.clone()]);
+        edit(&mut ed, EditCommand::SearchPrev).unwrap();
+        assert_eq!(path(&ed), vec![last.clone()]);
+        edit(&mut ed, EditCommand::SearchPrev).unwrap();
+        assert_eq!(path(&ed), tom);
+        edit(&mut ed, EditCommand::SearchCommit).unwrap();
+        assert!(state(&ed).search.is_none());
+        assert_eq!(path(&ed), tom);
+
+        // labels match too, ignoring case
+        edit(&mut ed, EditCommand::SearchBegin).unwrap();
+        edit(&mut ed, EditCommand::SearchInsert("LIS".to_string())).unwrap();
+        assert_eq!(path(&ed), vec![Step::Field(label("list"))]);
+    }
+
+    #[test]
+    fn search_delete_back_and_cancel() {
+        let n = vec![Step::Field(label("n"))];
+        let mut ed = people();
+        edit(&mut ed, EditCommand::GotoPath(n.clone())).unwrap();
+        edit(&mut ed, EditCommand::SearchBegin).unwrap();
+        edit(&mut ed, EditCommand::SearchInsert("o".to_string())).unwrap();
+        assert_eq!(path(&ed), vec![Step::Field(label("last"))]);
+        edit(&mut ed, EditCommand::SearchNext).unwrap();
+        assert_eq!(path(&ed), vec![Step::Field(label("first"))]);
+        edit(&mut ed, EditCommand::SearchNext).unwrap();
+        edit(&mut ed, EditCommand::SearchInsert("m".to_string())).unwrap();
+        assert_eq!(path(&ed), vec![Step::Field(label("list")), Step::Pos(0)]);
+        // the shorter query searches again from the origin, not from the last match
+        edit(&mut ed, EditCommand::SearchDeleteBack).unwrap();
+        assert_eq!(path(&ed), vec![Step::Field(label("last"))]);
+        edit(&mut ed, EditCommand::SearchDeleteBack).unwrap();
+        assert_eq!(path(&ed), n);
+        assert!(matches!(
+            edit(&mut ed, EditCommand::SearchDeleteBack),
+            Err(Error::OutOfBounds(_))
+        ));
+        // without a match, or with another command, the focus stays and the search goes on
+        edit(&mut ed, EditCommand::SearchInsert("b".to_string())).unwrap();
+        assert_eq!(path(&ed), vec![Step::Field(label("first"))]);
+        assert!(matches!(
+            edit(&mut ed, EditCommand::SearchInsert("zz".to_string())),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            edit(&mut ed, EditCommand::Ascend),
+            Err(Error::Invalid(_))
+        ));
+        assert_eq!(path(&ed), vec![Step::Field(label("first"))]);
+        edit(&mut ed, EditCommand::SearchCancel).unwrap();
+        assert!(state(&ed).search.is_none());
+        assert_eq!(path(&ed), n);
+        assert!(matches!(
+            edit(&mut ed, EditCommand::SearchNext),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn goto_paths() {
+        let mut ed = people();
+        let ann = vec![Step::Field(label("list")), Step::Pos(1)];
+        edit(&mut ed, EditCommand::GotoPath(ann.clone())).unwrap();
+        assert_eq!(path(&ed), ann);
+        assert_eq!(state(&ed).tree, text("ann"));
+        edit(&mut ed, EditCommand::GotoPath(vec![])).unwrap();
+        assert_eq!(path(&ed), vec![]);
+        // a path to no subtree leaves the focus put
+        edit(&mut ed, EditCommand::GotoPath(ann.clone())).unwrap();
+        for missing in [
+            vec![Step::Field(label("list")), Step::Pos(2)],
+            vec![Step::Field(label("none"))],
+            vec![Step::Field(label("n")), Step::Some],
+        ] {
+            assert!(edit(&mut ed, EditCommand::GotoPath(missing)).is_err());
+            assert_eq!(path(&ed), ann);
         }
     }
 }
@@ -4,6 +4,8 @@
 extern crate log;
 
 extern crate hashcons;
+extern crate num_bigint;
+extern crate num_traits;
 extern crate serde;
 extern crate serde_bytes;
 
@@ -11,17 +13,21 @@ extern crate serde_bytes;
 
 pub mod eval;
 pub mod init;
+pub mod syntax;
 pub mod types;
 
 // to do: complete menu module
 pub mod menu;
 
+pub mod candid;
+pub mod http;
+
 pub mod bitmap;
+pub mod pixmap;
 
 // to do: complete adapton module:
 pub mod adapton;
 
-// to do: complete chain and grid modules:
 pub mod chain;
 pub mod grid;
 
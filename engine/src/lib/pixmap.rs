@@ -0,0 +1,507 @@
+// Serde: Persistent state between invocations of ZQM
+use serde::{Deserialize, Serialize};
+
+use bitmap::Bitmap;
+use types::lang::{Dir1D, Dir2D, Nat};
+use types::render::Color;
+
+/// the most colors that a palette holds (so that a hex digit indexes each one)
+pub const MAX_COLORS: usize = 16;
+
+// Step 1:
+// -------
+// Define the structure, in terms of "simplified, affine Rust"
+// (no references or lifetimes; everything is affine, so no Rc<_>s either.)
+
+/// a grid of pixels, each an index into a small palette of colors
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
+pub struct Pixmap {
+    pub width: Nat,
+    pub height: Nat,
+    /// at most `MAX_COLORS` colors, indexed by the pixels
+    pub palette: Vec<Color>,
+    /// row major ordering (rows indexed first, then columns)
+    pub pixels: Vec<Vec<Nat>>,
+}
+
+// Step 2:
+// -------
+// Define the structure's "auto commands", as a DSL datatype.
+
+/// commands that advance the state of the pixmap,
+/// whose execution is independent of editor state
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum AutoCommand {
+    /// set the pixel at the given coordinate to the given palette index
+    SetPixel(Nat, Nat, Nat),
+
+    /// change the color at the given palette index
+    SetColor(Nat, Color),
+
+    /// add a color to the end of the palette
+    AddColor(Color),
+}
+
+// Step 3:
+// -------
+//
+// Define a canonical editor for the structure in question.  Again, use simplified, affine Rust.
+
+/// the history-_independent_ state of the editor
+#[derive(Debug, Serialize, Deserialize, Hash)]
+pub struct EditorState {
+    /// created by an Init command; affected by Auto and Edit commands
+    pub pixmap: Pixmap,
+
+    /// initialized by an Init command; affected by Edit commands (but not Auto commands)
+    pub cursor: (Nat, Nat),
+
+    /// the palette index of the active color, which paints pixels
+    pub color: Nat,
+}
+
+/// the history-_dependent_ state of the editor
+#[derive(Debug, Serialize, Deserialize, Hash)]
+pub struct Editor {
+    /// full linear history of this pixmap's evolution, as a sequence of commands
+    pub history: Vec<Command>,
+
+    /// current state of the pixmap and surrounding editor environment
+    pub state: Option<EditorState>,
+}
+
+// Step 4a:
+// -------
+// Define commands that initialize the editor state.
+
+/// commands that create new pixmaps
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum InitCommand {
+    /// make a new grid of pixels, with the given width and height, and the default palette
+    Make(Nat, Nat),
+
+    /// edit a copy of the given pixmap
+    Pixmap(Pixmap),
+
+    /// edit a copy of the given (monochrome) bitmap, with a black and white palette
+    Bitmap(Bitmap),
+}
+
+// Step 4b:
+// -------
+// Define commands that evolve the editor state with edits,
+//   or changes to the edit state (cursor location and active color).
+
+/// commands that advance the editor state,
+/// and possibly, its associated pixmap state.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum EditCommand {
+    /// move the grid cursor one unit in a relative direction
+    MoveRel(Dir2D),
+
+    /// move the grid cursor to a absolute position
+    MoveAbs(Nat, Nat),
+
+    /// make the color at the given palette index active
+    PickColor(Nat),
+
+    /// make the next (or previous) color in the palette active, wrapping around
+    CycleColor(Dir1D),
+
+    /// make the color of the pixel at the cursor's grid position active
+    PickAtCursor,
+
+    /// paint the pixel at the cursor's grid position with the active color
+    Paint,
+}
+
+// Step 4c:
+// -------
+//
+// Define a combined language of commands that includes (distinct) Init, Auto
+// and Edit sublanguages.
+
+/// commands that advance the evolution of a pixmap
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
+pub enum Command {
+    /// commands that create new pixmaps
+    Init(InitCommand),
+
+    /// commands that advance the state of the pixmap,
+    /// whose execution is independent of editor state
+    Auto(AutoCommand),
+
+    /// commands that advance the editor state,
+    /// and possibly, its associated pixmap state
+    Edit(EditCommand),
+}
+
+// Step 5:
+// -------
+//
+// Define the state-change semantics for the command languages.
+
+/// semantic definitions for pixmaps and pixmap editors.
+pub mod semantics {
+    use super::{AutoCommand, Bitmap, Color, Pixmap, MAX_COLORS};
+    use super::{Command, Dir1D, Dir2D, EditCommand, Editor, EditorState, InitCommand};
+    use bitmap::semantics::{bitmap_check, bitmap_get_bit};
+    use bitmap::Major;
+    use types::lang::Error;
+
+    /// black, white, and the primary and secondary colors
+    pub fn default_palette() -> Vec<Color> {
+        vec![
+            Color::RGB(0, 0, 0),
+            Color::RGB(255, 255, 255),
+            Color::RGB(255, 0, 0),
+            Color::RGB(0, 255, 0),
+            Color::RGB(0, 0, 255),
+            Color::RGB(255, 255, 0),
+            Color::RGB(0, 255, 255),
+            Color::RGB(255, 0, 255),
+        ]
+    }
+
+    fn pixmap_init(w: usize, h: usize, palette: Vec<Color>) -> Pixmap {
+        Pixmap {
+            width: w,
+            height: h,
+            palette,
+            pixels: vec![vec![0; w]; h],
+        }
+    }
+
+    /// check that the pixmap is non-empty, that its pixels agree with its width and height,
+    /// and that they index its palette (of at most `MAX_COLORS` colors)
+    pub fn pixmap_check(pixmap: &Pixmap) -> Result<(), Error> {
+        if pixmap.width == 0 || pixmap.height == 0 {
+            Err(Error::Invalid(
+                "pixmap dimensions must be positive".to_string(),
+            ))
+        } else if pixmap.pixels.len() != pixmap.height
+            || pixmap.pixels.iter().any(|row| row.len() != pixmap.width)
+        {
+            Err(Error::Invalid(format!(
+                "pixmap pixels do not match its {}x{} size",
+                pixmap.width, pixmap.height
+            )))
+        } else if pixmap.palette.is_empty() || pixmap.palette.len() > MAX_COLORS {
+            Err(Error::Invalid(format!(
+                "a palette holds from 1 to {} colors",
+                MAX_COLORS
+            )))
+        } else {
+            match pixmap
+                .pixels
+                .iter()
+                .flatten()
+                .find(|&&c| c >= pixmap.palette.len())
+            {
+                Some(&c) => Err(Error::OutOfBounds(vec![c])),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// the pixmap of the bitmap, whose palette is black (unset bits) and white (set bits)
+    pub fn pixmap_of_bitmap(bitmap: &Bitmap) -> Pixmap {
+        let palette = vec![Color::RGB(0, 0, 0), Color::RGB(255, 255, 255)];
+        let mut pixmap = pixmap_init(bitmap.width, bitmap.height, palette);
+        for y in 0..bitmap.height {
+            for x in 0..bitmap.width {
+                pixmap.pixels[y][x] = if bitmap_get_bit(bitmap, x, y) { 1 } else { 0 }
+            }
+        }
+        pixmap
+    }
+
+    /// the bitmap of the pixmap, whose set bits are the pixels of any color but the first
+    /// (the background color, at palette index zero)
+    pub fn bitmap_of_pixmap(pixmap: &Pixmap) -> Bitmap {
+        Bitmap {
+            width: pixmap.width,
+            height: pixmap.height,
+            major: Major::Row,
+            bits: pixmap
+                .pixels
+                .iter()
+                .map(|row| row.iter().map(|&c| c != 0).collect())
+                .collect(),
+        }
+    }
+
+    pub fn pixmap_get_pixel(pixmap: &Pixmap, x: usize, y: usize) -> usize {
+        pixmap.pixels[y][x]
+    }
+
+    /// the color of the pixel
+    pub fn pixmap_get_color(pixmap: &Pixmap, x: usize, y: usize) -> &Color {
+        &pixmap.palette[pixmap_get_pixel(pixmap, x, y)]
+    }
+
+    pub fn pixmap_set_pixel(
+        pixmap: &mut Pixmap,
+        x: usize,
+        y: usize,
+        c: usize,
+    ) -> Result<(), Error> {
+        if x >= pixmap.width || y >= pixmap.height {
+            Err(Error::OutOfBounds(vec![x, y]))
+        } else if c >= pixmap.palette.len() {
+            Err(Error::OutOfBounds(vec![c]))
+        } else {
+            pixmap.pixels[y][x] = c;
+            debug!("pixmap_set_pixel({}, {}, {})", x, y, c);
+            Ok(())
+        }
+    }
+
+    pub fn pixmap_eval(pixmap: &mut Pixmap, command: &AutoCommand) -> Result<(), Error> {
+        debug!("pixmap_eval {:?}", command);
+        let res = match command {
+            AutoCommand::SetPixel(x, y, c) => pixmap_set_pixel(pixmap, *x, *y, *c),
+            AutoCommand::SetColor(c, _) if *c >= pixmap.palette.len() => {
+                Err(Error::OutOfBounds(vec![*c]))
+            }
+            AutoCommand::SetColor(c, color) => {
+                pixmap.palette[*c] = color.clone();
+                Ok(())
+            }
+            AutoCommand::AddColor(_) if pixmap.palette.len() >= MAX_COLORS => Err(Error::Invalid(
+                format!("a palette holds at most {} colors", MAX_COLORS),
+            )),
+            AutoCommand::AddColor(color) => {
+                pixmap.palette.push(color.clone());
+                Ok(())
+            }
+        };
+        debug!("pixmap_eval {:?} ==> {:?}", command, res);
+        res
+    }
+
+    pub fn editor_state_eval(editor: &mut EditorState, command: &EditCommand) -> Result<(), Error> {
+        debug!("editor_state_eval {:?}", command);
+        let (x, y) = editor.cursor;
+        let colors = editor.pixmap.palette.len();
+        let res = match command {
+            EditCommand::MoveRel(dir) => {
+                let (w, h) = (editor.pixmap.width, editor.pixmap.height);
+                editor.cursor = match dir {
+                    Dir2D::Left => (if x == 0 { 0 } else { x - 1 }, y),
+                    Dir2D::Right => (if x + 1 >= w { w - 1 } else { x + 1 }, y),
+                    Dir2D::Up => (x, if y == 0 { 0 } else { y - 1 }),
+                    Dir2D::Down => (x, if y + 1 >= h { h - 1 } else { y + 1 }),
+                };
+                Ok(())
+            }
+            EditCommand::MoveAbs(x, y) => {
+                if *x < editor.pixmap.width && *y < editor.pixmap.height {
+                    editor.cursor = (*x, *y);
+                    Ok(())
+                } else {
+                    Err(Error::OutOfBounds(vec![*x, *y]))
+                }
+            }
+            EditCommand::PickColor(c) => {
+                if *c < colors {
+                    editor.color = *c;
+                    Ok(())
+                } else {
+                    Err(Error::OutOfBounds(vec![*c]))
+                }
+            }
+            EditCommand::CycleColor(dir) => {
+                editor.color = match dir {
+                    Dir1D::Forward => (editor.color + 1) % colors,
+                    Dir1D::Backward => (editor.color + colors - 1) % colors,
+                };
+                Ok(())
+            }
+            EditCommand::PickAtCursor => {
+                editor.color = pixmap_get_pixel(&editor.pixmap, x, y);
+                Ok(())
+            }
+            EditCommand::Paint => pixmap_set_pixel(&mut editor.pixmap, x, y, editor.color),
+        };
+        debug!("editor_state_eval {:?} ==> {:?}", command, res);
+        res
+    }
+
+    pub fn editor_eval(editor: &mut Editor, command: &Command) -> Result<(), Error> {
+        let num = editor.history.len();
+        debug!("#{}: editor_eval {:?}", num, command);
+        // save the command in the history
+        editor.history.push(command.clone());
+        // evaluate the command in the appropriate evaluation context:
+        let res = match command {
+            Command::Init(command) => {
+                let pixmap = match command {
+                    InitCommand::Make(w, h) => pixmap_init(*w, *h, default_palette()),
+                    InitCommand::Pixmap(pixmap) => pixmap.clone(),
+                    InitCommand::Bitmap(bitmap) => {
+                        bitmap_check(bitmap)?;
+                        pixmap_of_bitmap(bitmap)
+                    }
+                };
+                pixmap_check(&pixmap)?;
+                // the first color is the background, so paint with the next one (if any)
+                let color = if pixmap.palette.len() > 1 { 1 } else { 0 };
+                editor.state = Some(EditorState {
+                    pixmap,
+                    cursor: (0, 0),
+                    color,
+                });
+                Ok(())
+            }
+            Command::Auto(command) => match editor.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => pixmap_eval(&mut st.pixmap, command),
+            },
+            Command::Edit(command) => match editor.state {
+                None => Err(Error::NoEditorState),
+                Some(ref mut st) => editor_state_eval(st, command),
+            },
+        };
+        info!("#{}: editor_eval {:?} ==> {:?}", num, command, res);
+        res
+    }
+}
+
+// Step 6:
+// -------
+//
+// Define the IO for the Editor using the abstract `render` module, and associated types.
+
+pub mod io {
+    use super::{Dir1D, Dir2D, EditCommand, EditorState};
+    use types::event::{Event, Quit};
+    use types::render::{self, Color, Fill, Rect};
+
+    pub fn edit_commands_of_event(event: &Event) -> Result<Vec<EditCommand>, Quit> {
+        match event {
+            Event::Quit => Err(Quit),
+            Event::KeyDown(kei) => match kei.key.as_str() {
+                "Escape" => Err(Quit),
+                " " => Ok(vec![EditCommand::Paint]),
+                "p" => Ok(vec![EditCommand::PickAtCursor]),
+                "Tab" if kei.shift => Ok(vec![EditCommand::CycleColor(Dir1D::Backward)]),
+                "Tab" => Ok(vec![EditCommand::CycleColor(Dir1D::Forward)]),
+                "ArrowLeft" => Ok(vec![EditCommand::MoveRel(Dir2D::Left)]),
+                "ArrowRight" => Ok(vec![EditCommand::MoveRel(Dir2D::Right)]),
+                "ArrowUp" => Ok(vec![EditCommand::MoveRel(Dir2D::Up)]),
+                "ArrowDown" => Ok(vec![EditCommand::MoveRel(Dir2D::Down)]),
+                // a digit picks a color of the palette, by its index
+                key => match key.parse() {
+                    Ok(c) => Ok(vec![EditCommand::PickColor(c)]),
+                    Err(_) => Ok(vec![]),
+                },
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    pub fn render_elms(edit_state: &EditorState) -> Result<render::Elms, String> {
+        use render::Render;
+
+        let mut render: Render = Render::new();
+        let pixmap = &edit_state.pixmap;
+
+        // to do -- get these constants from the editor state
+        let zoom = 32;
+        let border_width = 2;
+        let cell_width = zoom + border_width * 2;
+
+        let grid_border_color = Color::RGB(100, 80, 100);
+        let cursor_border_color = Color::RGB(150, 255, 150);
+
+        // grid border is a single background rect, beneath the cursor's border:
+        let grid_rect = Rect::new(0, 0, pixmap.width * cell_width, pixmap.height * cell_width);
+        let cursor_rect = Rect::new(
+            (edit_state.cursor.0 * cell_width) as isize,
+            (edit_state.cursor.1 * cell_width) as isize,
+            cell_width,
+            cell_width,
+        );
+        render.rect(&grid_rect, Fill::Closed(grid_border_color.clone()));
+        render.rect(&cursor_rect, Fill::Closed(cursor_border_color.clone()));
+
+        // grid cells are rects, filled with their colors:
+        for y in 0..pixmap.height {
+            for x in 0..pixmap.width {
+                let cell_rect = Rect::new(
+                    (x * cell_width + border_width) as isize,
+                    (y * cell_width + border_width) as isize,
+                    zoom,
+                    zoom,
+                );
+                let color = super::semantics::pixmap_get_color(pixmap, x, y);
+                render.rect(&cell_rect, Fill::Closed(color.clone()));
+            }
+        }
+
+        // the palette is a row of swatches, below the grid; the active color has the cursor's border:
+        let palette_y = ((pixmap.height + 1) * cell_width) as isize;
+        for (c, color) in pixmap.palette.iter().enumerate() {
+            let swatch_rect =
+                Rect::new((c * cell_width) as isize, palette_y, cell_width, cell_width);
+            let border_color = if c == edit_state.color {
+                &cursor_border_color
+            } else {
+                &grid_border_color
+            };
+            render.rect(&swatch_rect, Fill::Closed(border_color.clone()));
+            let color_rect = Rect::new(
+                (c * cell_width + border_width) as isize,
+                palette_y + border_width as isize,
+                zoom,
+                zoom,
+            );
+            render.rect(&color_rect, Fill::Closed(color.clone()));
+        }
+        Ok(render.into_elms())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::semantics::{bitmap_of_pixmap, pixmap_check, pixmap_of_bitmap, pixmap_set_pixel};
+    use bitmap::semantics::bitmap_set_bit;
+    use bitmap::{Bitmap, Major};
+    use types::lang::Error;
+
+    #[test]
+    fn bitmap_round_trips() {
+        let mut bitmap = Bitmap {
+            width: 3,
+            height: 2,
+            major: Major::Row,
+            bits: vec![vec![false; 3]; 2],
+        };
+        bitmap_set_bit(&mut bitmap, 0, 0, true);
+        bitmap_set_bit(&mut bitmap, 2, 1, true);
+        let pixmap = pixmap_of_bitmap(&bitmap);
+        assert_eq!(pixmap_check(&pixmap), Ok(()));
+        assert_eq!(pixmap.pixels, vec![vec![1, 0, 0], vec![0, 0, 1]]);
+        assert_eq!(bitmap_of_pixmap(&pixmap), bitmap);
+    }
+
+    #[test]
+    fn palette_indices_are_bounded() {
+        let bitmap = Bitmap {
+            width: 2,
+            height: 2,
+            major: Major::Row,
+            bits: vec![vec![false; 2]; 2],
+        };
+        let mut pixmap = pixmap_of_bitmap(&bitmap);
+        // a black and white palette, so only indices 0 and 1
+        assert_eq!(pixmap_set_pixel(&mut pixmap, 1, 1, 1), Ok(()));
+        assert_eq!(
+            pixmap_set_pixel(&mut pixmap, 1, 1, 2),
+            Err(Error::OutOfBounds(vec![2]))
+        );
+        pixmap.pixels[0][1] = 5;
+        assert_eq!(pixmap_check(&pixmap), Err(Error::OutOfBounds(vec![5])));
+    }
+}
@@ -2,8 +2,9 @@ use serde::{Deserialize, Serialize};
 
 use bitmap;
 use glyph;
+use pixmap;
 use types::{
-    lang::{Atom, Dir2D, Name},
+    lang::{Atom, Dir2D, Media, Name},
     render::{Dim, Elm, Elms, Fill, Node, Pos, Rect},
 };
 
@@ -120,6 +121,21 @@ impl Render {
         }
     }
 
+    pub fn pixmap(&mut self, pm: &pixmap::Pixmap, ba: &BitmapAtts) {
+        for y in 0..pm.height {
+            for x in 0..pm.width {
+                let cell_rect = Rect::new(
+                    (x * ba.zoom) as isize,
+                    (y * ba.zoom) as isize,
+                    ba.zoom,
+                    ba.zoom,
+                );
+                let color = pixmap::semantics::pixmap_get_color(pm, x, y);
+                self.rect(&cell_rect, Fill::Closed(color.clone()));
+            }
+        }
+    }
+
     pub fn atom(&mut self, atom: &Atom, ta: &TextAtts) {
         match atom {
             Atom::Bool(b) => self.text(&format!("{:?}", b), ta),
@@ -135,6 +151,68 @@ impl Render {
         }
     }
 
+    pub fn media(&mut self, media: &Media, ta: &TextAtts, ba: &BitmapAtts) {
+        match media {
+            Media::Void => self.str("void", ta),
+            Media::Atom(a) => self.atom(a, ta),
+            Media::Name(n) => self.name(n, ta),
+            Media::Bitmap(bm) => {
+                self.begin(&Name::Void, FrameType::None);
+                self.bitmap(bm, ba);
+                self.end()
+            }
+            Media::Pixmap(pm) => {
+                self.begin(&Name::Void, FrameType::None);
+                self.pixmap(pm, ba);
+                self.end()
+            }
+            Media::Chain(ch) => {
+                let flow = FlowAtts {
+                    dir: Dir2D::Right,
+                    intra_pad: ta.glyph_flow.intra_pad * 2,
+                    inter_pad: ta.glyph_flow.inter_pad,
+                };
+                self.begin(&Name::Void, FrameType::Flow(flow));
+                for node in ch.iter() {
+                    self.media(&node.media, ta, ba)
+                }
+                self.end()
+            }
+            Media::Named(n, m) => {
+                let flow = FlowAtts {
+                    dir: Dir2D::Down,
+                    intra_pad: ta.glyph_flow.intra_pad,
+                    inter_pad: ta.glyph_flow.inter_pad,
+                };
+                self.begin(&Name::Void, FrameType::Flow(flow));
+                self.name(n, ta);
+                self.media(m, ta, ba);
+                self.end()
+            }
+            Media::Located(_, m) => self.media(m, ta, ba),
+            Media::Grid(gr) => {
+                let flow = |dir| FlowAtts {
+                    dir,
+                    intra_pad: ta.glyph_flow.intra_pad * 2,
+                    inter_pad: ta.glyph_flow.inter_pad,
+                };
+                self.begin(&Name::Void, FrameType::Flow(flow(Dir2D::Down)));
+                for row in gr.cells.iter() {
+                    self.begin(&Name::Void, FrameType::Flow(flow(Dir2D::Right)));
+                    for cell in row.iter() {
+                        self.media(cell, ta, ba)
+                    }
+                    self.end()
+                }
+                self.end()
+            }
+            Media::Location(_) => self.str("location", ta),
+            Media::Store(_) | Media::StoreProj(_, _) => self.str("store", ta),
+            Media::Merkle(_) => self.str("merkle", ta),
+            Media::Quote(_) => self.str("quote", ta),
+        }
+    }
+
     pub fn str(&mut self, s: &str, ta: &TextAtts) {
         self.text(&s.to_string(), ta)
     }
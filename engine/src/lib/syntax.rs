@@ -0,0 +1,1259 @@
+/*!
+
+Textual surface syntax for expressions and media
+
+A concise, human-readable alternative to the serde (JSON) form of
+`types::lang::Exp` and `types::lang::Media`, for scripts kept in
+version control.  The pretty-printer round-trips with the parser:
+`parse_exp(&print_exp(e)) == Ok(e)`.
+
+Summary of the syntax (whitespace is insignificant, except that a name's
+arguments follow it directly, as in `#tag(#x)`; `//` comments run to the
+end of the line):
+
+```text
+void  true  false  42  "text"       void and atoms
+#foo  #42  #"a b"  #tag(#x, 3)      names (`#` is omitted within a name)
+#()  #&foo                          the void name, and a merkle name
+loc(#t, #p)                         location
+#n: e   loc(#t, #p): e              named and located media
+bitmap[0 1; 1 0]  bitmap col[...]   bitmaps (nonzero digits are set bits)
+pixmap(c0, c1, c2)[0 1; 2 0]        pixmaps (hex digits index the palette)
+rgb(255, 0, 0)                      a color of a palette
+chain[#a: 1, #b: 2]                 chain of named media
+grid[1, 2; 3, 4]  grid[; ;]         grids of media (rows end with `;`,
+                                    optional after a non-empty last row)
+store #s { #a: 1, #b: 2 }           store of named media
+e.#a                                store projection
+merkle(e)  merkle_from(e)           merkle media, and its expression form
+store_from(#s, e)                   store from named media
+quote(e)  fix_quote(f, e)           quoted code, intensional recursion
+command {"Bitmap": ...}             embedded command (serde JSON)
+x  $#"not an ident"                 variables
+{ let x = e1; let y = e2 }          blocks
+put #n e  thunk #n { ... }  get e   adapton primitives
+```
+
+*/
+
+use hashcons::merkle::Merkle;
+use types::lang::{Atom, Block, Error, Exp, Location, Media, Name, Store};
+use types::render::Color;
+use {bitmap, chain, grid, pixmap};
+
+/// words that cannot be (bare) variables
+const KEYWORDS: &[&str] = &[
+    "void",
+    "true",
+    "false",
+    "let",
+    "put",
+    "get",
+    "thunk",
+    "loc",
+    "bitmap",
+    "col",
+    "pixmap",
+    "chain",
+    "grid",
+    "store",
+    "merkle",
+    "merkle_from",
+    "store_from",
+    "quote",
+    "fix_quote",
+    "command",
+];
+
+pub fn parse_exp(s: &str) -> Result<Exp, Error> {
+    let mut p = Parser::new(s);
+    let exp = p.exp()?;
+    p.end()?;
+    Ok(exp)
+}
+
+pub fn parse_media(s: &str) -> Result<Media, Error> {
+    let mut p = Parser::new(s);
+    let media = p.media()?;
+    p.end()?;
+    Ok(media)
+}
+
+pub fn parse_name(s: &str) -> Result<Name, Error> {
+    let mut p = Parser::new(s);
+    let name = p.name()?;
+    p.end()?;
+    Ok(name)
+}
+
+pub fn print_exp(exp: &Exp) -> String {
+    let mut p = Printer::new();
+    p.exp(exp);
+    p.out
+}
+
+pub fn print_media(media: &Media) -> String {
+    print_exp(&exp_of_media(media))
+}
+
+pub fn print_name(name: &Name) -> String {
+    format!("#{}", name_body(name))
+}
+
+/// every media value is also an expression (that evaluates to itself)
+pub fn exp_of_media(media: &Media) -> Exp {
+    match media {
+        Media::Void => Exp::Void,
+        Media::Atom(a) => Exp::Atom(a.clone()),
+        Media::Name(n) => Exp::Name(n.clone()),
+        Media::Location(l) => Exp::Location(l.clone()),
+        Media::Bitmap(b) => Exp::Bitmap(b.clone()),
+        Media::Pixmap(p) => Exp::Pixmap(p.clone()),
+        Media::Chain(c) => Exp::Chain(c.clone()),
+        Media::Grid(g) => Exp::Grid(g.clone()),
+        Media::Store(s) => Exp::Store(s.clone()),
+        Media::StoreProj(s, n) => Exp::StoreProj(Box::new(Exp::Store(s.clone())), n.clone()),
+        Media::Named(n, m) => Exp::Named(n.clone(), Box::new(exp_of_media(m))),
+        Media::Located(l, m) => Exp::Located(l.clone(), Box::new(exp_of_media(m))),
+        Media::Merkle(m) => Exp::Merkle(Merkle::new(exp_of_media(m))),
+        Media::Quote(e) => Exp::Quote(e.clone()),
+    }
+}
+
+/// the media value of the expression, if it has the form of one
+pub fn media_of_exp(exp: &Exp) -> Option<Media> {
+    match exp {
+        Exp::Void => Some(Media::Void),
+        Exp::Atom(a) => Some(Media::Atom(a.clone())),
+        Exp::Name(n) => Some(Media::Name(n.clone())),
+        Exp::Location(l) => Some(Media::Location(l.clone())),
+        Exp::Bitmap(b) => Some(Media::Bitmap(b.clone())),
+        Exp::Pixmap(p) => Some(Media::Pixmap(p.clone())),
+        Exp::Chain(c) => Some(Media::Chain(c.clone())),
+        Exp::Grid(g) => Some(Media::Grid(g.clone())),
+        Exp::Store(s) => Some(Media::Store(s.clone())),
+        Exp::StoreProj(e, n) => match **e {
+            Exp::Store(ref s) => Some(Media::StoreProj(s.clone(), n.clone())),
+            _ => None,
+        },
+        Exp::Named(n, e) => Some(Media::Named(n.clone(), Box::new(media_of_exp(e)?))),
+        Exp::Located(l, e) => Some(Media::Located(l.clone(), Box::new(media_of_exp(e)?))),
+        Exp::Merkle(e) => Some(Media::Merkle(Merkle::new(media_of_exp(e)?))),
+        Exp::Quote(e) => Some(Media::Quote(e.clone())),
+        _ => None,
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Parsing, by recursive descent over the characters of the input:
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Parser {
+        Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error<X>(&self, expected: &str) -> Result<X, Error> {
+        let (mut line, mut col) = (1, 1);
+        for c in self.chars[..self.pos].iter() {
+            if *c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        Err(Error::Syntax(line, col, expected.to_string()))
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            match self.chars.get(self.pos) {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('/') if self.chars.get(self.pos + 1) == Some(&'/') => {
+                    while self.pos < self.chars.len() && self.chars[self.pos] != '\n' {
+                        self.pos += 1
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// the next non-whitespace character, if any
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ws();
+        self.chars.get(self.pos).cloned()
+    }
+
+    /// consume the given character, if it is next
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(&format!("`{}`", c))
+        }
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => self.error("end of input"),
+        }
+    }
+
+    fn is_ident_start(c: char) -> bool {
+        c.is_ascii_alphabetic() || c == '_'
+    }
+
+    fn is_ident_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+
+    /// the next identifier, without consuming it
+    fn peek_ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(c) if Parser::is_ident_start(c) => {
+                let mut end = self.pos;
+                while end < self.chars.len() && Parser::is_ident_char(self.chars[end]) {
+                    end += 1
+                }
+                Some(self.chars[self.pos..end].iter().collect())
+            }
+            _ => None,
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let id = self.peek_ident()?;
+        self.pos += id.chars().count();
+        Some(id)
+    }
+
+    fn keyword(&mut self, kw: &str) -> Result<(), Error> {
+        match self.peek_ident() {
+            Some(ref id) if id == kw => {
+                self.pos += kw.len();
+                Ok(())
+            }
+            _ => self.error(&format!("`{}`", kw)),
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        let start = self.pos;
+        while self.pos < self.chars.len() && self.chars[self.pos].is_ascii_digit() {
+            self.pos += 1
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.parse() {
+            Ok(n) => Ok(n),
+            Err(_) => {
+                self.pos = start;
+                self.error("a number")
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                None => return self.error("end of string"),
+                Some(c) => *c,
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let c = match self.chars.get(self.pos) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        _ => return self.error("string escape"),
+                    };
+                    self.pos += 1;
+                    s.push(c)
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<Atom, Error> {
+        match self.peek() {
+            Some('"') => Ok(Atom::String(self.string()?)),
+            Some(c) if c.is_ascii_digit() => Ok(Atom::Usize(self.number()?)),
+            _ => match self.peek_ident().as_deref() {
+                Some("true") => self.keyword("true").map(|_| Atom::Bool(true)),
+                Some("false") => self.keyword("false").map(|_| Atom::Bool(false)),
+                _ => self.error("an atom"),
+            },
+        }
+    }
+
+    fn name(&mut self) -> Result<Name, Error> {
+        self.expect('#')?;
+        self.name_body()
+    }
+
+    /// a name, without its leading `#`
+    fn name_body(&mut self) -> Result<Name, Error> {
+        let mut name = self.name_primary()?;
+        // arguments follow directly, so `put #n (e)` is not a tagged tuple
+        while self.chars.get(self.pos) == Some(&'(') {
+            self.pos += 1;
+            let args = self.list(')', |p| p.name_body())?;
+            name = Name::TaggedTuple(Box::new(name), args)
+        }
+        Ok(name)
+    }
+
+    fn name_primary(&mut self) -> Result<Name, Error> {
+        match self.peek() {
+            Some('&') => {
+                self.pos += 1;
+                Ok(Name::Merkle(Merkle::new(self.name_primary()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                if self.eat(')') {
+                    Ok(Name::Void)
+                } else {
+                    let name = self.name_body()?;
+                    self.expect(')')?;
+                    Ok(name)
+                }
+            }
+            Some(c) if Parser::is_ident_start(c) => match self.ident().unwrap().as_str() {
+                "true" => Ok(Name::Atom(Atom::Bool(true))),
+                "false" => Ok(Name::Atom(Atom::Bool(false))),
+                id => Ok(Name::Atom(Atom::String(id.to_string()))),
+            },
+            Some('"') => Ok(Name::Atom(Atom::String(self.string()?))),
+            Some(c) if c.is_ascii_digit() => Ok(Name::Atom(Atom::Usize(self.number()?))),
+            _ => self.error("a name"),
+        }
+    }
+
+    /// a variable: an identifier that is not a keyword, or `$` and a name
+    fn var(&mut self) -> Result<Name, Error> {
+        if self.eat('$') {
+            return self.name();
+        };
+        match self.peek_ident() {
+            Some(ref id) if !KEYWORDS.contains(&id.as_str()) => {
+                self.pos += id.len();
+                Ok(Name::Atom(Atom::String(id.clone())))
+            }
+            _ => self.error("a variable"),
+        }
+    }
+
+    /// comma-separated items, ending with the given (consumed) character
+    fn list<X, F>(&mut self, close: char, item: F) -> Result<Vec<X>, Error>
+    where
+        F: Fn(&mut Parser) -> Result<X, Error>,
+    {
+        let mut items = vec![];
+        if self.eat(close) {
+            return Ok(items);
+        };
+        loop {
+            items.push(item(self)?);
+            if self.eat(close) {
+                return Ok(items);
+            };
+            self.expect(',')?
+        }
+    }
+
+    /// rows, each ending with `;` (optional after a non-empty last row), then `]`
+    fn rows<X, F>(&mut self, row: F) -> Result<Vec<Vec<X>>, Error>
+    where
+        F: Fn(&mut Parser) -> Result<Vec<X>, Error>,
+    {
+        self.expect('[')?;
+        let mut rows = vec![];
+        loop {
+            if self.eat(']') {
+                return Ok(rows);
+            };
+            rows.push(match self.peek() {
+                Some(';') => vec![],
+                _ => row(self)?,
+            });
+            if !self.eat(';') {
+                self.expect(']')?;
+                return Ok(rows);
+            }
+        }
+    }
+
+    fn location(&mut self) -> Result<Location, Error> {
+        self.keyword("loc")?;
+        self.expect('(')?;
+        let time = self.name()?;
+        self.expect(',')?;
+        let place = self.name()?;
+        self.expect(')')?;
+        Ok(Location { time, place })
+    }
+
+    fn media(&mut self) -> Result<Media, Error> {
+        let start = self.pos;
+        let exp = self.exp()?;
+        match media_of_exp(&exp) {
+            Some(media) => Ok(media),
+            None => {
+                self.pos = start;
+                self.skip_ws();
+                self.error("media")
+            }
+        }
+    }
+
+    fn named_media(&mut self) -> Result<(Name, Media), Error> {
+        let name = self.name()?;
+        self.expect(':')?;
+        Ok((name, self.media()?))
+    }
+
+    fn exp(&mut self) -> Result<Exp, Error> {
+        match self.peek() {
+            Some('#') => {
+                let name = self.name()?;
+                if self.eat(':') {
+                    Ok(Exp::Named(name, Box::new(self.exp()?)))
+                } else {
+                    self.projs(Exp::Name(name))
+                }
+            }
+            _ => match self.peek_ident().as_deref() {
+                Some("put") => {
+                    self.keyword("put")?;
+                    let name = self.name()?;
+                    Ok(Exp::Put(name, Box::new(self.exp()?)))
+                }
+                Some("get") => {
+                    self.keyword("get")?;
+                    Ok(Exp::Get(Box::new(self.exp()?)))
+                }
+                Some("thunk") => {
+                    self.keyword("thunk")?;
+                    let name = self.name()?;
+                    Ok(Exp::Thunk(name, self.block()?.bindings))
+                }
+                Some("loc") => {
+                    let loc = self.location()?;
+                    if self.eat(':') {
+                        Ok(Exp::Located(loc, Box::new(self.exp()?)))
+                    } else {
+                        self.projs(Exp::Location(loc))
+                    }
+                }
+                _ => {
+                    let exp = self.atomic()?;
+                    self.projs(exp)
+                }
+            },
+        }
+    }
+
+    /// zero or more store projections, each of the form `.#name`
+    fn projs(&mut self, mut exp: Exp) -> Result<Exp, Error> {
+        while self.eat('.') {
+            exp = Exp::StoreProj(Box::new(exp), self.name()?)
+        }
+        Ok(exp)
+    }
+
+    fn block(&mut self) -> Result<Block, Error> {
+        self.expect('{')?;
+        let mut bindings = vec![];
+        while !self.eat('}') {
+            self.keyword("let")?;
+            let var = self.var()?;
+            self.expect('=')?;
+            bindings.push((var, self.exp()?));
+            if !self.eat(';') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(Block { bindings })
+    }
+
+    fn atomic(&mut self) -> Result<Exp, Error> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let exp = self.exp()?;
+                self.expect(')')?;
+                return Ok(exp);
+            }
+            Some('{') => return Ok(Exp::Block(self.block()?)),
+            Some('$') => return Ok(Exp::Var(self.var()?)),
+            Some(c) if c == '"' || c.is_ascii_digit() => return Ok(Exp::Atom(self.atom()?)),
+            _ => (),
+        };
+        let kw = match self.peek_ident() {
+            Some(kw) => kw,
+            None => return self.error("an expression"),
+        };
+        match kw.as_str() {
+            "void" => {
+                self.keyword("void")?;
+                Ok(Exp::Void)
+            }
+            "true" | "false" => Ok(Exp::Atom(self.atom()?)),
+            "bitmap" => Ok(Exp::Bitmap(Box::new(self.bitmap()?))),
+            "pixmap" => Ok(Exp::Pixmap(Box::new(self.pixmap()?))),
+            "chain" => {
+                self.keyword("chain")?;
+                self.expect('[')?;
+                let nodes = self.list(']', |p| p.named_media())?;
+                let mut chain = chain::Chain::Empty;
+                for (name, media) in nodes.into_iter().rev() {
+                    chain.push_node(chain::Node {
+                        name,
+                        media: Box::new(media),
+                    })
+                }
+                Ok(Exp::Chain(Box::new(chain)))
+            }
+            "grid" => Ok(Exp::Grid(Box::new(self.grid()?))),
+            "store" => {
+                self.keyword("store")?;
+                let name = self.name()?;
+                self.expect('{')?;
+                let table = self
+                    .list('}', |p| p.named_media())?
+                    .into_iter()
+                    .map(|(n, m)| (Merkle::new(n), Merkle::new(m)))
+                    .collect();
+                Ok(Exp::Store(Store {
+                    name: Merkle::new(name),
+                    table,
+                }))
+            }
+            "merkle" | "merkle_from" | "quote" => {
+                self.keyword(&kw)?;
+                self.expect('(')?;
+                let exp = self.exp()?;
+                self.expect(')')?;
+                Ok(match kw.as_str() {
+                    "merkle" => Exp::Merkle(Merkle::new(exp)),
+                    "merkle_from" => Exp::MerkleFrom(Box::new(exp)),
+                    _ => Exp::Quote(Box::new(exp)),
+                })
+            }
+            "store_from" => {
+                self.keyword("store_from")?;
+                self.expect('(')?;
+                let name = self.name()?;
+                self.expect(',')?;
+                let exp = self.exp()?;
+                self.expect(')')?;
+                Ok(Exp::StoreFrom(name, Box::new(exp)))
+            }
+            "fix_quote" => {
+                self.keyword("fix_quote")?;
+                self.expect('(')?;
+                let var = self.var()?;
+                self.expect(',')?;
+                let exp = self.exp()?;
+                self.expect(')')?;
+                Ok(Exp::FixQuote(var, Box::new(exp)))
+            }
+            "command" => {
+                self.keyword("command")?;
+                self.command()
+            }
+            _ => Ok(Exp::Var(self.var()?)),
+        }
+    }
+
+    /// rows of digits, separated by `;`, as in `parse_glyph!`
+    fn bitmap(&mut self) -> Result<bitmap::Bitmap, Error> {
+        self.keyword("bitmap")?;
+        let major = match self.peek_ident() {
+            Some(ref id) if id == "col" => {
+                self.keyword("col")?;
+                bitmap::Major::Col
+            }
+            _ => bitmap::Major::Row,
+        };
+        let rows = self.rows(|p| {
+            let mut row = vec![];
+            while let Some(c) = p.peek().filter(|c| c.is_ascii_digit()) {
+                p.pos += 1;
+                row.push(c != '0')
+            }
+            if row.is_empty() {
+                p.error("a bit, `;` or `]`")
+            } else {
+                Ok(row)
+            }
+        })?;
+        let (width, height) = (rows.first().map(|r| r.len()).unwrap_or(0), rows.len());
+        if rows.iter().any(|r| r.len() != width) {
+            return self.error("bitmap rows of equal width");
+        };
+        let bits = match major {
+            bitmap::Major::Row => rows,
+            bitmap::Major::Col => (0..width)
+                .map(|x| rows.iter().map(|r| r[x]).collect())
+                .collect(),
+        };
+        Ok(bitmap::Bitmap {
+            width,
+            height,
+            major,
+            bits,
+        })
+    }
+
+    /// a palette of colors, then rows of hex digits (that index the palette), separated by `;`
+    fn pixmap(&mut self) -> Result<pixmap::Pixmap, Error> {
+        self.keyword("pixmap")?;
+        self.expect('(')?;
+        let palette = self.list(')', |p| p.color())?;
+        if palette.is_empty() || palette.len() > pixmap::MAX_COLORS {
+            return self.error(&format!("a palette of 1 to {} colors", pixmap::MAX_COLORS));
+        };
+        let pixels = self.rows(|p| {
+            let mut row = vec![];
+            while let Some(c) = p.peek().filter(|c| c.is_ascii_hexdigit()) {
+                let index = c.to_digit(16).unwrap() as usize;
+                if index >= palette.len() {
+                    return p.error("an index of the palette");
+                };
+                p.pos += 1;
+                row.push(index)
+            }
+            if row.is_empty() {
+                p.error("a hex digit, `;` or `]`")
+            } else {
+                Ok(row)
+            }
+        })?;
+        let (width, height) = (pixels.first().map(|r| r.len()).unwrap_or(0), pixels.len());
+        if pixels.iter().any(|r| r.len() != width) {
+            return self.error("pixmap rows of equal width");
+        };
+        Ok(pixmap::Pixmap {
+            width,
+            height,
+            palette,
+            pixels,
+        })
+    }
+
+    /// `rgb(r, g, b)`
+    fn color(&mut self) -> Result<Color, Error> {
+        self.keyword("rgb")?;
+        self.expect('(')?;
+        let mut rgb = vec![];
+        for i in 0..3 {
+            if i > 0 {
+                self.expect(',')?
+            };
+            self.peek();
+            rgb.push(self.number()?)
+        }
+        self.expect(')')?;
+        Ok(Color::RGB(rgb[0], rgb[1], rgb[2]))
+    }
+
+    /// rows of comma-separated media, separated by `;`
+    fn grid(&mut self) -> Result<grid::Grid, Error> {
+        self.keyword("grid")?;
+        let cells = self.rows(|p| {
+            let mut row = vec![p.media()?];
+            while p.eat(',') {
+                row.push(p.media()?)
+            }
+            Ok(row)
+        })?;
+        let (width, height) = (cells.first().map(|r| r.len()).unwrap_or(0), cells.len());
+        if cells.iter().any(|r| r.len() != width) {
+            return self.error("grid rows of equal width");
+        };
+        Ok(grid::Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// a command, as a (balanced) serde JSON object
+    fn command(&mut self) -> Result<Exp, Error> {
+        if self.peek() != Some('{') {
+            return self.error("`{`");
+        };
+        let start = self.pos;
+        let (mut depth, mut in_string) = (0, false);
+        while let Some(c) = self.chars.get(self.pos).cloned() {
+            self.pos += 1;
+            match c {
+                '\\' if in_string => self.pos += 1,
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let json: String = self.chars[start..self.pos].iter().collect();
+                        return match serde_json::from_str(&json) {
+                            Ok(command) => Ok(Exp::Command(command)),
+                            Err(_) => {
+                                self.pos = start;
+                                self.error("a command")
+                            }
+                        };
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.error("`}`")
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Pretty-printing:
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if Parser::is_ident_start(c) => chars.all(Parser::is_ident_char),
+        _ => false,
+    }
+}
+
+fn string_lit(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn atom_lit(atom: &Atom) -> String {
+    match atom {
+        Atom::Bool(b) => format!("{}", b),
+        Atom::Usize(n) => format!("{}", n),
+        Atom::String(s) => string_lit(s),
+    }
+}
+
+fn name_body(name: &Name) -> String {
+    match name {
+        Name::Void => "()".to_string(),
+        Name::Atom(Atom::String(s)) if is_ident(s) && s != "true" && s != "false" => s.clone(),
+        Name::Atom(a) => atom_lit(a),
+        Name::TaggedTuple(tag, args) => {
+            let args: Vec<String> = args.iter().map(name_body).collect();
+            format!("{}({})", name_body(tag), args.join(", "))
+        }
+        Name::Merkle(m) => match **m {
+            Name::TaggedTuple(_, _) => format!("&({})", name_body(m)),
+            _ => format!("&{}", name_body(m)),
+        },
+    }
+}
+
+fn var_lit(name: &Name) -> String {
+    match name {
+        Name::Atom(Atom::String(s)) if is_ident(s) && !KEYWORDS.contains(&s.as_str()) => s.clone(),
+        _ => format!("${}", print_name(name)),
+    }
+}
+
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn new() -> Printer {
+        Printer {
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.out.push_str(s)
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ")
+        }
+    }
+
+    fn media(&mut self, media: &Media) {
+        self.exp(&exp_of_media(media))
+    }
+
+    fn named_media(&mut self, name: &Name, media: &Media) {
+        self.str(&print_name(name));
+        self.str(": ");
+        self.media(media)
+    }
+
+    fn exp(&mut self, exp: &Exp) {
+        match exp {
+            Exp::Void => self.str("void"),
+            Exp::Atom(a) => self.str(&atom_lit(a)),
+            Exp::Name(n) => self.str(&print_name(n)),
+            Exp::Location(l) => self.location(l),
+            Exp::Bitmap(b) => self.bitmap(b),
+            Exp::Pixmap(p) => self.pixmap(p),
+            Exp::Chain(c) => {
+                self.str("chain[");
+                for (i, node) in c.iter().enumerate() {
+                    if i > 0 {
+                        self.str(", ")
+                    };
+                    self.named_media(&node.name, &node.media)
+                }
+                self.str("]")
+            }
+            Exp::Grid(g) => {
+                self.str("grid[");
+                for (y, row) in g.cells.iter().enumerate() {
+                    if y > 0 {
+                        self.str(" ")
+                    };
+                    for (x, cell) in row.iter().enumerate() {
+                        if x > 0 {
+                            self.str(", ")
+                        };
+                        self.media(cell)
+                    }
+                    // an empty row ends with `;`, even when it is the last
+                    if y + 1 < g.cells.len() || row.is_empty() {
+                        self.str(";")
+                    }
+                }
+                self.str("]")
+            }
+            Exp::Store(s) => {
+                self.str("store ");
+                self.str(&print_name(&s.name));
+                self.str(" {");
+                for (i, (n, m)) in s.table.iter().enumerate() {
+                    self.str(if i > 0 { ", " } else { " " });
+                    self.named_media(n, m)
+                }
+                self.str(if s.table.is_empty() { "}" } else { " }" })
+            }
+            Exp::StoreProj(e, n) => {
+                match **e {
+                    // prefix forms extend rightward, so they need parens here
+                    Exp::Named(_, _)
+                    | Exp::Located(_, _)
+                    | Exp::Put(_, _)
+                    | Exp::Get(_)
+                    | Exp::Thunk(_, _) => {
+                        self.str("(");
+                        self.exp(e);
+                        self.str(")")
+                    }
+                    _ => self.exp(e),
+                };
+                self.str(".");
+                self.str(&print_name(n))
+            }
+            Exp::Named(n, e) => {
+                self.str(&print_name(n));
+                self.str(": ");
+                self.exp(e)
+            }
+            Exp::Located(l, e) => {
+                self.location(l);
+                self.str(": ");
+                self.exp(e)
+            }
+            Exp::Merkle(e) => self.call("merkle", e),
+            Exp::MerkleFrom(e) => self.call("merkle_from", e),
+            Exp::Quote(e) => self.call("quote", e),
+            Exp::FixQuote(f, e) => {
+                self.str("fix_quote(");
+                self.str(&var_lit(f));
+                self.str(", ");
+                self.exp(e);
+                self.str(")")
+            }
+            Exp::StoreFrom(n, e) => {
+                self.str("store_from(");
+                self.str(&print_name(n));
+                self.str(", ");
+                self.exp(e);
+                self.str(")")
+            }
+            Exp::Command(c) => {
+                self.str("command ");
+                self.str(&serde_json::to_string(c).unwrap())
+            }
+            Exp::Block(b) => self.block(&b.bindings),
+            Exp::Var(n) => self.str(&var_lit(n)),
+            Exp::Put(n, e) => {
+                self.str("put ");
+                self.str(&print_name(n));
+                self.str(" ");
+                self.exp(e)
+            }
+            Exp::Thunk(n, bindings) => {
+                self.str("thunk ");
+                self.str(&print_name(n));
+                self.str(" ");
+                self.block(bindings)
+            }
+            Exp::Get(e) => {
+                self.str("get ");
+                self.exp(e)
+            }
+        }
+    }
+
+    fn call(&mut self, f: &str, exp: &Exp) {
+        self.str(f);
+        self.str("(");
+        self.exp(exp);
+        self.str(")")
+    }
+
+    fn location(&mut self, loc: &Location) {
+        self.str("loc(");
+        self.str(&print_name(&loc.time));
+        self.str(", ");
+        self.str(&print_name(&loc.place));
+        self.str(")")
+    }
+
+    fn block(&mut self, bindings: &[(Name, Exp)]) {
+        if bindings.is_empty() {
+            return self.str("{}");
+        };
+        self.str("{");
+        self.indent += 1;
+        for (i, (x, e)) in bindings.iter().enumerate() {
+            self.newline();
+            self.str("let ");
+            self.str(&var_lit(x));
+            self.str(" = ");
+            self.exp(e);
+            if i + 1 < bindings.len() {
+                self.str(";")
+            }
+        }
+        self.indent -= 1;
+        self.newline();
+        self.str("}")
+    }
+
+    /// one row of digits per line, as in `parse_glyph!`
+    fn bitmap(&mut self, b: &bitmap::Bitmap) {
+        self.str("bitmap");
+        if b.major == bitmap::Major::Col {
+            self.str(" col")
+        };
+        self.str("[");
+        self.indent += 1;
+        for y in 0..b.height {
+            self.newline();
+            for x in 0..b.width {
+                if x > 0 {
+                    self.str(" ")
+                };
+                let bit = match b.major {
+                    bitmap::Major::Row => b.bits[y][x],
+                    bitmap::Major::Col => b.bits[x][y],
+                };
+                self.str(if bit { "1" } else { "0" })
+            }
+            if y + 1 < b.height || b.width == 0 {
+                self.str(";")
+            }
+        }
+        self.indent -= 1;
+        if b.height > 0 {
+            self.newline()
+        };
+        self.str("]")
+    }
+
+    /// the palette, then one row of hex digits per line
+    fn pixmap(&mut self, p: &pixmap::Pixmap) {
+        let colors: Vec<String> = p
+            .palette
+            .iter()
+            .map(|Color::RGB(r, g, b)| format!("rgb({}, {}, {})", r, g, b))
+            .collect();
+        self.str(&format!("pixmap({})[", colors.join(", ")));
+        self.indent += 1;
+        for (y, row) in p.pixels.iter().enumerate() {
+            self.newline();
+            let digits: Vec<String> = row.iter().map(|c| format!("{:x}", c)).collect();
+            self.str(&digits.join(" "));
+            if y + 1 < p.height || row.is_empty() {
+                self.str(";")
+            }
+        }
+        self.indent -= 1;
+        if p.height > 0 {
+            self.newline()
+        };
+        self.str("]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_exp, parse_media, print_exp, print_media};
+    use hashcons::merkle::Merkle;
+    use types::lang::{Atom, Block, Command, Exp, Location, Media, Name, Store};
+    use types::render::Color;
+    use types::util::{name_of_str, name_of_usize};
+    use {bitmap, chain, grid, pixmap};
+
+    fn round_trip_exp(exp: Exp) {
+        let text = print_exp(&exp);
+        assert_eq!(parse_exp(&text), Ok(exp), "{}", text)
+    }
+
+    fn round_trip_media(media: Media) {
+        let text = print_media(&media);
+        assert_eq!(parse_media(&text), Ok(media), "{}", text)
+    }
+
+    fn num(n: usize) -> Media {
+        Media::Atom(Atom::Usize(n))
+    }
+
+    fn names() -> Vec<Name> {
+        let tuple = Name::TaggedTuple(
+            Box::new(name_of_str("tag")),
+            vec![Name::Void, name_of_usize(3)],
+        );
+        vec![
+            Name::Void,
+            name_of_str("a"),
+            name_of_str("not an ident"),
+            name_of_str("true"),
+            name_of_str("put"),
+            Name::Atom(Atom::Bool(false)),
+            name_of_usize(42),
+            tuple.clone(),
+            Name::TaggedTuple(Box::new(tuple.clone()), vec![name_of_str("x")]),
+            Name::TaggedTuple(
+                Box::new(Name::Merkle(Merkle::new(name_of_str("m")))),
+                vec![],
+            ),
+            Name::Merkle(Merkle::new(tuple)),
+        ]
+    }
+
+    fn bitmap(width: usize, height: usize, major: bitmap::Major) -> bitmap::Bitmap {
+        let (outer, inner) = match major {
+            bitmap::Major::Row => (height, width),
+            bitmap::Major::Col => (width, height),
+        };
+        bitmap::Bitmap {
+            width,
+            height,
+            major,
+            bits: (0..outer)
+                .map(|i| (0..inner).map(|j| (i + j) % 2 == 0).collect())
+                .collect(),
+        }
+    }
+
+    fn pixmap(width: usize, height: usize) -> pixmap::Pixmap {
+        pixmap::Pixmap {
+            width,
+            height,
+            palette: vec![Color::RGB(0, 0, 0), Color::RGB(255, 128, 1)],
+            pixels: (0..height)
+                .map(|y| (0..width).map(|x| (x + y) % 2).collect())
+                .collect(),
+        }
+    }
+
+    fn grid(width: usize, height: usize) -> grid::Grid {
+        grid::Grid {
+            width,
+            height,
+            cells: (0..height)
+                .map(|y| (0..width).map(|x| num(x + y)).collect())
+                .collect(),
+        }
+    }
+
+    fn store() -> Store {
+        Store {
+            name: Merkle::new(name_of_str("s")),
+            table: vec![
+                (Merkle::new(name_of_str("a")), Merkle::new(num(1))),
+                (Merkle::new(name_of_usize(2)), Merkle::new(Media::Void)),
+            ],
+        }
+    }
+
+    /// a media value of each form, including the empty and zero-width ones
+    fn medias() -> Vec<Media> {
+        let mut chain = chain::Chain::Empty;
+        for (i, m) in [num(1), Media::Void].iter().enumerate() {
+            chain.push_node(chain::Node {
+                name: name_of_usize(i),
+                media: Box::new(m.clone()),
+            })
+        }
+        let loc = Location {
+            time: name_of_str("t"),
+            place: name_of_usize(0),
+        };
+        let mut medias = vec![
+            Media::Void,
+            Media::Atom(Atom::Bool(true)),
+            num(0),
+            Media::Atom(Atom::String("a \"quoted\"\n\\ string".to_string())),
+            Media::Location(loc.clone()),
+            Media::Chain(Box::new(chain::Chain::Empty)),
+            Media::Chain(Box::new(chain)),
+            Media::Store(store()),
+            Media::Store(Store {
+                name: Merkle::new(Name::Void),
+                table: vec![],
+            }),
+            Media::StoreProj(store(), name_of_str("a")),
+            Media::Named(name_of_str("n"), Box::new(Media::Store(store()))),
+            Media::Located(loc, Box::new(num(1))),
+            Media::Merkle(Merkle::new(num(1))),
+            Media::Quote(Box::new(Exp::Var(name_of_str("x")))),
+        ];
+        medias.extend(names().into_iter().map(Media::Name));
+        // media without rows parse with zero width, so no `(1, 0)` here
+        for (w, h) in [(0, 0), (0, 1), (0, 2), (1, 1), (3, 2)].iter() {
+            for major in [bitmap::Major::Row, bitmap::Major::Col].iter() {
+                medias.push(Media::Bitmap(Box::new(bitmap(*w, *h, major.clone()))))
+            }
+            medias.push(Media::Pixmap(Box::new(pixmap(*w, *h))));
+            medias.push(Media::Grid(Box::new(grid(*w, *h))));
+        }
+        medias
+    }
+
+    #[test]
+    fn media_round_trips() {
+        for media in medias() {
+            round_trip_media(media)
+        }
+    }
+
+    #[test]
+    fn exp_round_trips() {
+        let x = Exp::Var(name_of_str("x"));
+        let bx = |e: &Exp| Box::new(e.clone());
+        let block = vec![
+            (name_of_str("x"), Exp::Atom(Atom::Usize(1))),
+            (name_of_str("let"), x.clone()),
+        ];
+        let get = Exp::Get(bx(&x));
+        let mut exps = vec![
+            Exp::Block(Block { bindings: vec![] }),
+            Exp::Block(Block {
+                bindings: block.clone(),
+            }),
+            Exp::Var(name_of_str("void")),
+            Exp::Var(name_of_usize(7)),
+            Exp::MerkleFrom(bx(&x)),
+            Exp::StoreFrom(name_of_str("s"), bx(&x)),
+            Exp::Quote(bx(&get)),
+            Exp::FixQuote(name_of_str("f"), bx(&Exp::Var(name_of_str("f")))),
+            Exp::Command(Command::Grid(grid::Command::Init(grid::InitCommand::Make(
+                2, 3,
+            )))),
+            get.clone(),
+            Exp::Thunk(name_of_str("t"), vec![]),
+            Exp::Thunk(name_of_str("t"), block),
+            Exp::Merkle(Merkle::new(get.clone())),
+        ];
+        // names, and prefix forms, before a projection or as the body of `put`
+        for n in names() {
+            exps.push(Exp::Put(n.clone(), bx(&x)));
+            exps.push(Exp::StoreProj(bx(&Exp::Name(n.clone())), n.clone()));
+        }
+        let prefixed = vec![
+            get.clone(),
+            Exp::Put(name_of_str("p"), bx(&x)),
+            Exp::Thunk(name_of_str("t"), vec![]),
+            Exp::Named(name_of_str("n"), bx(&x)),
+            Exp::Located(
+                Location {
+                    time: Name::Void,
+                    place: Name::Void,
+                },
+                bx(&x),
+            ),
+        ];
+        for e in prefixed {
+            let proj = Exp::StoreProj(bx(&e), name_of_str("b"));
+            exps.push(Exp::Put(name_of_str("n"), bx(&proj)));
+            exps.push(Exp::Put(
+                Name::TaggedTuple(Box::new(name_of_str("n")), vec![Name::Void]),
+                bx(&proj),
+            ));
+            exps.push(Exp::Get(bx(&proj)));
+            exps.push(proj);
+        }
+        exps.extend(medias().iter().map(super::exp_of_media));
+        for exp in exps {
+            round_trip_exp(exp)
+        }
+    }
+
+    #[test]
+    fn grid_rows() {
+        assert_eq!(print_media(&Media::Grid(Box::new(grid(0, 2)))), "grid[; ;]");
+        assert_eq!(
+            print_media(&Media::Grid(Box::new(grid(2, 2)))),
+            "grid[0, 1; 1, 2]"
+        );
+        for text in ["grid[0, 1; 1, 2]", "grid[0, 1; 1, 2;]"].iter() {
+            assert_eq!(parse_media(text), Ok(Media::Grid(Box::new(grid(2, 2)))));
+        }
+        assert!(parse_media("grid[0, 1; 1]").is_err());
+    }
+}
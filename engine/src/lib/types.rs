@@ -1,18 +1,19 @@
 /// The ZQM language: abstract syntax
 pub mod lang {
-    use crate::{bitmap, chain, grid, menu};
+    use crate::{adapton, bitmap, chain, grid, menu, pixmap};
     use hashcons::merkle::Merkle;
     use serde::{Deserialize, Serialize};
 
     /// Media combines words and images
     /// (eventually, we add sound and moving images)
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub enum Media {
         Void,
         Atom(Atom),
         Name(Name),
         Location(Location),
         Bitmap(Box<bitmap::Bitmap>),
+        Pixmap(Box<pixmap::Pixmap>),
         Chain(Box<chain::Chain>),
         Grid(Box<grid::Grid>),
         Store(Store),
@@ -33,7 +34,7 @@ pub mod lang {
     */
 
     /// We lift Media to an expression language, with media operations, and adapton operations
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub enum Exp {
         //----------------------------------------------------------------
         // Media forms (think "data values" in the PL sense):
@@ -43,6 +44,7 @@ pub mod lang {
         Name(Name),
         Location(Location),
         Bitmap(Box<bitmap::Bitmap>),
+        Pixmap(Box<pixmap::Pixmap>),
         Chain(Box<chain::Chain>),
         Grid(Box<grid::Grid>),
         Store(Store),
@@ -59,6 +61,10 @@ pub mod lang {
         //----------------------------------------------------------------
         MerkleFrom(Box<Exp>),
         StoreFrom(Name, Box<Exp>),
+        /// An editor command, whose input is the media of the _last_ binding in scope
+        /// (not the nearest one of the command's kind), e.g., `b` in
+        /// `{ let b = bitmap[..]; let r = command {"Bitmap": ..} }`;
+        /// an `Init` command has no input
         Command(Command),
         Block(Block),
         Var(Name),
@@ -73,16 +79,48 @@ pub mod lang {
     /// The data result produced by running a command
     pub type Result = std::result::Result<Media, Error>;
 
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
-    pub enum Error {}
+    /// errors shared by the evaluators of media, editors and expressions
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
+    pub enum Error {
+        /// variable not bound by the environment
+        Unbound(Name),
+        /// expected media of the described kind, but found other media
+        TypeMismatch(String, Media),
+        /// command for one kind of editor (first), given to another kind (second)
+        EditorMismatch(String, String),
+        /// editor has no state; it expects an `Init` command first
+        NoEditorState,
+        /// coordinate (of any dimension) is outside the bounds of the media
+        OutOfBounds(Vec<usize>),
+        /// name is absent from the store
+        AbsentName(Name),
+        /// failure of a chain operation
+        Chain(chain::AutoError),
+        /// expected a menu tree with the first tag, but found the second
+        MenuTag(menu::Tag, menu::Tag),
+        /// menu tree that is ill-typed or incomplete, at some path
+        Menu(menu::Error),
+        /// command does not apply to the current media or editor state
+        Invalid(String),
+        /// failure to update a DCG node
+        Put(adapton::PutError),
+        /// failure to demand a DCG node (e.g., an undefined node, or a cycle)
+        Get(adapton::GetError),
+        /// failure to read or write persistent state
+        Io(String),
+        /// failure to parse text, at a line and column, expecting the described syntax
+        Syntax(usize, usize, String),
+        /// failure to decode binary data, at a byte offset, expecting the described data
+        Binary(usize, String),
+    }
 
     /// an expression block consists of a sequence of bindings
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub struct Block {
         pub bindings: Vec<(Name, Exp)>,
     }
 
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub struct Store {
         //pub name: Rc<Name>,
         pub name: Merkle<Name>,
@@ -92,7 +130,7 @@ pub mod lang {
         pub table: Vec<(Merkle<Name>, Merkle<Media>)>, // todo: use hashcons crate for this
     }
 
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub struct StoreRecord {
         //pub name: Rc<Name>,
         //pub content: Rc<Media>,
@@ -103,6 +141,7 @@ pub mod lang {
     #[derive(Debug, Serialize, Deserialize, Hash)]
     pub enum Editor {
         Bitmap(Box<bitmap::Editor>),
+        Pixmap(Box<pixmap::Editor>),
         Menu(Box<menu::Editor>),
         Chain(Box<chain::Editor>),
         Grid(Box<grid::Editor>),
@@ -110,21 +149,22 @@ pub mod lang {
 
     // to do -- eventually, we may want these to be "open" wrt the exp environment;
     // for expressing scripts, etc; then we'd need to do substitution, or more env-passing, or both.
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
     pub enum Command {
         Menu(menu::Command),
         Bitmap(bitmap::Command),
+        Pixmap(pixmap::Command),
         Chain(chain::Command),
         Grid(grid::Command),
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
     pub enum Dir1D {
         Forward,
         Backward,
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
     pub enum Dir2D {
         Up,
         Down,
@@ -158,7 +198,7 @@ pub mod lang {
         // Eventually(as of 2020-01-04): Permit Media to name Media.
     }
 
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub struct Location {
         pub time: Name,
         pub place: Name,
@@ -169,17 +209,17 @@ pub mod lang {
 // See design/AdaptonDesign.md for details.
 
 pub mod adapton {
-    use super::lang::{Exp, Media, Name, Result as EvalResult};
+    use super::lang::{Exp, Map, Media, Name, Result as EvalResult};
     use crate::adapton;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
     /// media-naming environments
     pub struct Env {
         pub bindings: Vec<(Name, Media)>,
     }
     /// media-producing closures
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq)]
     pub struct Closure {
         pub env: Env,
         pub exp: Exp,
@@ -229,7 +269,7 @@ pub mod adapton {
         /// allocate/overwrite a thunk node with the given media-producing closure
         Thunk(Closure),
         /// demand/observe the media content of a ref/thunk node
-        Get(Media),
+        Get(EvalResult),
     }
     /// The public type exposed by ref and thunk allocation
     #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
@@ -246,11 +286,12 @@ pub mod adapton {
         Archivist,
         Editor,
     }
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
-    pub struct Store(pub Vec<(Name, Node)>);
+    /// each name determines (at most) one node; a put replaces the node in place
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Store(pub Map<Name, Node>);
     #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
     pub struct Stack(pub Vec<Name>);
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Context {
         pub agent: Agent,
         pub edges: Vec<Edge>,
@@ -285,7 +326,7 @@ pub mod adapton {
     pub enum LogEvent {
         Put(Name, Media, LogEvents),
         PutThunk(Name, Closure, LogEvents),
-        Get(Name, Closure, LogEvents),
+        Get(Name, EvalResult, LogEvents),
         DirtyIncomingTo(Name, LogEvents),
         CleanEdgeTo(Name, bool, LogEvents),
         CleanThunk(Name, bool, LogEvents),
@@ -294,7 +335,7 @@ pub mod adapton {
     pub enum LogEventTag {
         Put(Name, Media),
         PutThunk(Name, Closure),
-        Get(Name, Closure),
+        Get(Name, EvalResult),
         DirtyIncomingTo(Name),
         CleanEdgeTo(Name, bool),
         CleanThunk(Name, bool),
@@ -315,6 +356,10 @@ pub mod event {
         KeyDown(KeyEventInfo),
         KeyUp(KeyEventInfo),
     }
+
+    /// the user asks to quit the editor (e.g., by closing its window, or pressing Escape)
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Quit;
     #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
     pub struct KeyEventInfo {
         pub key: String,
@@ -330,7 +375,7 @@ pub mod render {
     use super::lang::Name;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+    #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq)]
     pub enum Color {
         RGB(usize, usize, usize),
     }
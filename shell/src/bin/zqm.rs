@@ -15,13 +15,13 @@ extern crate structopt;
 use structopt::StructOpt;
 
 use sdl2::event::Event as SysEvent;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 use std::io;
 
 // ZQM:
 extern crate zqm_engine;
 use zqm_engine::{
-    eval,
+    adapton, candid, eval, http, init, menu, syntax,
     types::{self, event, render},
 };
 
@@ -59,6 +59,47 @@ enum CliCommand {
     )]
     History,
 
+    #[structopt(
+        name = "candid",
+        about = "Edit a value of a Candid type, loaded from a .did file."
+    )]
+    Candid {
+        file: String,
+        /// The type to edit (by default, the service, or else the last type defined).
+        #[structopt(long = "type")]
+        type_name: Option<String>,
+    },
+
+    #[structopt(name = "eval", about = "Evaluate a script file, and print its result.")]
+    Eval { file: String },
+
+    #[structopt(
+        name = "push",
+        about = "Save the last interaction's state to a canister."
+    )]
+    Push {
+        #[structopt(flatten)]
+        endpoint: EndpointOpt,
+    },
+
+    #[structopt(
+        name = "pull",
+        about = "Load the state saved in a canister, to resume interaction."
+    )]
+    Pull {
+        #[structopt(flatten)]
+        endpoint: EndpointOpt,
+    },
+
+    #[structopt(
+        name = "serve",
+        about = "Serve a local stand-in for a canister that saves states."
+    )]
+    Serve {
+        #[structopt(long = "port", default_value = "8000")]
+        port: u16,
+    },
+
     #[structopt(name = "version", about = "Display version.")]
     Version,
 
@@ -69,6 +110,19 @@ enum CliCommand {
     Completions { shell: Shell },
 }
 
+#[derive(StructOpt, Debug)]
+struct EndpointOpt {
+    /// The server to connect to.
+    #[structopt(long = "url", default_value = "http://127.0.0.1:8000")]
+    url: String,
+    /// The canister that saves states.
+    #[structopt(long = "canister", default_value = "zqm")]
+    canister: String,
+    /// The name of the saved state.
+    #[structopt(long = "name", default_value = "state")]
+    name: String,
+}
+
 fn init_log(level_filter: log::LevelFilter) {
     use env_logger::{Builder, WriteStyle};
     let mut builder = Builder::new();
@@ -155,39 +209,64 @@ pub fn draw_elms<T: RenderTarget>(
     Ok(())
 }
 
-fn translate_system_event(event: SysEvent) -> Option<event::Event> {
+fn translate_system_event(event: SysEvent) -> Vec<event::Event> {
+    fn key_down(key: String) -> event::Event {
+        event::Event::KeyDown(event::KeyEventInfo {
+            key,
+            alt: false,
+            ctrl: false,
+            meta: false,
+            shift: false,
+        })
+    }
     match &event {
-        SysEvent::Quit { .. }
-        | SysEvent::KeyDown {
-            keycode: Some(Keycode::Escape),
-            ..
-        } => Some(event::Event::Quit),
+        SysEvent::Quit { .. } => vec![event::Event::Quit],
+        // printable keys (including space) arrive as text input, one event per character
+        SysEvent::TextInput { ref text, .. } => {
+            text.chars().map(|c| key_down(c.to_string())).collect()
+        }
         SysEvent::KeyDown {
             keycode: Some(ref kc),
+            keymod,
             ..
         } => {
+            let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+            let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
             let key = match &kc {
+                Keycode::Escape => "Escape".to_string(),
                 Keycode::Tab => "Tab".to_string(),
-                Keycode::Space => " ".to_string(),
                 Keycode::Return => "Enter".to_string(),
                 Keycode::Left => "ArrowLeft".to_string(),
                 Keycode::Right => "ArrowRight".to_string(),
                 Keycode::Up => "ArrowUp".to_string(),
                 Keycode::Down => "ArrowDown".to_string(),
                 Keycode::Backspace => "Backspace".to_string(),
-                keycode => format!("unrecognized({:?})", keycode),
+                Keycode::Delete => "Delete".to_string(),
+                Keycode::Home => "Home".to_string(),
+                Keycode::End => "End".to_string(),
+                Keycode::Insert => "Insert".to_string(),
+                Keycode::PageUp => "PageUp".to_string(),
+                Keycode::PageDown => "PageDown".to_string(),
+                // with control held, a letter names a command (and enters no text), as in the browser
+                kc if ctrl && kc.name().len() == 1 => {
+                    if shift {
+                        kc.name().to_uppercase()
+                    } else {
+                        kc.name().to_lowercase()
+                    }
+                }
+                _ => return vec![],
             };
-            let event = event::Event::KeyDown(event::KeyEventInfo {
+            vec![event::Event::KeyDown(event::KeyEventInfo {
                 key: key,
-                // to do -- translate modifier keys,
+                // to do -- translate the other modifier keys,
                 alt: false,
-                ctrl: false,
+                ctrl,
                 meta: false,
-                shift: false,
-            });
-            Some(event)
+                shift,
+            })]
         }
-        _ => None,
+        _ => vec![],
     }
 }
 
@@ -237,35 +316,43 @@ pub fn do_event_loop(state: &mut types::lang::State) -> Result<(), String> {
     event_pump.disable_event(EventType::MouseMotion);
 
     'running: loop {
-        let event = translate_system_event(event_pump.wait_event());
-        let event = match event {
-            None => continue 'running,
-            Some(event) => event,
-        };
-        match eval::commands_of_event(state, &event) {
-            Ok(commands) => {
-                for c in commands.iter() {
-                    // note: we borrow the command here, possibly requiring some cloning when it is evaluated.
-                    // todo -- we do nothing with the result; we should log it.
-                    match eval::command_eval(state, c) {
-                        Ok(()) => {}
-                        Err(msg) => {
-                            warn!("Command {:?} lead to an error:", c);
-                            error!("{}", msg)
+        for event in translate_system_event(event_pump.wait_event()) {
+            match eval::commands_of_event(state, &event) {
+                Ok(commands) => {
+                    for c in commands.iter() {
+                        // note: we borrow the command here, possibly requiring some cloning when it is evaluated.
+                        // todo -- we do nothing with the result; we should log it.
+                        match eval::command_eval(state, c) {
+                            Ok(()) => {}
+                            Err(err) => {
+                                warn!("Command {:?} lead to an error:", c);
+                                error!("{:?}", err)
+                            }
                         }
                     }
+                    let elms = eval::render_elms(state)?;
+                    draw_elms(&mut canvas, &pos, &dim, &fill, &elms)?;
+                    canvas.present();
+                    drop(elms);
                 }
-                let elms = eval::render_elms(state)?;
-                draw_elms(&mut canvas, &pos, &dim, &fill, &elms)?;
-                canvas.present();
-                drop(elms);
+                Err(event::Quit) => break 'running,
             }
-            Err(()) => break 'running,
         }
     }
     Ok(())
 }
 
+/// report the error (if any) and exit with a non-zero status
+fn or_exit<X, E: std::fmt::Debug>(res: Result<X, E>) -> X {
+    match res {
+        Ok(x) => x,
+        Err(err) => {
+            error!("{:?}", err);
+            std::process::exit(1)
+        }
+    }
+}
+
 fn main() {
     let cliopt = CliOpt::from_args();
     init_log(
@@ -277,7 +364,7 @@ fn main() {
         },
     );
 
-    let mut state = eval::load_state();
+    let mut state = or_exit(eval::load_state());
 
     info!("Evaluating CLI command: {:?} ...", &cliopt.command);
 
@@ -293,12 +380,80 @@ fn main() {
             info!("done")
         }
         CliCommand::Start => {
-            do_event_loop(&mut state).unwrap();
-            eval::save_state(&state);
+            or_exit(do_event_loop(&mut state));
+            or_exit(eval::save_state(&state));
         }
         CliCommand::Resume => {
-            do_event_loop(&mut state).unwrap();
-            eval::save_state(&state);
+            or_exit(do_event_loop(&mut state));
+            or_exit(eval::save_state(&state));
+        }
+        CliCommand::Candid { file, type_name } => {
+            let prog = or_exit(
+                std::fs::read_to_string(&file)
+                    .map_err(|err| types::lang::Error::Io(err.to_string()))
+                    .and_then(|text| candid::parse_prog(&text)),
+            );
+            let type_name = type_name.as_deref();
+            let mut state = or_exit(init::init_state_of_candid_prog(&prog, type_name));
+            or_exit(do_event_loop(&mut state));
+            or_exit(eval::save_state(&state));
+            // print the edited value, for pasting into other Candid tools;
+            // for a service, print the chosen method, its arguments, and their encoding.
+            if let types::lang::Editor::Menu(ref ed) = state.editor {
+                if let Some(ref st) = ed.state {
+                    let value = menu::semantics::root_tree(st).and_then(|(tree, typ)| {
+                        if type_name.is_none() && prog.service.is_some() {
+                            let call = prog.call_of_tree(&tree)?;
+                            // the textual arguments, from the encoded ones (and the method's types)
+                            let typs = prog
+                                .get_method(&call.method)
+                                .map(|m| m.args.clone())
+                                .unwrap_or_default();
+                            let trees = candid::binary::decode_as(&call.args, &typs)?;
+                            let args: Vec<_> = trees.into_iter().zip(typs).collect();
+                            let hex: String =
+                                call.args.iter().map(|b| format!("{:02x}", b)).collect();
+                            Ok(format!(
+                                "{} {}\n{}",
+                                call.method,
+                                candid::print_args(&args)?,
+                                hex
+                            ))
+                        } else {
+                            candid::print_value(&tree, &typ)
+                        }
+                    });
+                    println!("{}", or_exit(value));
+                }
+            }
+        }
+        CliCommand::Eval { file } => {
+            let res = std::fs::read_to_string(&file)
+                .map_err(|err| types::lang::Error::Io(err.to_string()))
+                .and_then(|text| syntax::parse_exp(&text))
+                .and_then(|exp| {
+                    let env = types::adapton::Env { bindings: vec![] };
+                    eval::eval_exp(&env, &mut adapton::init(), &exp)
+                });
+            println!("{}", syntax::print_media(&or_exit(res)));
+        }
+        CliCommand::Push { endpoint: e } => {
+            let res = http::Endpoint::new(&e.url, &e.canister)
+                .and_then(|endpoint| http::push_state(&endpoint, &e.name, &state));
+            or_exit(res);
+            info!("done")
+        }
+        CliCommand::Pull { endpoint: e } => {
+            let res = http::Endpoint::new(&e.url, &e.canister)
+                .and_then(|endpoint| http::pull_state(&endpoint, &e.name))
+                .and_then(|state| eval::save_state(&state));
+            or_exit(res);
+            info!("done")
+        }
+        CliCommand::Serve { port } => {
+            let mut server = or_exit(http::mock::Server::bind(&format!("127.0.0.1:{}", port)));
+            info!("Serving at http://127.0.0.1:{} ...", port);
+            or_exit(server.serve(None))
         }
         CliCommand::Replay => unimplemented!(),
         CliCommand::History => unimplemented!(),
@@ -137,8 +137,19 @@ pub fn main() -> Result<(), JsValue> {
         let render_elms = {
             // translate each system event into zero, one or more in the engine's format.
             let events = match format!("{}", event.key()).as_str() {
-                "Tab" | "Escape" | "ArrowUp" | "ArrowDown" | "ArrowLeft" | "ArrowRight" | " "
-                | "Backspace" => vec![Event::KeyDown(KeyEventInfo {
+                "Tab" | "Escape" | "ArrowUp" | "ArrowDown" | "ArrowLeft" | "ArrowRight"
+                | "Enter" | "Backspace" | "Delete" | "Home" | "End" | "Insert" | "PageUp"
+                | "PageDown" => {
+                    vec![Event::KeyDown(KeyEventInfo {
+                        key: event.key(),
+                        alt: event.alt_key(),
+                        ctrl: event.ctrl_key(),
+                        shift: event.shift_key(),
+                        meta: event.meta_key(),
+                    })]
+                }
+                // printable keys (including " ") name the character that they enter
+                key if key.chars().count() == 1 => vec![Event::KeyDown(KeyEventInfo {
                     key: event.key(),
                     alt: event.alt_key(),
                     ctrl: event.ctrl_key(),